@@ -0,0 +1,116 @@
+use solana_program::program_error::ProgramError;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MktError {
+    #[error("Account is already initialized")]
+    AlreadyInitialized,
+
+    #[error("Account is not initialized")]
+    UninitializedAccount,
+
+    #[error("Signer is not the account authority")]
+    InvalidAuthority,
+
+    #[error("Campaign end time must be after its start time")]
+    InvalidSchedule,
+
+    #[error("Campaign is not active")]
+    CampaignInactive,
+
+    #[error("Reward pool is invalid")]
+    InvalidRewardPool,
+
+    #[error("Reward program is not active")]
+    RewardProgramInactive,
+
+    #[error("Reward pool exhausted")]
+    RewardPoolExhausted,
+
+    #[error("Reward type cannot be paid out on-chain")]
+    UnsupportedRewardType,
+
+    #[error("Arithmetic overflow")]
+    Overflow,
+
+    #[error("Account address does not match the expected program-derived address")]
+    InvalidAccountAddress,
+
+    #[error("Campaign id exceeds the maximum length")]
+    IdTooLong,
+
+    #[error("Campaign title exceeds the maximum length")]
+    TitleTooLong,
+
+    #[error("Campaign creator exceeds the maximum length")]
+    CreatorTooLong,
+
+    #[error("Campaign metadata URI exceeds the maximum length")]
+    MetadataUriTooLong,
+
+    #[error("Account uses an outdated layout and must be migrated")]
+    AccountNeedsMigration,
+
+    #[error("Account already uses the current layout")]
+    AccountAlreadyMigrated,
+
+    #[error("Amount must be greater than zero")]
+    InvalidAmount,
+
+    #[error("Campaign is not accepting contributions at this time")]
+    OutsideCampaignWindow,
+
+    #[error("Campaign is still running")]
+    CampaignNotEnded,
+
+    #[error("Campaign reached its target, so contributions are not refundable")]
+    TargetMet,
+
+    #[error("Campaign has not reached its target")]
+    TargetNotMet,
+
+    #[error("Campaign has more milestones than fit in its account")]
+    TooManyMilestones,
+
+    #[error("Milestone description exceeds the maximum length")]
+    MilestoneDescriptionTooLong,
+
+    #[error("Milestones need an amount, a deadline after the campaign starts and must fit within the target")]
+    InvalidMilestone,
+
+    #[error("Milestone approval needs a valid approver or a quorum between 1 and 10000 basis points")]
+    InvalidMilestoneApproval,
+
+    #[error("Campaign has no milestone at that index")]
+    InvalidMilestoneIndex,
+
+    #[error("Milestone is not in the right state for this operation")]
+    InvalidMilestoneStatus,
+
+    #[error("Milestone deadline has passed")]
+    MilestoneDeadlinePassed,
+
+    #[error("Contributor already voted for this milestone")]
+    AlreadyVoted,
+
+    #[error("Campaign funds are released by milestone")]
+    MilestonesPending,
+
+    #[error("Mint is not a valid SPL token mint")]
+    InvalidMint,
+
+    #[error("Token account does not match the campaign's mint")]
+    InvalidTokenAccount,
+
+    #[error("Campaign cannot move from its current status to the requested one")]
+    InvalidStatusTransition,
+
+    #[error("Campaign was cancelled")]
+    CampaignCancelled,
+}
+
+impl From<MktError> for ProgramError {
+    fn from(e: MktError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
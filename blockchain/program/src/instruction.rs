@@ -0,0 +1,632 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+use spl_associated_token_account::get_associated_token_address;
+
+use crate::{
+    pda::{find_contribution_address, find_milestone_vote_address, find_vault_address},
+    state::{CampaignData, CampaignMetrics, CampaignStatus, RewardProgram},
+};
+
+/// Instructions understood by the MKT4U program.
+///
+/// The Borsh enum tag doubles as the instruction discriminator, so variants
+/// must only ever be appended.
+///
+/// Campaigns raising an SPL mint keep their escrow in the vault PDA's
+/// associated token account. Their escrow instructions take the token
+/// accounts listed after the lamport ones; lamport campaigns leave them out.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum MktInstruction {
+    /// Allocate and initialize a campaign account.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign PDA, see `pda::find_campaign_address`
+    /// 1. `[writable, signer]` Payer, funds rent and is recorded as the campaign authority
+    /// 2. `[]` System program
+    /// 3. `[]` Mint, for token campaigns
+    CreateCampaign { campaign: CampaignData },
+
+    /// Overwrite the metrics of an active or paused campaign.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign account
+    /// 1. `[signer]` Campaign authority
+    UpdateMetrics { metrics: CampaignMetrics },
+
+    /// Complete a campaign and withdraw lamports above its rent-exempt reserve.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign account
+    /// 1. `[writable, signer]` Campaign authority
+    /// 2. `[]` System program
+    CloseCampaign,
+
+    /// Allocate a reward program account and fund its pool.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Reward program PDA, see `pda::find_reward_program_address`
+    /// 1. `[writable, signer]` Payer, funds rent plus the SOL pool and becomes the authority
+    /// 2. `[]` System program
+    CreateRewardProgram { program: RewardProgram },
+
+    /// Pay a reward out of a program's pool.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Reward program account
+    /// 1. `[writable]` Recipient wallet
+    /// 2. `[signer]` Reward program authority
+    /// 3. `[]` System program
+    ClaimReward { user_id: String },
+
+    /// Rewrite a campaign stored in an older layout into the current one,
+    /// growing the account and topping up rent as needed.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign account
+    /// 1. `[writable, signer]` Campaign authority, funds any additional rent
+    /// 2. `[]` System program
+    MigrateCampaign,
+
+    /// Move lamports from a contributor into an active campaign's vault and
+    /// add them to its `current_amount` and the contributor's receipt. The
+    /// vault and receipt are created on first use, funded by the contributor.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign account
+    /// 1. `[writable]` Vault PDA, see `pda::find_vault_address`
+    /// 2. `[writable]` Contribution receipt PDA, see `pda::find_contribution_address`
+    /// 3. `[writable, signer]` Contributor
+    /// 4. `[]` System program
+    /// 5. `[writable]` Contributor's associated token account, for token campaigns
+    /// 6. `[writable]` Vault's associated token account, created on first use
+    /// 7. `[]` Mint
+    /// 8. `[]` Token program
+    /// 9. `[]` Associated token account program
+    Contribute { amount: u64 },
+
+    /// Return a contributor's lamports from the vault of a campaign that
+    /// ended below its target, and close their receipt. Their votes for
+    /// milestones not yet approved are withdrawn with it.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign account
+    /// 1. `[writable]` Vault PDA
+    /// 2. `[writable]` Contribution receipt PDA
+    /// 3. `[writable, signer]` Contributor, receives the refund and the receipt's rent
+    /// 4. `[writable]` Contributor's associated token account, for token campaigns
+    /// 5. `[writable]` Vault's associated token account
+    /// 6. `[]` Mint
+    /// 7. `[]` Token program
+    Refund,
+
+    /// Move everything in the vault above its rent-exempt reserve to the
+    /// campaign authority, once the campaign has ended having reached its
+    /// target, and mark it completed.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign account
+    /// 1. `[writable]` Vault PDA
+    /// 2. `[writable, signer]` Campaign authority
+    /// 3. `[writable]` Authority's associated token account, for token campaigns
+    /// 4. `[writable]` Vault's associated token account
+    /// 5. `[]` Mint
+    /// 6. `[]` Token program
+    WithdrawFunds,
+
+    /// Report a milestone of a campaign that ended funded as delivered so it
+    /// can be approved, before its deadline. The amount raised at this point
+    /// is what a contributor vote's quorum is measured against.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign account
+    /// 1. `[signer]` Campaign authority
+    SubmitMilestone { index: u8 },
+
+    /// Approve a submitted milestone. Under `MilestoneApproval::ContributorVote`
+    /// this records one backer's vote, weighted by their contribution, and the
+    /// milestone is approved once the quorum is reached.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign account
+    /// 1. `[writable, signer]` Approver: the campaign authority, the configured
+    ///    approver or a contributor, who funds the vote record
+    /// 2. `[writable]` Contribution receipt PDA of the approver, which records the vote
+    /// 3. `[writable]` Milestone vote PDA, see `pda::find_milestone_vote_address`
+    /// 4. `[]` System program
+    ApproveMilestone { index: u8 },
+
+    /// Move an approved milestone's amount from the vault to the campaign
+    /// authority.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign account
+    /// 1. `[writable]` Vault PDA
+    /// 2. `[writable, signer]` Campaign authority
+    /// 3. `[writable]` Authority's associated token account, for token campaigns
+    /// 4. `[writable]` Vault's associated token account
+    /// 5. `[]` Mint
+    /// 6. `[]` Token program
+    ReleaseMilestone { index: u8 },
+
+    /// Move a campaign to `status`, if `CampaignStatus::can_transition_to`
+    /// allows it: launch a draft, pause, resume, complete or cancel.
+    ///
+    /// Accounts:
+    /// 0. `[writable]` Campaign account
+    /// 1. `[signer]` Campaign authority
+    SetCampaignStatus { status: CampaignStatus },
+}
+
+impl MktInstruction {
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(input).map_err(|_| ProgramError::InvalidInstructionData)
+    }
+
+    pub fn pack(&self) -> Vec<u8> {
+        borsh::to_vec(self).expect("instruction serialization is infallible")
+    }
+}
+
+pub fn create_campaign(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    payer: &Pubkey,
+    campaign: CampaignData,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*campaign_account, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    // An unparseable mint is left for the program to reject
+    if let Ok(Some(mint)) = campaign.mint() {
+        accounts.push(AccountMeta::new_readonly(mint, false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: MktInstruction::CreateCampaign { campaign }.pack(),
+    }
+}
+
+pub fn update_metrics(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    authority: &Pubkey,
+    metrics: CampaignMetrics,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*campaign_account, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: MktInstruction::UpdateMetrics { metrics }.pack(),
+    }
+}
+
+pub fn close_campaign(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    authority: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*campaign_account, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: MktInstruction::CloseCampaign.pack(),
+    }
+}
+
+pub fn create_reward_program(
+    program_id: &Pubkey,
+    reward_program_account: &Pubkey,
+    payer: &Pubkey,
+    program: RewardProgram,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*reward_program_account, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: MktInstruction::CreateRewardProgram { program }.pack(),
+    }
+}
+
+pub fn claim_reward(
+    program_id: &Pubkey,
+    reward_program_account: &Pubkey,
+    recipient: &Pubkey,
+    authority: &Pubkey,
+    user_id: String,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*reward_program_account, false),
+            AccountMeta::new(*recipient, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: MktInstruction::ClaimReward { user_id }.pack(),
+    }
+}
+
+pub fn migrate_campaign(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    authority: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*campaign_account, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: MktInstruction::MigrateCampaign.pack(),
+    }
+}
+
+pub fn contribute(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    contributor: &Pubkey,
+    mint: Option<&Pubkey>,
+    amount: u64,
+) -> Instruction {
+    let (vault, _) = find_vault_address(program_id, campaign_account);
+    let (contribution, _) = find_contribution_address(program_id, campaign_account, contributor);
+    let mut accounts = vec![
+        AccountMeta::new(*campaign_account, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(contribution, false),
+        AccountMeta::new(*contributor, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if let Some(mint) = mint {
+        accounts.extend(token_accounts(&vault, contributor, mint));
+        accounts.push(AccountMeta::new_readonly(spl_associated_token_account::id(), false));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: MktInstruction::Contribute { amount }.pack(),
+    }
+}
+
+pub fn refund(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    contributor: &Pubkey,
+    mint: Option<&Pubkey>,
+) -> Instruction {
+    let (vault, _) = find_vault_address(program_id, campaign_account);
+    let (contribution, _) = find_contribution_address(program_id, campaign_account, contributor);
+    let mut accounts = vec![
+        AccountMeta::new(*campaign_account, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(contribution, false),
+        AccountMeta::new(*contributor, true),
+    ];
+    if let Some(mint) = mint {
+        accounts.extend(token_accounts(&vault, contributor, mint));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: MktInstruction::Refund.pack(),
+    }
+}
+
+pub fn withdraw_funds(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    authority: &Pubkey,
+    mint: Option<&Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: payout_accounts(program_id, campaign_account, authority, mint),
+        data: MktInstruction::WithdrawFunds.pack(),
+    }
+}
+
+pub fn submit_milestone(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    authority: &Pubkey,
+    index: u8,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*campaign_account, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: MktInstruction::SubmitMilestone { index }.pack(),
+    }
+}
+
+pub fn approve_milestone(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    approver: &Pubkey,
+    index: u8,
+) -> Instruction {
+    let (contribution, _) = find_contribution_address(program_id, campaign_account, approver);
+    let (vote, _) = find_milestone_vote_address(program_id, campaign_account, index, approver);
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*campaign_account, false),
+            AccountMeta::new(*approver, true),
+            AccountMeta::new(contribution, false),
+            AccountMeta::new(vote, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: MktInstruction::ApproveMilestone { index }.pack(),
+    }
+}
+
+pub fn release_milestone(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    authority: &Pubkey,
+    mint: Option<&Pubkey>,
+    index: u8,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: payout_accounts(program_id, campaign_account, authority, mint),
+        data: MktInstruction::ReleaseMilestone { index }.pack(),
+    }
+}
+
+pub fn set_campaign_status(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    authority: &Pubkey,
+    status: CampaignStatus,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(*campaign_account, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: MktInstruction::SetCampaignStatus { status }.pack(),
+    }
+}
+
+/// Accounts of an instruction paying the campaign authority out of the vault.
+fn payout_accounts(
+    program_id: &Pubkey,
+    campaign_account: &Pubkey,
+    authority: &Pubkey,
+    mint: Option<&Pubkey>,
+) -> Vec<AccountMeta> {
+    let (vault, _) = find_vault_address(program_id, campaign_account);
+    let mut accounts = vec![
+        AccountMeta::new(*campaign_account, false),
+        AccountMeta::new(vault, false),
+        AccountMeta::new(*authority, true),
+    ];
+    if let Some(mint) = mint {
+        accounts.extend(token_accounts(&vault, authority, mint));
+    }
+    accounts
+}
+
+/// The token accounts a token campaign's escrow instructions append: the
+/// wallet's and the vault's associated token accounts, the mint and the
+/// token program.
+fn token_accounts(vault: &Pubkey, wallet: &Pubkey, mint: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(get_associated_token_address(wallet, mint), false),
+        AccountMeta::new(get_associated_token_address(vault, mint), false),
+        AccountMeta::new_readonly(*mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Milestone, MilestoneApproval, RewardCriteria, RewardType};
+
+    fn round_trip(instruction: MktInstruction) {
+        let packed = instruction.pack();
+        assert_eq!(MktInstruction::unpack(&packed).unwrap(), instruction);
+    }
+
+    #[test]
+    fn test_create_campaign_round_trip() {
+        round_trip(MktInstruction::CreateCampaign {
+            campaign: CampaignData {
+                id: "campaign-1".to_string(),
+                title: "Launch".to_string(),
+                creator: "creator".to_string(),
+                target_amount: 1_000_000,
+                current_amount: 0,
+                start_time: 1_700_000_000,
+                end_time: 1_700_086_400,
+                status: CampaignStatus::Active,
+                metadata_uri: "https://example.com/metadata".to_string(),
+                milestones: vec![Milestone::new(
+                    500_000,
+                    "Prototype".to_string(),
+                    1_700_050_000,
+                )],
+                milestone_approval: MilestoneApproval::ContributorVote { quorum_bps: 5_000 },
+                mint: Some(Pubkey::new_unique().to_string()),
+                decimals: 6,
+            },
+        });
+    }
+
+    #[test]
+    fn test_update_metrics_round_trip() {
+        round_trip(MktInstruction::UpdateMetrics {
+            metrics: CampaignMetrics {
+                views: 1500,
+                clicks: 150,
+                conversions: 15,
+                total_spent: 500_000,
+                roi: 2.5,
+            },
+        });
+    }
+
+    #[test]
+    fn test_close_campaign_round_trip() {
+        round_trip(MktInstruction::CloseCampaign);
+    }
+
+    #[test]
+    fn test_create_reward_program_round_trip() {
+        round_trip(MktInstruction::CreateRewardProgram {
+            program: RewardProgram {
+                id: "rewards-1".to_string(),
+                name: "Rewards".to_string(),
+                description: "Engagement rewards".to_string(),
+                reward_type: RewardType::Token {
+                    mint: Pubkey::new_unique().to_string(),
+                    amount: 42,
+                },
+                total_pool: 10_000,
+                remaining_pool: 8_500,
+                start_time: 0,
+                end_time: 86_400,
+                criteria: RewardCriteria {
+                    min_engagement: 100,
+                    min_conversions: 5,
+                    min_spend: 50_000,
+                    requires_verification: true,
+                },
+                is_active: true,
+            },
+        });
+    }
+
+    #[test]
+    fn test_claim_reward_round_trip() {
+        round_trip(MktInstruction::ClaimReward {
+            user_id: "user-1".to_string(),
+        });
+    }
+
+    #[test]
+    fn test_migrate_campaign_round_trip() {
+        round_trip(MktInstruction::MigrateCampaign);
+    }
+
+    #[test]
+    fn test_contribute_round_trip() {
+        round_trip(MktInstruction::Contribute { amount: 250_000 });
+    }
+
+    #[test]
+    fn test_refund_and_withdraw_round_trip() {
+        round_trip(MktInstruction::Refund);
+        round_trip(MktInstruction::WithdrawFunds);
+    }
+
+    #[test]
+    fn test_milestone_round_trip() {
+        round_trip(MktInstruction::SubmitMilestone { index: 0 });
+        round_trip(MktInstruction::ApproveMilestone { index: 3 });
+        round_trip(MktInstruction::ReleaseMilestone { index: 7 });
+    }
+
+    #[test]
+    fn test_set_campaign_status_round_trip() {
+        round_trip(MktInstruction::SetCampaignStatus {
+            status: CampaignStatus::Paused,
+        });
+        assert_eq!(
+            MktInstruction::SetCampaignStatus {
+                status: CampaignStatus::Cancelled
+            }
+            .pack(),
+            vec![12, 4]
+        );
+    }
+
+    #[test]
+    fn test_discriminators_are_stable() {
+        assert_eq!(MktInstruction::CloseCampaign.pack(), vec![2]);
+        let packed = MktInstruction::ClaimReward {
+            user_id: "u".to_string(),
+        }
+        .pack();
+        assert_eq!(packed[0], 4);
+        assert_eq!(MktInstruction::Contribute { amount: 1 }.pack()[0], 6);
+        assert_eq!(MktInstruction::WithdrawFunds.pack(), vec![8]);
+        assert_eq!(MktInstruction::ReleaseMilestone { index: 1 }.pack(), vec![11, 1]);
+    }
+
+    #[test]
+    fn test_unpack_rejects_unknown_tag() {
+        assert_eq!(
+            MktInstruction::unpack(&[0xff]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_builder_account_metas() {
+        let program_id = Pubkey::new_unique();
+        let reward = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let ix = claim_reward(&program_id, &reward, &recipient, &authority, "u".to_string());
+
+        assert_eq!(ix.program_id, program_id);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                AccountMeta::new(reward, false),
+                AccountMeta::new(recipient, false),
+                AccountMeta::new_readonly(authority, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_campaign_account_metas() {
+        let program_id = Pubkey::new_unique();
+        let campaign = Pubkey::new_unique();
+        let contributor = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let (vault, _) = find_vault_address(&program_id, &campaign);
+
+        let lamports = contribute(&program_id, &campaign, &contributor, None, 1);
+        assert_eq!(lamports.accounts.len(), 5);
+
+        let tokens = contribute(&program_id, &campaign, &contributor, Some(&mint), 1);
+        assert_eq!(
+            tokens.accounts[5..],
+            [
+                AccountMeta::new(get_associated_token_address(&contributor, &mint), false),
+                AccountMeta::new(get_associated_token_address(&vault, &mint), false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            ]
+        );
+        let release = release_milestone(&program_id, &campaign, &contributor, Some(&mint), 0);
+        assert_eq!(release.accounts[3..], tokens.accounts[5..9]);
+    }
+}
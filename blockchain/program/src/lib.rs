@@ -0,0 +1,13 @@
+//! MKT4U on-chain program.
+//!
+//! Stores marketing campaigns, their performance metrics and reward programs
+//! in program-owned accounts. The off-chain service in the parent crate builds
+//! the instructions this program understands.
+
+#[cfg(not(feature = "no-entrypoint"))]
+mod entrypoint;
+pub mod error;
+pub mod instruction;
+pub mod pda;
+pub mod processor;
+pub mod state;
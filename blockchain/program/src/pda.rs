@@ -0,0 +1,122 @@
+//! Program-derived addresses for accounts owned by the program.
+//!
+//! Campaigns and reward programs are keyed by their creator and the
+//! off-chain database id, so any service that knows both can locate the
+//! account without storing its address. A campaign's vault, its
+//! contribution receipts and milestone votes are keyed by the campaign
+//! account in turn.
+
+use solana_program::{hash::hash, pubkey::Pubkey};
+
+pub const CAMPAIGN_SEED: &[u8] = b"campaign";
+pub const REWARD_PROGRAM_SEED: &[u8] = b"reward_program";
+pub const VAULT_SEED: &[u8] = b"vault";
+pub const CONTRIBUTION_SEED: &[u8] = b"contribution";
+pub const MILESTONE_VOTE_SEED: &[u8] = b"milestone_vote";
+
+/// Database ids can exceed the 32 byte seed limit (UUIDs are 36 characters),
+/// so they are hashed before being used as a seed.
+pub fn id_seed(id: &str) -> [u8; 32] {
+    hash(id.as_bytes()).to_bytes()
+}
+
+pub fn find_campaign_address(
+    program_id: &Pubkey,
+    creator: &Pubkey,
+    campaign_id: &str,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[CAMPAIGN_SEED, creator.as_ref(), &id_seed(campaign_id)],
+        program_id,
+    )
+}
+
+pub fn find_reward_program_address(
+    program_id: &Pubkey,
+    creator: &Pubkey,
+    reward_program_id: &str,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[REWARD_PROGRAM_SEED, creator.as_ref(), &id_seed(reward_program_id)],
+        program_id,
+    )
+}
+
+/// Escrow holding the lamports contributed to a campaign.
+pub fn find_vault_address(program_id: &Pubkey, campaign: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, campaign.as_ref()], program_id)
+}
+
+/// Receipt for everything one contributor has put into a campaign.
+pub fn find_contribution_address(
+    program_id: &Pubkey,
+    campaign: &Pubkey,
+    contributor: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[CONTRIBUTION_SEED, campaign.as_ref(), contributor.as_ref()],
+        program_id,
+    )
+}
+
+/// Marks that a contributor has voted for one of a campaign's milestones.
+pub fn find_milestone_vote_address(
+    program_id: &Pubkey,
+    campaign: &Pubkey,
+    milestone: u8,
+    contributor: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[MILESTONE_VOTE_SEED, campaign.as_ref(), &[milestone], contributor.as_ref()],
+        program_id,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_campaign_address_is_deterministic() {
+        let program_id = Pubkey::new_unique();
+        let creator = Pubkey::new_unique();
+        let id = "6f1c2a58-3b7e-4d0a-9f59-2f0d6f3c1e7a";
+
+        assert_eq!(
+            find_campaign_address(&program_id, &creator, id),
+            find_campaign_address(&program_id, &creator, id),
+        );
+    }
+
+    #[test]
+    fn test_addresses_depend_on_every_seed() {
+        let program_id = Pubkey::new_unique();
+        let creator = Pubkey::new_unique();
+        let (campaign, _) = find_campaign_address(&program_id, &creator, "campaign-1");
+
+        assert_ne!(
+            campaign,
+            find_campaign_address(&program_id, &creator, "campaign-2").0
+        );
+        assert_ne!(
+            campaign,
+            find_campaign_address(&program_id, &Pubkey::new_unique(), "campaign-1").0
+        );
+        assert_ne!(
+            campaign,
+            find_reward_program_address(&program_id, &creator, "campaign-1").0
+        );
+        assert_ne!(
+            find_contribution_address(&program_id, &campaign, &creator).0,
+            find_contribution_address(&program_id, &campaign, &Pubkey::new_unique()).0
+        );
+        assert_ne!(
+            find_vault_address(&program_id, &campaign).0,
+            find_vault_address(&program_id, &Pubkey::new_unique()).0
+        );
+        assert_ne!(
+            find_milestone_vote_address(&program_id, &campaign, 0, &creator).0,
+            find_milestone_vote_address(&program_id, &campaign, 1, &creator).0
+        );
+    }
+}
@@ -0,0 +1,1055 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+    sysvar::Sysvar,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+use crate::{
+    error::MktError,
+    instruction::MktInstruction,
+    pda::{
+        find_campaign_address, find_contribution_address, find_milestone_vote_address,
+        find_reward_program_address, find_vault_address, id_seed, CAMPAIGN_SEED,
+        CONTRIBUTION_SEED, MILESTONE_VOTE_SEED, REWARD_PROGRAM_SEED, VAULT_SEED,
+    },
+    state::{
+        is_uninitialized, unpack, CampaignAccount, CampaignData, CampaignMetrics,
+        CampaignStatus, ContributionAccount, Milestone, MilestoneApproval, MilestoneStatus, MilestoneVote,
+        RewardProgram, RewardProgramAccount, RewardType,
+    },
+};
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    match MktInstruction::unpack(instruction_data)? {
+        MktInstruction::CreateCampaign { campaign } => {
+            msg!("Instruction: CreateCampaign");
+            process_create_campaign(program_id, accounts, campaign)
+        }
+        MktInstruction::UpdateMetrics { metrics } => {
+            msg!("Instruction: UpdateMetrics");
+            process_update_metrics(program_id, accounts, metrics)
+        }
+        MktInstruction::CloseCampaign => {
+            msg!("Instruction: CloseCampaign");
+            process_close_campaign(program_id, accounts)
+        }
+        MktInstruction::CreateRewardProgram { program } => {
+            msg!("Instruction: CreateRewardProgram");
+            process_create_reward_program(program_id, accounts, program)
+        }
+        MktInstruction::ClaimReward { user_id } => {
+            msg!("Instruction: ClaimReward");
+            process_claim_reward(program_id, accounts, &user_id)
+        }
+        MktInstruction::MigrateCampaign => {
+            msg!("Instruction: MigrateCampaign");
+            process_migrate_campaign(program_id, accounts)
+        }
+        MktInstruction::Contribute { amount } => {
+            msg!("Instruction: Contribute");
+            process_contribute(program_id, accounts, amount)
+        }
+        MktInstruction::Refund => {
+            msg!("Instruction: Refund");
+            process_refund(program_id, accounts)
+        }
+        MktInstruction::WithdrawFunds => {
+            msg!("Instruction: WithdrawFunds");
+            process_withdraw_funds(program_id, accounts)
+        }
+        MktInstruction::SubmitMilestone { index } => {
+            msg!("Instruction: SubmitMilestone");
+            process_submit_milestone(program_id, accounts, index)
+        }
+        MktInstruction::ApproveMilestone { index } => {
+            msg!("Instruction: ApproveMilestone");
+            process_approve_milestone(program_id, accounts, index)
+        }
+        MktInstruction::ReleaseMilestone { index } => {
+            msg!("Instruction: ReleaseMilestone");
+            process_release_milestone(program_id, accounts, index)
+        }
+        MktInstruction::SetCampaignStatus { status } => {
+            msg!("Instruction: SetCampaignStatus");
+            process_set_campaign_status(program_id, accounts, status)
+        }
+    }
+}
+
+fn process_create_campaign(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    mut campaign: CampaignData,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !payer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_system_program(system_program_info)?;
+    let (address, bump) = find_campaign_address(program_id, payer_info.key, &campaign.id);
+    if campaign_info.key != &address {
+        return Err(MktError::InvalidAccountAddress.into());
+    }
+    if campaign_info.owner != &system_program::id() || campaign_info.data_len() != 0 {
+        return Err(MktError::AlreadyInitialized.into());
+    }
+    // The escrow starts empty, so only contributions can count towards the target
+    campaign.current_amount = 0;
+    campaign.validate()?;
+    if !matches!(campaign.status, CampaignStatus::Draft | CampaignStatus::Active) {
+        return Err(MktError::InvalidStatusTransition.into());
+    }
+    campaign.decimals = match campaign.mint()? {
+        Some(mint) => {
+            let mint_info = next_account_info(account_info_iter)?;
+            if mint_info.key != &mint {
+                return Err(MktError::InvalidMint.into());
+            }
+            load_mint(mint_info)?.decimals
+        }
+        None => CampaignData::SOL_DECIMALS,
+    };
+
+    let state = CampaignAccount::new(*payer_info.key, campaign);
+    let lamports = Rent::get()?.minimum_balance(CampaignAccount::LEN);
+    create_pda_account(
+        payer_info,
+        campaign_info,
+        system_program_info,
+        program_id,
+        CampaignAccount::LEN,
+        lamports,
+        &[
+            CAMPAIGN_SEED,
+            payer_info.key.as_ref(),
+            &id_seed(&state.campaign.id),
+            &[bump],
+        ],
+    )?;
+    pack(&state, campaign_info)?;
+
+    msg!("Campaign {} created at {}", state.campaign.id, address);
+    Ok(())
+}
+
+fn process_update_metrics(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    metrics: CampaignMetrics,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+
+    check_program_account(program_id, campaign_info)?;
+    let mut state = load_campaign(campaign_info)?;
+    check_authority(&state.authority, authority_info)?;
+    if !matches!(state.campaign.status, CampaignStatus::Active | CampaignStatus::Paused) {
+        return Err(MktError::CampaignInactive.into());
+    }
+
+    state.metrics = metrics;
+    pack(&state, campaign_info)
+}
+
+fn process_close_campaign(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    check_program_account(program_id, campaign_info)?;
+    check_system_program(system_program_info)?;
+    let mut state = load_campaign(campaign_info)?;
+    check_authority(&state.authority, authority_info)?;
+    if !state.campaign.status.can_transition_to(CampaignStatus::Completed) {
+        return Err(MktError::InvalidStatusTransition.into());
+    }
+
+    state.campaign.status = CampaignStatus::Completed;
+    pack(&state, campaign_info)?;
+
+    let reserve = Rent::get()?.minimum_balance(campaign_info.data_len());
+    let excess = campaign_info.lamports().saturating_sub(reserve);
+    if excess > 0 {
+        if !authority_info.is_writable {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        transfer_lamports(campaign_info, authority_info, excess)?;
+    }
+
+    msg!("Campaign {} closed", state.campaign.id);
+    Ok(())
+}
+
+fn process_create_reward_program(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    program: RewardProgram,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reward_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !payer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_system_program(system_program_info)?;
+    let (address, bump) = find_reward_program_address(program_id, payer_info.key, &program.id);
+    if reward_info.key != &address {
+        return Err(MktError::InvalidAccountAddress.into());
+    }
+    if reward_info.owner != &system_program::id() || reward_info.data_len() != 0 {
+        return Err(MktError::AlreadyInitialized.into());
+    }
+    if program.end_time <= program.start_time {
+        return Err(MktError::InvalidSchedule.into());
+    }
+    if program.remaining_pool > program.total_pool {
+        return Err(MktError::InvalidRewardPool.into());
+    }
+
+    let pool_lamports = match program.reward_type {
+        RewardType::SOL { .. } => program.remaining_pool,
+        _ => 0,
+    };
+    let state = RewardProgramAccount {
+        authority: *payer_info.key,
+        program,
+    };
+    let data = borsh::to_vec(&state).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let lamports = Rent::get()?
+        .minimum_balance(data.len())
+        .checked_add(pool_lamports)
+        .ok_or(MktError::Overflow)?;
+
+    create_pda_account(
+        payer_info,
+        reward_info,
+        system_program_info,
+        program_id,
+        data.len(),
+        lamports,
+        &[
+            REWARD_PROGRAM_SEED,
+            payer_info.key.as_ref(),
+            &id_seed(&state.program.id),
+            &[bump],
+        ],
+    )?;
+    reward_info.try_borrow_mut_data()?.copy_from_slice(&data);
+
+    msg!("Reward program {} created at {}", state.program.id, address);
+    Ok(())
+}
+
+fn process_claim_reward(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    user_id: &str,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reward_info = next_account_info(account_info_iter)?;
+    let recipient_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    check_program_account(program_id, reward_info)?;
+    check_system_program(system_program_info)?;
+    let mut state: RewardProgramAccount = load(reward_info)?;
+    check_authority(&state.authority, authority_info)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let program = &mut state.program;
+    if !program.is_active || now < program.start_time || now > program.end_time {
+        return Err(MktError::RewardProgramInactive.into());
+    }
+
+    let amount = match program.reward_type {
+        RewardType::SOL { amount } => amount,
+        RewardType::Points { amount } => u64::from(amount),
+        RewardType::Token { .. } | RewardType::NFT { .. } => {
+            return Err(MktError::UnsupportedRewardType.into())
+        }
+    };
+    program.remaining_pool = program
+        .remaining_pool
+        .checked_sub(amount)
+        .ok_or(MktError::RewardPoolExhausted)?;
+
+    if let RewardType::SOL { .. } = program.reward_type {
+        if !recipient_info.is_writable {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        transfer_lamports(reward_info, recipient_info, amount)?;
+    }
+    pack(&state, reward_info)?;
+
+    msg!("Reward of {} paid to user {}", amount, user_id);
+    Ok(())
+}
+
+fn process_migrate_campaign(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    check_program_account(program_id, campaign_info)?;
+    check_system_program(system_program_info)?;
+    let mut state = {
+        let data = campaign_info.try_borrow_data()?;
+        if is_uninitialized(&data) {
+            return Err(MktError::UninitializedAccount.into());
+        }
+        if CampaignAccount::is_current_layout(&data) {
+            return Err(MktError::AccountAlreadyMigrated.into());
+        }
+        CampaignAccount::unpack(&data).map_err(|_| ProgramError::InvalidAccountData)?
+    };
+    check_authority(&state.authority, authority_info)?;
+    // Older layouts predate the escrow counter, so their amount was the
+    // client's claim rather than lamports held in the vault.
+    state.campaign.current_amount = 0;
+    state.campaign.validate()?;
+
+    let required_lamports = Rent::get()?
+        .minimum_balance(CampaignAccount::LEN)
+        .saturating_sub(campaign_info.lamports());
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(authority_info.key, campaign_info.key, required_lamports),
+            &[
+                authority_info.clone(),
+                campaign_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+    campaign_info.realloc(CampaignAccount::LEN, true)?;
+    pack(&state, campaign_info)?;
+
+    msg!("Campaign {} migrated to layout v{}", state.campaign.id, state.version);
+    Ok(())
+}
+
+fn process_contribute(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let vault_info = next_account_info(account_info_iter)?;
+    let contribution_info = next_account_info(account_info_iter)?;
+    let contributor_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !contributor_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_program_account(program_id, campaign_info)?;
+    check_system_program(system_program_info)?;
+    if amount == 0 {
+        return Err(MktError::InvalidAmount.into());
+    }
+    let mut state = load_campaign(campaign_info)?;
+    if !state.campaign.is_active() {
+        return Err(MktError::CampaignInactive.into());
+    }
+    let now = Clock::get()?.unix_timestamp;
+    if now < state.campaign.start_time || now > state.campaign.end_time {
+        return Err(MktError::OutsideCampaignWindow.into());
+    }
+    state.campaign.current_amount = state
+        .campaign
+        .current_amount
+        .checked_add(amount)
+        .ok_or(MktError::Overflow)?;
+
+    let (vault, vault_bump) = find_vault_address(program_id, campaign_info.key);
+    if vault_info.key != &vault {
+        return Err(MktError::InvalidAccountAddress.into());
+    }
+    let tokens = match state.campaign.mint()? {
+        Some(mint) => {
+            let tokens = next_token_accounts(account_info_iter, vault_info, &mint)?;
+            let associated_token_program_info = next_account_info(account_info_iter)?;
+            if associated_token_program_info.key != &spl_associated_token_account::id() {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            invoke(
+                &create_associated_token_account_idempotent(
+                    contributor_info.key,
+                    vault_info.key,
+                    tokens.mint.key,
+                    &spl_token::id(),
+                ),
+                &[
+                    contributor_info.clone(),
+                    tokens.vault.clone(),
+                    vault_info.clone(),
+                    tokens.mint.clone(),
+                    system_program_info.clone(),
+                    tokens.token_program.clone(),
+                    associated_token_program_info.clone(),
+                ],
+            )?;
+            Some(tokens)
+        }
+        None => None,
+    };
+    if tokens.is_none() && vault_info.owner != program_id {
+        create_pda_account(
+            contributor_info,
+            vault_info,
+            system_program_info,
+            program_id,
+            0,
+            Rent::get()?.minimum_balance(0),
+            &[VAULT_SEED, campaign_info.key.as_ref(), &[vault_bump]],
+        )?;
+    }
+
+    let (contribution, contribution_bump) =
+        find_contribution_address(program_id, campaign_info.key, contributor_info.key);
+    if contribution_info.key != &contribution {
+        return Err(MktError::InvalidAccountAddress.into());
+    }
+    let mut receipt = if contribution_info.owner == program_id {
+        load::<ContributionAccount>(contribution_info)?
+    } else {
+        create_pda_account(
+            contributor_info,
+            contribution_info,
+            system_program_info,
+            program_id,
+            ContributionAccount::LEN,
+            Rent::get()?.minimum_balance(ContributionAccount::LEN),
+            &[
+                CONTRIBUTION_SEED,
+                campaign_info.key.as_ref(),
+                contributor_info.key.as_ref(),
+                &[contribution_bump],
+            ],
+        )?;
+        ContributionAccount {
+            campaign: *campaign_info.key,
+            contributor: *contributor_info.key,
+            amount: 0,
+            contributed_at: now,
+            votes: 0,
+        }
+    };
+    receipt.amount = receipt.amount.checked_add(amount).ok_or(MktError::Overflow)?;
+    receipt.contributed_at = now;
+
+    match &tokens {
+        Some(tokens) => invoke(
+            &spl_token::instruction::transfer_checked(
+                &spl_token::id(),
+                tokens.wallet.key,
+                tokens.mint.key,
+                tokens.vault.key,
+                contributor_info.key,
+                &[],
+                amount,
+                state.campaign.decimals,
+            )?,
+            &[
+                tokens.wallet.clone(),
+                tokens.mint.clone(),
+                tokens.vault.clone(),
+                contributor_info.clone(),
+                tokens.token_program.clone(),
+            ],
+        )?,
+        None => invoke(
+            &system_instruction::transfer(contributor_info.key, vault_info.key, amount),
+            &[
+                contributor_info.clone(),
+                vault_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?,
+    }
+    pack(&state, campaign_info)?;
+    pack(&receipt, contribution_info)?;
+
+    msg!("Contribution of {} to campaign {} from {}", amount, state.campaign.id, contributor_info.key);
+    Ok(())
+}
+
+fn process_refund(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let vault_info = next_account_info(account_info_iter)?;
+    let contribution_info = next_account_info(account_info_iter)?;
+    let contributor_info = next_account_info(account_info_iter)?;
+
+    if !contributor_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_program_account(program_id, campaign_info)?;
+    check_program_account(program_id, contribution_info)?;
+    let mut state = load_campaign(campaign_info)?;
+    let tokens = next_escrow_accounts(account_info_iter, program_id, campaign_info, vault_info, &state.campaign)?;
+    let now = Clock::get()?.unix_timestamp;
+    // Cancelling the campaign or missing a milestone reopens refunds of
+    // whatever has not been released
+    if state.campaign.status != CampaignStatus::Cancelled && !state.campaign.missed_milestone(now) {
+        if !state.campaign.has_ended(now) {
+            return Err(MktError::CampaignNotEnded.into());
+        }
+        if state.campaign.target_met() {
+            return Err(MktError::TargetMet.into());
+        }
+    }
+
+    let (contribution, _) =
+        find_contribution_address(program_id, campaign_info.key, contributor_info.key);
+    if contribution_info.key != &contribution {
+        return Err(MktError::InvalidAccountAddress.into());
+    }
+    let receipt: ContributionAccount = load(contribution_info)?;
+    // A backer who leaves takes their votes with them. Contributions stop
+    // before voting starts, so each vote weighed the whole receipt.
+    for (index, milestone) in state.campaign.milestones.iter_mut().enumerate() {
+        if receipt.votes & (1 << index) != 0 && milestone.status == MilestoneStatus::Submitted {
+            milestone.approvals = milestone
+                .approvals
+                .checked_sub(receipt.amount)
+                .ok_or(MktError::Overflow)?;
+        }
+    }
+    // Backers share what is left in escrow pro rata, which is exactly their
+    // contribution unless milestones have already been paid out. Approved
+    // milestones stay reserved for the creator, unless the campaign was
+    // cancelled and can no longer release them.
+    let reserved = match state.campaign.status {
+        CampaignStatus::Cancelled => 0,
+        _ => state.campaign.approved_unreleased().ok_or(MktError::Overflow)?,
+    };
+    let escrow = escrow_balance(vault_info, tokens.as_ref())?.saturating_sub(reserved);
+    let amount = u128::from(receipt.amount)
+        .checked_mul(u128::from(escrow))
+        .and_then(|share| share.checked_div(u128::from(state.campaign.current_amount)))
+        .and_then(|share| u64::try_from(share).ok())
+        .ok_or(MktError::Overflow)?;
+    state.campaign.current_amount = state
+        .campaign
+        .current_amount
+        .checked_sub(receipt.amount)
+        .ok_or(MktError::Overflow)?;
+
+    pay_from_vault(program_id, campaign_info, vault_info, contributor_info, tokens.as_ref(), &state.campaign, amount)?;
+    pack(&state, campaign_info)?;
+    // Closing the receipt returns its rent and keeps it from being refunded twice
+    contribution_info.try_borrow_mut_data()?.fill(0);
+    transfer_lamports(contribution_info, contributor_info, contribution_info.lamports())?;
+
+    msg!("Refunded {} from campaign {} to {}", amount, state.campaign.id, contributor_info.key);
+    Ok(())
+}
+
+fn process_withdraw_funds(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let vault_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+
+    check_program_account(program_id, campaign_info)?;
+    let mut state = load_campaign(campaign_info)?;
+    let tokens = next_escrow_accounts(account_info_iter, program_id, campaign_info, vault_info, &state.campaign)?;
+    check_authority(&state.authority, authority_info)?;
+    let status = state.campaign.status;
+    if status == CampaignStatus::Cancelled {
+        return Err(MktError::CampaignCancelled.into());
+    }
+    // Withdrawing completes the campaign, unless it already is
+    if status != CampaignStatus::Completed && !status.can_transition_to(CampaignStatus::Completed) {
+        return Err(MktError::InvalidStatusTransition.into());
+    }
+    if !state.campaign.has_ended(Clock::get()?.unix_timestamp) {
+        return Err(MktError::CampaignNotEnded.into());
+    }
+    if !state.campaign.target_met() {
+        return Err(MktError::TargetNotMet.into());
+    }
+    if state
+        .campaign
+        .milestones
+        .iter()
+        .any(|milestone| milestone.status != MilestoneStatus::Released)
+    {
+        return Err(MktError::MilestonesPending.into());
+    }
+
+    // Completing the campaign keeps it from being cancelled once its escrow is gone
+    state.campaign.status = CampaignStatus::Completed;
+    let amount = escrow_balance(vault_info, tokens.as_ref())?;
+    pay_from_vault(program_id, campaign_info, vault_info, authority_info, tokens.as_ref(), &state.campaign, amount)?;
+    pack(&state, campaign_info)?;
+
+    msg!("Withdrew {} from campaign {}", amount, state.campaign.id);
+    Ok(())
+}
+
+fn process_submit_milestone(program_id: &Pubkey, accounts: &[AccountInfo], index: u8) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+
+    check_program_account(program_id, campaign_info)?;
+    let mut state = load_campaign(campaign_info)?;
+    check_authority(&state.authority, authority_info)?;
+    let now = Clock::get()?.unix_timestamp;
+    // Votes are weighed against the amount raised, which only settles once
+    // contributions have stopped
+    if !state.campaign.has_ended(now) {
+        return Err(MktError::CampaignNotEnded.into());
+    }
+    if !state.campaign.target_met() {
+        return Err(MktError::TargetNotMet.into());
+    }
+    let raised = state.campaign.current_amount;
+    let milestone = milestone_mut(&mut state.campaign, index, MilestoneStatus::Pending)?;
+    if now > milestone.deadline {
+        return Err(MktError::MilestoneDeadlinePassed.into());
+    }
+
+    milestone.status = MilestoneStatus::Submitted;
+    milestone.quorum_base = raised;
+    pack(&state, campaign_info)?;
+
+    msg!("Milestone {} of campaign {} submitted", index, state.campaign.id);
+    Ok(())
+}
+
+fn process_approve_milestone(program_id: &Pubkey, accounts: &[AccountInfo], index: u8) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let approver_info = next_account_info(account_info_iter)?;
+    let contribution_info = next_account_info(account_info_iter)?;
+    let vote_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !approver_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_program_account(program_id, campaign_info)?;
+    let mut state = load_campaign(campaign_info)?;
+    let approval = state.campaign.milestone_approval.clone();
+    let now = Clock::get()?.unix_timestamp;
+    if !state.campaign.has_ended(now) {
+        return Err(MktError::CampaignNotEnded.into());
+    }
+    let milestone = milestone_mut(&mut state.campaign, index, MilestoneStatus::Submitted)?;
+    if now > milestone.deadline {
+        return Err(MktError::MilestoneDeadlinePassed.into());
+    }
+
+    match approval {
+        MilestoneApproval::CampaignAuthority => {
+            check_authority(&state.authority, approver_info)?;
+            milestone.status = MilestoneStatus::Approved;
+        }
+        MilestoneApproval::Approver { approver } => {
+            let approver = approver
+                .parse::<Pubkey>()
+                .map_err(|_| MktError::InvalidMilestoneApproval)?;
+            check_authority(&approver, approver_info)?;
+            milestone.status = MilestoneStatus::Approved;
+        }
+        MilestoneApproval::ContributorVote { quorum_bps } => {
+            check_system_program(system_program_info)?;
+            let (contribution, _) =
+                find_contribution_address(program_id, campaign_info.key, approver_info.key);
+            if contribution_info.key != &contribution {
+                return Err(MktError::InvalidAccountAddress.into());
+            }
+            if contribution_info.owner != program_id {
+                return Err(MktError::InvalidAuthority.into());
+            }
+            let mut receipt: ContributionAccount = load(contribution_info)?;
+
+            let (vote, vote_bump) =
+                find_milestone_vote_address(program_id, campaign_info.key, index, approver_info.key);
+            if vote_info.key != &vote {
+                return Err(MktError::InvalidAccountAddress.into());
+            }
+            if vote_info.owner == program_id {
+                return Err(MktError::AlreadyVoted.into());
+            }
+            create_pda_account(
+                approver_info,
+                vote_info,
+                system_program_info,
+                program_id,
+                MilestoneVote::LEN,
+                Rent::get()?.minimum_balance(MilestoneVote::LEN),
+                &[
+                    MILESTONE_VOTE_SEED,
+                    campaign_info.key.as_ref(),
+                    &[index],
+                    approver_info.key.as_ref(),
+                    &[vote_bump],
+                ],
+            )?;
+            pack(
+                &MilestoneVote {
+                    campaign: *campaign_info.key,
+                    milestone: index,
+                    contributor: *approver_info.key,
+                    weight: receipt.amount,
+                },
+                vote_info,
+            )?;
+
+            receipt.votes |= 1 << index;
+            pack(&receipt, contribution_info)?;
+
+            milestone.approvals = milestone
+                .approvals
+                .checked_add(receipt.amount)
+                .ok_or(MktError::Overflow)?;
+            if u128::from(milestone.approvals) * 10_000 >= u128::from(quorum_bps) * u128::from(milestone.quorum_base) {
+                milestone.status = MilestoneStatus::Approved;
+            }
+        }
+    }
+    let status = milestone.status;
+    pack(&state, campaign_info)?;
+
+    msg!("Milestone {} of campaign {} is {:?}", index, state.campaign.id, status);
+    Ok(())
+}
+
+fn process_release_milestone(program_id: &Pubkey, accounts: &[AccountInfo], index: u8) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let vault_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+
+    check_program_account(program_id, campaign_info)?;
+    let mut state = load_campaign(campaign_info)?;
+    let tokens = next_escrow_accounts(account_info_iter, program_id, campaign_info, vault_info, &state.campaign)?;
+    check_authority(&state.authority, authority_info)?;
+    let milestone = milestone_mut(&mut state.campaign, index, MilestoneStatus::Approved)?;
+    let amount = milestone.amount;
+
+    if escrow_balance(vault_info, tokens.as_ref())? < amount {
+        return Err(ProgramError::InsufficientFunds);
+    }
+    milestone.status = MilestoneStatus::Released;
+    pay_from_vault(program_id, campaign_info, vault_info, authority_info, tokens.as_ref(), &state.campaign, amount)?;
+    pack(&state, campaign_info)?;
+
+    msg!("Released {} for milestone {} of campaign {}", amount, index, state.campaign.id);
+    Ok(())
+}
+
+fn process_set_campaign_status(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    status: CampaignStatus,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let campaign_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+
+    check_program_account(program_id, campaign_info)?;
+    let mut state = load_campaign(campaign_info)?;
+    check_authority(&state.authority, authority_info)?;
+    if !state.campaign.status.can_transition_to(status) {
+        return Err(MktError::InvalidStatusTransition.into());
+    }
+
+    let previous = state.campaign.status;
+    state.campaign.status = status;
+    pack(&state, campaign_info)?;
+
+    msg!("Campaign {} moved from {:?} to {:?}", state.campaign.id, previous, status);
+    Ok(())
+}
+
+/// The milestone at `index` of a campaign that was not cancelled, which must
+/// currently be `expected`.
+fn milestone_mut(
+    campaign: &mut CampaignData,
+    index: u8,
+    expected: MilestoneStatus,
+) -> Result<&mut Milestone, ProgramError> {
+    if campaign.status == CampaignStatus::Cancelled {
+        return Err(MktError::CampaignCancelled.into());
+    }
+    let milestone = campaign
+        .milestones
+        .get_mut(usize::from(index))
+        .ok_or(MktError::InvalidMilestoneIndex)?;
+    if milestone.status != expected {
+        return Err(MktError::InvalidMilestoneStatus.into());
+    }
+    Ok(milestone)
+}
+
+fn check_program_account(program_id: &Pubkey, info: &AccountInfo) -> ProgramResult {
+    if info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if !info.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Check `info` is the campaign's vault and has been created by a contribution.
+fn check_vault(program_id: &Pubkey, campaign_info: &AccountInfo, info: &AccountInfo) -> ProgramResult {
+    let (vault, _) = find_vault_address(program_id, campaign_info.key);
+    if info.key != &vault {
+        return Err(MktError::InvalidAccountAddress.into());
+    }
+    check_program_account(program_id, info)
+}
+
+/// The token accounts a token campaign's escrow instructions end with.
+struct TokenAccounts<'a, 'b> {
+    /// Token account of the contributor or authority on the other side
+    wallet: &'a AccountInfo<'b>,
+    /// The vault PDA's associated token account
+    vault: &'a AccountInfo<'b>,
+    mint: &'a AccountInfo<'b>,
+    token_program: &'a AccountInfo<'b>,
+}
+
+fn next_token_accounts<'a, 'b>(
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    vault_info: &AccountInfo,
+    mint: &Pubkey,
+) -> Result<TokenAccounts<'a, 'b>, ProgramError> {
+    let tokens = TokenAccounts {
+        wallet: next_account_info(account_info_iter)?,
+        vault: next_account_info(account_info_iter)?,
+        mint: next_account_info(account_info_iter)?,
+        token_program: next_account_info(account_info_iter)?,
+    };
+    if tokens.mint.key != mint {
+        return Err(MktError::InvalidMint.into());
+    }
+    if tokens.token_program.key != &spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if tokens.vault.key != &get_associated_token_address(vault_info.key, mint) {
+        return Err(MktError::InvalidAccountAddress.into());
+    }
+    Ok(tokens)
+}
+
+/// Check the vault accounts that follow a campaign's fixed accounts: the
+/// lamport vault itself, or the token accounts of a token campaign.
+fn next_escrow_accounts<'a, 'b>(
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    program_id: &Pubkey,
+    campaign_info: &AccountInfo,
+    vault_info: &AccountInfo,
+    campaign: &CampaignData,
+) -> Result<Option<TokenAccounts<'a, 'b>>, ProgramError> {
+    match campaign.mint()? {
+        Some(mint) => {
+            let (vault, _) = find_vault_address(program_id, campaign_info.key);
+            if vault_info.key != &vault {
+                return Err(MktError::InvalidAccountAddress.into());
+            }
+            next_token_accounts(account_info_iter, vault_info, &mint).map(Some)
+        }
+        None => {
+            check_vault(program_id, campaign_info, vault_info)?;
+            Ok(None)
+        }
+    }
+}
+
+/// What the vault holds beyond its rent-exempt reserve, in the campaign's units.
+fn escrow_balance(vault_info: &AccountInfo, tokens: Option<&TokenAccounts>) -> Result<u64, ProgramError> {
+    match tokens {
+        Some(tokens) => {
+            let data = tokens.vault.try_borrow_data()?;
+            let account = TokenAccount::unpack(&data).map_err(|_| MktError::InvalidTokenAccount)?;
+            Ok(account.amount)
+        }
+        None => {
+            let reserve = Rent::get()?.minimum_balance(vault_info.data_len());
+            Ok(vault_info.lamports().saturating_sub(reserve))
+        }
+    }
+}
+
+/// Pay `amount` out of a campaign's escrow to `recipient_info`, or to its
+/// token account for a token campaign.
+fn pay_from_vault<'b>(
+    program_id: &Pubkey,
+    campaign_info: &AccountInfo<'b>,
+    vault_info: &AccountInfo<'b>,
+    recipient_info: &AccountInfo<'b>,
+    tokens: Option<&TokenAccounts<'_, 'b>>,
+    campaign: &CampaignData,
+    amount: u64,
+) -> ProgramResult {
+    let Some(tokens) = tokens else {
+        return transfer_lamports(vault_info, recipient_info, amount);
+    };
+    let (_, vault_bump) = find_vault_address(program_id, campaign_info.key);
+    invoke_signed(
+        &spl_token::instruction::transfer_checked(
+            &spl_token::id(),
+            tokens.vault.key,
+            tokens.mint.key,
+            tokens.wallet.key,
+            vault_info.key,
+            &[],
+            amount,
+            campaign.decimals,
+        )?,
+        &[
+            tokens.vault.clone(),
+            tokens.mint.clone(),
+            tokens.wallet.clone(),
+            vault_info.clone(),
+            tokens.token_program.clone(),
+        ],
+        &[&[VAULT_SEED, campaign_info.key.as_ref(), &[vault_bump]]],
+    )
+}
+
+fn load_mint(info: &AccountInfo) -> Result<Mint, ProgramError> {
+    if info.owner != &spl_token::id() {
+        return Err(MktError::InvalidMint.into());
+    }
+    let data = info.try_borrow_data()?;
+    Mint::unpack(&data).map_err(|_| MktError::InvalidMint.into())
+}
+
+fn check_system_program(info: &AccountInfo) -> ProgramResult {
+    if info.key != &system_program::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+fn check_authority(expected: &Pubkey, info: &AccountInfo) -> ProgramResult {
+    if !info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if info.key != expected {
+        return Err(MktError::InvalidAuthority.into());
+    }
+    Ok(())
+}
+
+/// Load a campaign that is in the current layout and may be modified.
+fn load_campaign(info: &AccountInfo) -> Result<CampaignAccount, ProgramError> {
+    let data = info.try_borrow_data()?;
+    if is_uninitialized(&data) {
+        return Err(MktError::UninitializedAccount.into());
+    }
+    if !CampaignAccount::is_current_layout(&data) {
+        return Err(MktError::AccountNeedsMigration.into());
+    }
+    unpack(&data).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+fn load<T: BorshDeserialize>(info: &AccountInfo) -> Result<T, ProgramError> {
+    let data = info.try_borrow_data()?;
+    if is_uninitialized(&data) {
+        return Err(MktError::UninitializedAccount.into());
+    }
+    unpack(&data).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+/// Write account state and zero whatever a previous, longer value left behind.
+fn pack<T: BorshSerialize>(state: &T, info: &AccountInfo) -> ProgramResult {
+    let bytes = borsh::to_vec(state).map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut data = info.try_borrow_mut_data()?;
+    if bytes.len() > data.len() {
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    let (head, tail) = data.split_at_mut(bytes.len());
+    head.copy_from_slice(&bytes);
+    tail.fill(0);
+    Ok(())
+}
+
+/// Allocate a program-owned account at a PDA, topping up any lamports that
+/// were sent to the address before it was created.
+fn create_pda_account<'a>(
+    payer: &AccountInfo<'a>,
+    new_account: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    program_id: &Pubkey,
+    space: usize,
+    lamports: u64,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let current_lamports = new_account.lamports();
+    if current_lamports == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                new_account.key,
+                lamports,
+                space as u64,
+                program_id,
+            ),
+            &[payer.clone(), new_account.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        );
+    }
+
+    let required_lamports = lamports.saturating_sub(current_lamports);
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, new_account.key, required_lamports),
+            &[payer.clone(), new_account.clone(), system_program_info.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(new_account.key, space as u64),
+        &[new_account.clone(), system_program_info.clone()],
+        &[signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(new_account.key, program_id),
+        &[new_account.clone(), system_program_info.clone()],
+        &[signer_seeds],
+    )
+}
+
+/// Move lamports out of an account owned by this program.
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> ProgramResult {
+    let from_balance = from
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    let to_balance = to.lamports().checked_add(amount).ok_or(MktError::Overflow)?;
+    **from.try_borrow_mut_lamports()? = from_balance;
+    **to.try_borrow_mut_lamports()? = to_balance;
+    Ok(())
+}
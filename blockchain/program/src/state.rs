@@ -0,0 +1,884 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::error::MktError;
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CampaignData {
+    pub id: String,
+    pub title: String,
+    pub creator: String,
+    pub target_amount: u64,
+    /// Held in escrow for the campaign, counted by the program from
+    /// contributions and refunds. Whatever a new campaign claims is ignored.
+    pub current_amount: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub status: CampaignStatus,
+    pub metadata_uri: String,
+    /// Deliverables the raised funds are released against, in order. A
+    /// campaign without milestones releases everything at once.
+    pub milestones: Vec<Milestone>,
+    pub milestone_approval: MilestoneApproval,
+    /// SPL mint the campaign raises, or `None` to raise lamports. All
+    /// amounts are in the base units of whichever it raises.
+    pub mint: Option<String>,
+    /// Decimals of the mint, or of SOL, filled in by the program on creation.
+    pub decimals: u8,
+}
+
+impl CampaignData {
+    pub const MAX_ID_LEN: usize = 64;
+    pub const MAX_TITLE_LEN: usize = 128;
+    pub const MAX_CREATOR_LEN: usize = 64;
+    pub const MAX_METADATA_URI_LEN: usize = 200;
+    pub const MAX_MILESTONES: usize = 8;
+    pub const SOL_DECIMALS: u8 = 9;
+
+    /// Largest Borsh encoding of a campaign that passes `validate`.
+    pub const MAX_LEN: usize = CampaignDataV2::MAX_LEN
+        + 1 + 4 + 44 // mint
+        + 1; // decimals
+
+    /// Check the constraints the program enforces on new campaigns.
+    ///
+    /// String limits are in bytes, not characters.
+    pub fn validate(&self) -> Result<(), MktError> {
+        if self.id.len() > Self::MAX_ID_LEN {
+            return Err(MktError::IdTooLong);
+        }
+        if self.title.len() > Self::MAX_TITLE_LEN {
+            return Err(MktError::TitleTooLong);
+        }
+        if self.creator.len() > Self::MAX_CREATOR_LEN {
+            return Err(MktError::CreatorTooLong);
+        }
+        if self.metadata_uri.len() > Self::MAX_METADATA_URI_LEN {
+            return Err(MktError::MetadataUriTooLong);
+        }
+        if self.end_time <= self.start_time {
+            return Err(MktError::InvalidSchedule);
+        }
+        if self.milestones.len() > Self::MAX_MILESTONES {
+            return Err(MktError::TooManyMilestones);
+        }
+        let mut milestone_total: u64 = 0;
+        for milestone in &self.milestones {
+            if milestone.description.len() > Milestone::MAX_DESCRIPTION_LEN {
+                return Err(MktError::MilestoneDescriptionTooLong);
+            }
+            if milestone.amount == 0
+                || milestone.deadline <= self.start_time
+                || milestone.status != MilestoneStatus::Pending
+                || milestone.approvals != 0
+                || milestone.quorum_base != 0
+            {
+                return Err(MktError::InvalidMilestone);
+            }
+            milestone_total = milestone_total
+                .checked_add(milestone.amount)
+                .ok_or(MktError::InvalidMilestone)?;
+        }
+        if milestone_total > self.target_amount {
+            return Err(MktError::InvalidMilestone);
+        }
+        self.mint()?;
+        self.milestone_approval.validate()
+    }
+
+    /// The mint a token campaign raises, or `None` for a lamport campaign.
+    pub fn mint(&self) -> Result<Option<Pubkey>, MktError> {
+        self.mint
+            .as_deref()
+            .map(|mint| mint.parse().map_err(|_| MktError::InvalidMint))
+            .transpose()
+    }
+
+    /// `amount` in whole tokens, or SOL, for display.
+    pub fn ui_amount(&self, amount: u64) -> f64 {
+        amount as f64 / 10f64.powi(i32::from(self.decimals))
+    }
+
+    /// Whether enough was raised for the creator to withdraw it.
+    pub fn target_met(&self) -> bool {
+        self.current_amount >= self.target_amount
+    }
+
+    /// Whether the campaign is live and taking contributions.
+    pub fn is_active(&self) -> bool {
+        self.status == CampaignStatus::Active
+    }
+
+    /// Whether the campaign can no longer raise anything, because it was
+    /// completed or cancelled or its end time has passed.
+    pub fn has_ended(&self, now: i64) -> bool {
+        matches!(self.status, CampaignStatus::Completed | CampaignStatus::Cancelled) || now > self.end_time
+    }
+
+    /// Whether a milestone's deadline passed before it was approved, so
+    /// backers can take back what is left in the vault.
+    pub fn missed_milestone(&self, now: i64) -> bool {
+        self.milestones.iter().any(|milestone| milestone.is_missed(now))
+    }
+
+    /// Lamports owed to the creator for milestones approved but not yet released.
+    pub fn approved_unreleased(&self) -> Option<u64> {
+        self.milestones
+            .iter()
+            .filter(|milestone| milestone.status == MilestoneStatus::Approved)
+            .try_fold(0u64, |total, milestone| total.checked_add(milestone.amount))
+    }
+}
+
+/// Where a campaign is in its lifecycle, matching the web app's
+/// `campaigns.status` plus `Cancelled`.
+///
+/// Encoded in the byte that used to hold `is_active`, so the discriminants of
+/// `Completed` and `Active` line up with `false` and `true`: campaigns written
+/// before the status existed read as completed if they were closed and as
+/// active otherwise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[borsh(use_discriminant = true)]
+#[repr(u8)]
+pub enum CampaignStatus {
+    /// Set up but not yet taking contributions.
+    Draft = 2,
+    #[default]
+    Active = 1,
+    /// Temporarily not taking contributions; can be resumed.
+    Paused = 3,
+    Completed = 0,
+    /// Abandoned by its creator; backers can take back what is left in escrow.
+    Cancelled = 4,
+}
+
+impl CampaignStatus {
+    /// Whether a campaign in this status may move to `next`. Completed and
+    /// cancelled campaigns are final.
+    pub fn can_transition_to(self, next: CampaignStatus) -> bool {
+        use CampaignStatus::*;
+
+        matches!(
+            (self, next),
+            (Draft, Active | Cancelled)
+                | (Active, Paused | Completed | Cancelled)
+                | (Paused, Active | Completed | Cancelled)
+        )
+    }
+}
+
+impl From<bool> for CampaignStatus {
+    /// The status of a campaign stored with an `is_active` flag.
+    fn from(is_active: bool) -> Self {
+        if is_active {
+            Self::Active
+        } else {
+            Self::Completed
+        }
+    }
+}
+
+/// Campaign fields as they were before SPL mints.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct CampaignDataV2 {
+    pub id: String,
+    pub title: String,
+    pub creator: String,
+    pub target_amount: u64,
+    pub current_amount: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub is_active: bool,
+    pub metadata_uri: String,
+    pub milestones: Vec<Milestone>,
+    pub milestone_approval: MilestoneApproval,
+}
+
+impl CampaignDataV2 {
+    pub const MAX_LEN: usize = LegacyCampaignData::MAX_LEN
+        + 4 + CampaignData::MAX_MILESTONES * Milestone::MAX_LEN
+        + MilestoneApproval::MAX_LEN;
+}
+
+impl From<CampaignDataV2> for CampaignData {
+    fn from(v2: CampaignDataV2) -> Self {
+        Self {
+            id: v2.id,
+            title: v2.title,
+            creator: v2.creator,
+            target_amount: v2.target_amount,
+            current_amount: v2.current_amount,
+            start_time: v2.start_time,
+            end_time: v2.end_time,
+            status: v2.is_active.into(),
+            metadata_uri: v2.metadata_uri,
+            milestones: v2.milestones,
+            milestone_approval: v2.milestone_approval,
+            mint: None,
+            decimals: CampaignData::SOL_DECIMALS,
+        }
+    }
+}
+
+/// Campaign fields as they were before milestones.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct LegacyCampaignData {
+    pub id: String,
+    pub title: String,
+    pub creator: String,
+    pub target_amount: u64,
+    pub current_amount: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub is_active: bool,
+    pub metadata_uri: String,
+}
+
+impl LegacyCampaignData {
+    pub const MAX_LEN: usize = 4 + CampaignData::MAX_ID_LEN
+        + 4 + CampaignData::MAX_TITLE_LEN
+        + 4 + CampaignData::MAX_CREATOR_LEN
+        + 8 // target_amount
+        + 8 // current_amount
+        + 8 // start_time
+        + 8 // end_time
+        + 1 // is_active
+        + 4 + CampaignData::MAX_METADATA_URI_LEN;
+}
+
+impl From<LegacyCampaignData> for CampaignData {
+    fn from(legacy: LegacyCampaignData) -> Self {
+        Self {
+            id: legacy.id,
+            title: legacy.title,
+            creator: legacy.creator,
+            target_amount: legacy.target_amount,
+            current_amount: legacy.current_amount,
+            start_time: legacy.start_time,
+            end_time: legacy.end_time,
+            status: legacy.is_active.into(),
+            metadata_uri: legacy.metadata_uri,
+            milestones: Vec::new(),
+            milestone_approval: MilestoneApproval::default(),
+            mint: None,
+            decimals: CampaignData::SOL_DECIMALS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Milestone {
+    /// Lamports released from the vault to the campaign authority.
+    pub amount: u64,
+    pub description: String,
+    /// Unix time by which the milestone must be approved.
+    pub deadline: i64,
+    pub status: MilestoneStatus,
+    /// Contributions behind the backers who voted for it, under
+    /// `MilestoneApproval::ContributorVote`.
+    pub approvals: u64,
+    /// Amount raised when the milestone was submitted, which the quorum is
+    /// measured against.
+    pub quorum_base: u64,
+}
+
+impl Milestone {
+    pub const MAX_DESCRIPTION_LEN: usize = 128;
+    pub const MAX_LEN: usize = 8 + 4 + Self::MAX_DESCRIPTION_LEN + 8 + 1 + 8 + 8;
+
+    /// A milestone as it is created, pending and without votes.
+    pub fn new(amount: u64, description: String, deadline: i64) -> Self {
+        Self {
+            amount,
+            description,
+            deadline,
+            status: MilestoneStatus::Pending,
+            approvals: 0,
+            quorum_base: 0,
+        }
+    }
+
+    pub fn is_missed(&self, now: i64) -> bool {
+        matches!(self.status, MilestoneStatus::Pending | MilestoneStatus::Submitted) && now > self.deadline
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MilestoneStatus {
+    Pending,
+    /// The creator reports the deliverable shipped and asks for approval.
+    Submitted,
+    Approved,
+    Released,
+}
+
+/// Who decides that a submitted milestone was delivered.
+#[derive(Debug, Clone, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MilestoneApproval {
+    /// The campaign authority approves its own milestones.
+    #[default]
+    CampaignAuthority,
+    /// A separate key, such as the platform's, approves them.
+    Approver { approver: String },
+    /// Backers vote with what they contributed, and a milestone is approved
+    /// once votes reach `quorum_bps` of the amount raised when it was submitted.
+    ContributorVote { quorum_bps: u16 },
+}
+
+impl MilestoneApproval {
+    /// Tag plus the longest base58 pubkey.
+    pub const MAX_LEN: usize = 1 + 4 + 44;
+
+    pub fn validate(&self) -> Result<(), MktError> {
+        match self {
+            Self::CampaignAuthority => Ok(()),
+            Self::Approver { approver } => approver
+                .parse::<Pubkey>()
+                .map(|_| ())
+                .map_err(|_| MktError::InvalidMilestoneApproval),
+            Self::ContributorVote { quorum_bps } if (1..=10_000).contains(quorum_bps) => Ok(()),
+            Self::ContributorVote { .. } => Err(MktError::InvalidMilestoneApproval),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CampaignMetrics {
+    pub views: u64,
+    pub clicks: u64,
+    pub conversions: u64,
+    pub total_spent: u64,
+    pub roi: f64,
+}
+
+impl CampaignMetrics {
+    pub const LEN: usize = 8 * 5;
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RewardProgram {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub reward_type: RewardType,
+    pub total_pool: u64,
+    pub remaining_pool: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub criteria: RewardCriteria,
+    pub is_active: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RewardType {
+    Token { mint: String, amount: u64 },
+    SOL { amount: u64 },
+    NFT { collection: String },
+    Points { amount: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RewardCriteria {
+    pub min_engagement: u64,
+    pub min_conversions: u64,
+    pub min_spend: u64,
+    pub requires_verification: bool,
+}
+
+/// Layout version written by the current program.
+pub const CAMPAIGN_ACCOUNT_VERSION: u8 = 3;
+
+/// Contents of a campaign account owned by the program.
+///
+/// Accounts are allocated at `CampaignAccount::LEN` bytes and zero padded
+/// after the Borsh payload, so they never need to grow when fields change.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct CampaignAccount {
+    pub version: u8,
+    /// Key allowed to update metrics and close the campaign.
+    pub authority: Pubkey,
+    pub campaign: CampaignData,
+    pub metrics: CampaignMetrics,
+}
+
+/// Campaign layout written before accounts carried a version byte.
+///
+/// These accounts were sized to their exact Borsh length (or to a fixed
+/// guess by early clients) and must be migrated before they can be modified.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct LegacyCampaignAccount {
+    pub authority: Pubkey,
+    pub campaign: LegacyCampaignData,
+    pub metrics: CampaignMetrics,
+}
+
+/// Version 1 campaign layout, fixed size but without milestones.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct CampaignAccountV1 {
+    pub version: u8,
+    pub authority: Pubkey,
+    pub campaign: LegacyCampaignData,
+    pub metrics: CampaignMetrics,
+}
+
+impl CampaignAccountV1 {
+    pub const VERSION: u8 = 1;
+    pub const LEN: usize = 1 + 32 + LegacyCampaignData::MAX_LEN + CampaignMetrics::LEN;
+}
+
+/// Version 2 campaign layout, with milestones but raising only lamports.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct CampaignAccountV2 {
+    pub version: u8,
+    pub authority: Pubkey,
+    pub campaign: CampaignDataV2,
+    pub metrics: CampaignMetrics,
+}
+
+impl CampaignAccountV2 {
+    pub const VERSION: u8 = 2;
+    pub const LEN: usize = 1 + 32 + CampaignDataV2::MAX_LEN + CampaignMetrics::LEN;
+}
+
+impl CampaignAccount {
+    pub const LEN: usize = 1 + 32 + CampaignData::MAX_LEN + CampaignMetrics::LEN;
+
+    pub fn new(authority: Pubkey, campaign: CampaignData) -> Self {
+        Self {
+            version: CAMPAIGN_ACCOUNT_VERSION,
+            authority,
+            campaign,
+            metrics: CampaignMetrics::default(),
+        }
+    }
+
+    /// Whether the account data is already in the current layout.
+    ///
+    /// Legacy accounts start with the authority key rather than a version
+    /// byte, so the allocation size is checked as well.
+    pub fn is_current_layout(data: &[u8]) -> bool {
+        data.len() == Self::LEN && data[0] == CAMPAIGN_ACCOUNT_VERSION
+    }
+
+    /// Decode any supported layout, upgrading older accounts in memory.
+    pub fn unpack(data: &[u8]) -> std::io::Result<Self> {
+        if Self::is_current_layout(data) {
+            return unpack(data);
+        }
+        if data.len() == CampaignAccountV2::LEN && data[0] == CampaignAccountV2::VERSION {
+            let v2: CampaignAccountV2 = unpack(data)?;
+            return Ok(v2.into());
+        }
+        if data.len() == CampaignAccountV1::LEN && data[0] == CampaignAccountV1::VERSION {
+            let v1: CampaignAccountV1 = unpack(data)?;
+            return Ok(v1.into());
+        }
+        let legacy: LegacyCampaignAccount = unpack(data)?;
+        Ok(legacy.into())
+    }
+}
+
+impl From<LegacyCampaignAccount> for CampaignAccount {
+    fn from(legacy: LegacyCampaignAccount) -> Self {
+        Self {
+            version: CAMPAIGN_ACCOUNT_VERSION,
+            authority: legacy.authority,
+            campaign: legacy.campaign.into(),
+            metrics: legacy.metrics,
+        }
+    }
+}
+
+impl From<CampaignAccountV2> for CampaignAccount {
+    fn from(v2: CampaignAccountV2) -> Self {
+        Self {
+            version: CAMPAIGN_ACCOUNT_VERSION,
+            authority: v2.authority,
+            campaign: v2.campaign.into(),
+            metrics: v2.metrics,
+        }
+    }
+}
+
+impl From<CampaignAccountV1> for CampaignAccount {
+    fn from(v1: CampaignAccountV1) -> Self {
+        Self {
+            version: CAMPAIGN_ACCOUNT_VERSION,
+            authority: v1.authority,
+            campaign: v1.campaign.into(),
+            metrics: v1.metrics,
+        }
+    }
+}
+
+/// Contents of a reward program account owned by the program.
+///
+/// For `RewardType::SOL` programs the account also holds the undistributed
+/// pool in lamports on top of its rent-exempt reserve.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct RewardProgramAccount {
+    /// Key allowed to pay out rewards from the pool.
+    pub authority: Pubkey,
+    pub program: RewardProgram,
+}
+
+/// Receipt for one contributor's lamports in a campaign's vault.
+///
+/// Created by the contributor's first contribution and added to by every
+/// later one.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct ContributionAccount {
+    pub campaign: Pubkey,
+    pub contributor: Pubkey,
+    /// Lamports held in the vault for this contributor.
+    pub amount: u64,
+    /// Unix time of the latest contribution.
+    pub contributed_at: i64,
+    /// Milestones the contributor voted for, one bit per index, so a refund
+    /// can take back the votes of milestones still being decided.
+    pub votes: u8,
+}
+
+impl ContributionAccount {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 1;
+}
+
+/// Record of one backer's vote for a milestone, so they cannot vote twice.
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct MilestoneVote {
+    pub campaign: Pubkey,
+    pub milestone: u8,
+    pub contributor: Pubkey,
+    /// Contributed lamports the vote counted for.
+    pub weight: u64,
+}
+
+impl MilestoneVote {
+    pub const LEN: usize = 32 + 1 + 32 + 8;
+}
+
+/// Decode account state, ignoring the zero padding after the Borsh payload.
+pub fn unpack<T: BorshDeserialize>(data: &[u8]) -> std::io::Result<T> {
+    T::deserialize(&mut &data[..])
+}
+
+/// Whether an account has never been written by the program.
+pub fn is_uninitialized(data: &[u8]) -> bool {
+    data.iter().all(|b| *b == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn campaign_with_lengths(id: usize, title: usize, creator: usize, uri: usize) -> CampaignData {
+        CampaignData {
+            id: "i".repeat(id),
+            title: "t".repeat(title),
+            creator: "c".repeat(creator),
+            target_amount: u64::MAX,
+            current_amount: u64::MAX,
+            start_time: 0,
+            end_time: i64::MAX,
+            status: CampaignStatus::Active,
+            metadata_uri: "u".repeat(uri),
+            milestones: Vec::new(),
+            milestone_approval: MilestoneApproval::default(),
+            mint: None,
+            decimals: CampaignData::SOL_DECIMALS,
+        }
+    }
+
+    fn max_campaign() -> CampaignData {
+        let mut campaign = campaign_with_lengths(
+            CampaignData::MAX_ID_LEN,
+            CampaignData::MAX_TITLE_LEN,
+            CampaignData::MAX_CREATOR_LEN,
+            CampaignData::MAX_METADATA_URI_LEN,
+        );
+        campaign.milestones = vec![
+            Milestone::new(1, "d".repeat(Milestone::MAX_DESCRIPTION_LEN), i64::MAX);
+            CampaignData::MAX_MILESTONES
+        ];
+        campaign.milestone_approval = MilestoneApproval::Approver {
+            approver: Pubkey::new_from_array([255; 32]).to_string(),
+        };
+        campaign.mint = Some(Pubkey::new_from_array([255; 32]).to_string());
+        campaign
+    }
+
+    fn legacy_campaign(campaign: &CampaignData) -> LegacyCampaignData {
+        LegacyCampaignData {
+            id: campaign.id.clone(),
+            title: campaign.title.clone(),
+            creator: campaign.creator.clone(),
+            target_amount: campaign.target_amount,
+            current_amount: campaign.current_amount,
+            start_time: campaign.start_time,
+            end_time: campaign.end_time,
+            is_active: campaign.is_active(),
+            metadata_uri: campaign.metadata_uri.clone(),
+        }
+    }
+
+    #[test]
+    fn test_len_matches_largest_valid_campaign() {
+        let campaign = max_campaign();
+        assert!(campaign.validate().is_ok());
+        assert_eq!(borsh::to_vec(&campaign).unwrap().len(), CampaignData::MAX_LEN);
+
+        let account = CampaignAccount::new(Pubkey::new_unique(), campaign);
+        assert_eq!(borsh::to_vec(&account).unwrap().len(), CampaignAccount::LEN);
+
+        let contribution = ContributionAccount {
+            campaign: Pubkey::new_unique(),
+            contributor: Pubkey::new_unique(),
+            amount: u64::MAX,
+            contributed_at: i64::MAX,
+            votes: u8::MAX,
+        };
+        assert_eq!(borsh::to_vec(&contribution).unwrap().len(), ContributionAccount::LEN);
+
+        let vote = MilestoneVote {
+            campaign: Pubkey::new_unique(),
+            milestone: u8::MAX,
+            contributor: Pubkey::new_unique(),
+            weight: u64::MAX,
+        };
+        assert_eq!(borsh::to_vec(&vote).unwrap().len(), MilestoneVote::LEN);
+    }
+
+    #[test]
+    fn test_validate_milestones() {
+        let mut campaign = campaign_with_lengths(8, 8, 8, 8);
+        campaign.target_amount = 1_000;
+        campaign.milestones = vec![
+            Milestone::new(400, "Prototype".to_string(), 100),
+            Milestone::new(600, "Launch".to_string(), 200),
+        ];
+        assert!(campaign.validate().is_ok());
+
+        let mut over_target = campaign.clone();
+        over_target.milestones[1].amount = 601;
+        assert_eq!(over_target.validate(), Err(MktError::InvalidMilestone));
+
+        let mut before_start = campaign.clone();
+        before_start.milestones[0].deadline = before_start.start_time;
+        assert_eq!(before_start.validate(), Err(MktError::InvalidMilestone));
+
+        let mut already_approved = campaign.clone();
+        already_approved.milestones[0].status = MilestoneStatus::Approved;
+        assert_eq!(already_approved.validate(), Err(MktError::InvalidMilestone));
+
+        let mut too_many = campaign.clone();
+        too_many.milestones = vec![Milestone::new(1, String::new(), 100); CampaignData::MAX_MILESTONES + 1];
+        assert_eq!(too_many.validate(), Err(MktError::TooManyMilestones));
+
+        let mut long_description = campaign.clone();
+        long_description.milestones[0].description = "d".repeat(Milestone::MAX_DESCRIPTION_LEN + 1);
+        assert_eq!(long_description.validate(), Err(MktError::MilestoneDescriptionTooLong));
+
+        let mut no_quorum = campaign.clone();
+        no_quorum.milestone_approval = MilestoneApproval::ContributorVote { quorum_bps: 0 };
+        assert_eq!(no_quorum.validate(), Err(MktError::InvalidMilestoneApproval));
+
+        let mut bad_approver = campaign;
+        bad_approver.milestone_approval = MilestoneApproval::Approver {
+            approver: "not-a-pubkey".to_string(),
+        };
+        assert_eq!(bad_approver.validate(), Err(MktError::InvalidMilestoneApproval));
+    }
+
+    #[test]
+    fn test_missed_milestone() {
+        let mut campaign = campaign_with_lengths(8, 8, 8, 8);
+        campaign.milestones = vec![
+            Milestone::new(1, String::new(), 100),
+            Milestone::new(1, String::new(), 200),
+        ];
+        assert!(!campaign.missed_milestone(100));
+        assert!(campaign.missed_milestone(101));
+
+        campaign.milestones[0].status = MilestoneStatus::Approved;
+        assert!(!campaign.missed_milestone(101));
+        assert_eq!(campaign.approved_unreleased(), Some(1));
+        campaign.milestones[1].status = MilestoneStatus::Submitted;
+        assert!(campaign.missed_milestone(201));
+    }
+
+    #[test]
+    fn test_validate_rejects_long_fields() {
+        let (id, title, creator, uri) = (
+            CampaignData::MAX_ID_LEN,
+            CampaignData::MAX_TITLE_LEN,
+            CampaignData::MAX_CREATOR_LEN,
+            CampaignData::MAX_METADATA_URI_LEN,
+        );
+        assert_eq!(
+            campaign_with_lengths(id + 1, title, creator, uri).validate(),
+            Err(MktError::IdTooLong)
+        );
+        assert_eq!(
+            campaign_with_lengths(id, title + 1, creator, uri).validate(),
+            Err(MktError::TitleTooLong)
+        );
+        assert_eq!(
+            campaign_with_lengths(id, title, creator + 1, uri).validate(),
+            Err(MktError::CreatorTooLong)
+        );
+        assert_eq!(
+            campaign_with_lengths(id, title, creator, uri + 1).validate(),
+            Err(MktError::MetadataUriTooLong)
+        );
+    }
+
+    #[test]
+    fn test_unpack_current_layout() {
+        let account = CampaignAccount::new(Pubkey::new_unique(), campaign_with_lengths(8, 8, 8, 8));
+        let mut data = borsh::to_vec(&account).unwrap();
+        data.resize(CampaignAccount::LEN, 0);
+
+        assert!(CampaignAccount::is_current_layout(&data));
+        assert_eq!(CampaignAccount::unpack(&data).unwrap(), account);
+    }
+
+    #[test]
+    fn test_status_replaces_is_active_byte() {
+        assert_eq!(borsh::to_vec(&CampaignStatus::Active).unwrap(), borsh::to_vec(&true).unwrap());
+        assert_eq!(borsh::to_vec(&CampaignStatus::Completed).unwrap(), borsh::to_vec(&false).unwrap());
+        for status in [
+            CampaignStatus::Draft,
+            CampaignStatus::Active,
+            CampaignStatus::Paused,
+            CampaignStatus::Completed,
+            CampaignStatus::Cancelled,
+        ] {
+            let bytes = borsh::to_vec(&status).unwrap();
+            assert_eq!(bytes.len(), 1);
+            assert_eq!(borsh::from_slice::<CampaignStatus>(&bytes).unwrap(), status);
+        }
+        assert!(borsh::from_slice::<CampaignStatus>(&[5]).is_err());
+    }
+
+    #[test]
+    fn test_status_transitions() {
+        use CampaignStatus::*;
+
+        assert!(Draft.can_transition_to(Active));
+        assert!(Active.can_transition_to(Paused));
+        assert!(Paused.can_transition_to(Active));
+        assert!(Paused.can_transition_to(Completed));
+        assert!(Active.can_transition_to(Cancelled));
+
+        assert!(!Draft.can_transition_to(Paused));
+        assert!(!Draft.can_transition_to(Completed));
+        assert!(!Active.can_transition_to(Active));
+        assert!(!Completed.can_transition_to(Active));
+        assert!(!Cancelled.can_transition_to(Active));
+        assert!(!Completed.can_transition_to(Cancelled));
+    }
+
+    #[test]
+    fn test_validate_mint() {
+        let mut campaign = campaign_with_lengths(8, 8, 8, 8);
+        assert_eq!(campaign.mint(), Ok(None));
+
+        let mint = Pubkey::new_unique();
+        campaign.mint = Some(mint.to_string());
+        assert_eq!(campaign.mint(), Ok(Some(mint)));
+        assert!(campaign.validate().is_ok());
+
+        campaign.mint = Some("not-a-mint".to_string());
+        assert_eq!(campaign.validate(), Err(MktError::InvalidMint));
+    }
+
+    #[test]
+    fn test_ui_amount_uses_decimals() {
+        let mut campaign = campaign_with_lengths(8, 8, 8, 8);
+        assert_eq!(campaign.ui_amount(1_500_000_000), 1.5);
+        campaign.decimals = 6;
+        assert_eq!(campaign.ui_amount(2_500_000), 2.5);
+    }
+
+    #[test]
+    fn test_unpack_v2_layout() {
+        let mut campaign = campaign_with_lengths(8, 8, 8, 8);
+        campaign.milestones = vec![Milestone::new(1, "Prototype".to_string(), 100)];
+        let v2 = CampaignAccountV2 {
+            version: CampaignAccountV2::VERSION,
+            authority: Pubkey::new_unique(),
+            campaign: CampaignDataV2 {
+                id: campaign.id.clone(),
+                title: campaign.title.clone(),
+                creator: campaign.creator.clone(),
+                target_amount: campaign.target_amount,
+                current_amount: campaign.current_amount,
+                start_time: campaign.start_time,
+                end_time: campaign.end_time,
+                is_active: campaign.is_active(),
+                metadata_uri: campaign.metadata_uri.clone(),
+                milestones: campaign.milestones.clone(),
+                milestone_approval: campaign.milestone_approval.clone(),
+            },
+            metrics: CampaignMetrics::default(),
+        };
+        let mut data = borsh::to_vec(&v2).unwrap();
+        data.resize(CampaignAccountV2::LEN, 0);
+
+        assert!(!CampaignAccount::is_current_layout(&data));
+        let account = CampaignAccount::unpack(&data).unwrap();
+        assert_eq!(account.version, CAMPAIGN_ACCOUNT_VERSION);
+        assert_eq!(account.authority, v2.authority);
+        assert_eq!(account.campaign, campaign);
+    }
+
+    #[test]
+    fn test_unpack_v1_layout() {
+        let v1 = CampaignAccountV1 {
+            version: CampaignAccountV1::VERSION,
+            authority: Pubkey::new_unique(),
+            campaign: legacy_campaign(&campaign_with_lengths(8, 8, 8, 8)),
+            metrics: CampaignMetrics::default(),
+        };
+        let mut data = borsh::to_vec(&v1).unwrap();
+        data.resize(CampaignAccountV1::LEN, 0);
+
+        assert!(!CampaignAccount::is_current_layout(&data));
+        let account = CampaignAccount::unpack(&data).unwrap();
+        assert_eq!(account.version, CAMPAIGN_ACCOUNT_VERSION);
+        assert_eq!(account.authority, v1.authority);
+        assert_eq!(account.campaign, campaign_with_lengths(8, 8, 8, 8));
+    }
+
+    #[test]
+    fn test_unpack_legacy_layout() {
+        let legacy = LegacyCampaignAccount {
+            authority: Pubkey::new_unique(),
+            campaign: legacy_campaign(&campaign_with_lengths(8, 8, 8, 8)),
+            metrics: CampaignMetrics {
+                views: 10,
+                ..CampaignMetrics::default()
+            },
+        };
+        // Early clients over-allocated and left the tail zeroed
+        let mut data = borsh::to_vec(&legacy).unwrap();
+        data.resize(264, 0);
+
+        assert!(!CampaignAccount::is_current_layout(&data));
+        let account = CampaignAccount::unpack(&data).unwrap();
+        assert_eq!(account.version, CAMPAIGN_ACCOUNT_VERSION);
+        assert_eq!(account.authority, legacy.authority);
+        assert_eq!(account.campaign, campaign_with_lengths(8, 8, 8, 8));
+        assert_eq!(account.metrics, legacy.metrics);
+    }
+}
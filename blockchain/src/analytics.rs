@@ -1,8 +1,14 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
 use std::collections::HashMap;
+use std::str::FromStr;
 
-use crate::{SolanaClient, campaigns::CampaignMetrics};
+use crate::{
+    SolanaClient,
+    campaigns::{CampaignManager, CampaignMetrics},
+    errors::BlockchainError,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalyticsData {
@@ -49,7 +55,7 @@ impl AnalyticsManager {
         Ok(format!("analytics_{}", analytics_hash))
     }
 
-    /// Aggregate analytics for a time period
+    /// Aggregate analytics for a time period over campaign account addresses
     pub async fn aggregate_analytics(
         &self,
         campaign_ids: &[String],
@@ -63,10 +69,8 @@ impl AnalyticsManager {
         let mut roi_sum = 0.0;
         let mut campaign_performance = HashMap::new();
 
-        // In a real implementation, this would query on-chain data
-        for campaign_id in campaign_ids {
-            let metrics = self.get_campaign_metrics(campaign_id).await?;
-            
+        let all_metrics = self.get_campaigns_metrics(campaign_ids).await?;
+        for (campaign_id, metrics) in campaign_ids.iter().zip(all_metrics) {
             total_spend += metrics.total_spent;
             total_conversions += metrics.conversions;
             roi_sum += metrics.roi;
@@ -96,19 +100,30 @@ impl AnalyticsManager {
         })
     }
 
-    /// Get real-time metrics for a campaign
-    pub async fn get_campaign_metrics(&self, campaign_id: &str) -> Result<CampaignMetrics> {
-        log::info!("Fetching metrics for campaign: {}", campaign_id);
-        
-        // In a real implementation, this would query the campaign account on-chain
-        // For now, return mock data
-        Ok(CampaignMetrics {
-            views: 1500,
-            clicks: 150,
-            conversions: 15,
-            total_spent: 500000, // 0.5 SOL in lamports
-            roi: 2.5,
-        })
+    /// Get real-time metrics for a campaign from its account
+    pub async fn get_campaign_metrics(&self, campaign_pubkey: &str) -> Result<CampaignMetrics> {
+        log::info!("Fetching metrics for campaign: {}", campaign_pubkey);
+
+        let mut metrics = self.get_campaigns_metrics(&[campaign_pubkey.to_string()]).await?;
+        Ok(metrics.remove(0))
+    }
+
+    /// Metrics for several campaign accounts, read in batches rather than one request each
+    pub async fn get_campaigns_metrics(&self, campaign_pubkeys: &[String]) -> Result<Vec<CampaignMetrics>> {
+        let addresses = campaign_pubkeys
+            .iter()
+            .map(|pubkey| Pubkey::from_str(pubkey).map_err(|e| BlockchainError::InvalidPubkey(e.to_string())))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let accounts = self.client.get_multiple_accounts(&addresses).await?;
+        addresses
+            .iter()
+            .zip(accounts)
+            .map(|(address, account)| {
+                let account = account.ok_or_else(|| BlockchainError::AccountNotFound(address.to_string()))?;
+                Ok(CampaignManager::decode_campaign(&account.data)?.metrics)
+            })
+            .collect()
     }
 
     /// Compute performance predictions using on-chain data
@@ -122,7 +137,7 @@ impl AnalyticsManager {
         let current_metrics = self.get_campaign_metrics(campaign_id).await?;
         
         // Simple linear prediction (in reality, you'd use more sophisticated models)
-        let daily_growth_rate = 1.05; // 5% daily growth
+        let daily_growth_rate: f64 = 1.05; // 5% daily growth
         let growth_factor = daily_growth_rate.powi(days_ahead as i32);
         
         Ok(CampaignMetrics {
@@ -137,10 +152,9 @@ impl AnalyticsManager {
     /// Generate insights from campaign performance
     pub async fn generate_insights(&self, campaign_ids: &[String]) -> Result<Vec<String>> {
         let mut insights = Vec::new();
-        
-        for campaign_id in campaign_ids {
-            let metrics = self.get_campaign_metrics(campaign_id).await?;
-            
+        let all_metrics = self.get_campaigns_metrics(campaign_ids).await?;
+
+        for (campaign_id, metrics) in campaign_ids.iter().zip(all_metrics) {
             // Generate insights based on metrics
             if metrics.roi > 3.0 {
                 insights.push(format!("Campaign {} has excellent ROI of {:.2}x", campaign_id, metrics.roi));
@@ -179,7 +193,13 @@ impl AnalyticsManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{BlockchainConfig, campaigns::CampaignMetrics};
+    use crate::{
+        BlockchainConfig,
+        backend::MemoryBackend,
+        campaigns::CampaignData,
+        fixtures::{memory_client, sample_campaign, store_campaign},
+    };
+    use mkt4u_program::state::CampaignAccount;
 
     #[tokio::test]
     async fn test_analytics_recording() {
@@ -206,17 +226,67 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    fn sample_metrics() -> CampaignMetrics {
+        CampaignMetrics {
+            views: 1500,
+            clicks: 150,
+            conversions: 15,
+            total_spent: 500000,
+            roi: 2.5,
+        }
+    }
+
+    /// Store a campaign account holding `metrics` and return its address
+    fn seed_campaign(backend: &MemoryBackend, program_id: &Pubkey, metrics: CampaignMetrics) -> String {
+        let campaign = CampaignData {
+            id: Pubkey::new_unique().to_string(),
+            ..sample_campaign()
+        };
+        let mut account = CampaignAccount::new(Pubkey::new_unique(), campaign);
+        account.metrics = metrics;
+        store_campaign(backend, program_id, &account).to_string()
+    }
+
     #[tokio::test]
     async fn test_performance_prediction() {
-        let config = BlockchainConfig::default();
-        let client = SolanaClient::new(config).unwrap();
+        let (client, backend, _) = memory_client();
+        let campaign = seed_campaign(&backend, &client.get_program_id().unwrap(), sample_metrics());
         let analytics_manager = AnalyticsManager::new(client);
 
-        let prediction = analytics_manager.predict_performance("test-campaign", 7).await;
+        let prediction = analytics_manager.predict_performance(&campaign, 7).await;
         assert!(prediction.is_ok());
         
         let predicted_metrics = prediction.unwrap();
         assert!(predicted_metrics.views > 0);
         assert!(predicted_metrics.roi > 0.0);
     }
+
+    #[tokio::test]
+    async fn test_aggregate_reads_campaigns_in_one_request() {
+        let (client, backend, _) = memory_client();
+        let program_id = client.get_program_id().unwrap();
+        let analytics_manager = AnalyticsManager::new(client);
+
+        let campaigns: Vec<String> = [1.5, 4.0, 0.5]
+            .into_iter()
+            .map(|roi| seed_campaign(&backend, &program_id, CampaignMetrics { roi, ..sample_metrics() }))
+            .collect();
+        let requests = backend.request_count();
+
+        let report = analytics_manager.aggregate_analytics(&campaigns, 0, 86400).await.unwrap();
+        assert_eq!(backend.request_count(), requests + 1);
+        assert_eq!(report.total_campaigns, 3);
+        assert_eq!(report.total_spend, 3 * 500000);
+        assert_eq!(report.total_conversions, 45);
+        assert_eq!(report.average_roi, 2.0);
+        assert_eq!(report.top_performing_campaigns, vec![
+            campaigns[1].clone(),
+            campaigns[0].clone(),
+            campaigns[2].clone(),
+        ]);
+
+        let missing = [campaigns[0].clone(), Pubkey::new_unique().to_string()];
+        let err = analytics_manager.aggregate_analytics(&missing, 0, 86400).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<BlockchainError>(), Some(BlockchainError::AccountNotFound(_))));
+    }
 }
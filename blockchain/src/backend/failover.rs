@@ -0,0 +1,572 @@
+//! Routing across several RPC endpoints.
+//!
+//! Calls go to the first healthy endpoint in configuration order and move on
+//! to the next one when an endpoint fails at the transport level (unreachable,
+//! rate limited, behind, or erroring). An endpoint is marked unhealthy when its
+//! recent error rate climbs too high, and periodic health checks compare each
+//! endpoint's slot and latency against the others.
+//!
+//! A transaction is sent to the endpoint that handed out its blockhash, and
+//! its status is asked of the endpoint it was sent to, so a send-and-confirm
+//! cycle stays on one node while that node is up.
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+use super::{ChainBackend, SignatureStatus, SimulatedTransaction};
+use crate::errors::{BlockchainError, Result};
+
+/// Weight of the newest sample in the moving averages.
+const SMOOTHING: f64 = 0.2;
+/// How many blockhashes and signatures are remembered for sticky routing.
+const STICKY_ROUTES: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthPolicy {
+    pub check_interval_ms: u64,
+    /// Slots an endpoint may trail the most advanced one before it is skipped.
+    pub max_slot_lag: u64,
+    /// Slowest health check response still considered healthy.
+    pub max_latency_ms: u64,
+    /// Share of recent calls, between 0 and 1, that may fail at the transport level.
+    pub max_error_rate: f64,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            check_interval_ms: 10_000,
+            max_slot_lag: 50,
+            max_latency_ms: 2_000,
+            max_error_rate: 0.5,
+        }
+    }
+}
+
+/// What one endpoint has seen so far.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EndpointStats {
+    pub url: String,
+    pub healthy: bool,
+    pub requests: u64,
+    pub errors: u64,
+    pub rate_limited: u64,
+    /// Moving average of the share of calls that failed at the transport level.
+    pub error_rate: f64,
+    /// Moving average of call latency in milliseconds.
+    pub latency_ms: f64,
+    /// Slot reported by the last health check.
+    pub slot: Option<u64>,
+    /// Slots behind the most advanced endpoint at the last health check.
+    pub slot_lag: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+struct Endpoint {
+    backend: Arc<dyn ChainBackend>,
+    stats: Mutex<EndpointStats>,
+}
+
+/// Which endpoint served a blockhash or took a transaction.
+#[derive(Default)]
+struct StickyRoutes {
+    blockhashes: VecDeque<(Hash, usize)>,
+    signatures: VecDeque<(Signature, usize)>,
+}
+
+fn remember<K: PartialEq>(routes: &mut VecDeque<(K, usize)>, key: K, endpoint: usize) {
+    routes.retain(|(k, _)| *k != key);
+    if routes.len() == STICKY_ROUTES {
+        routes.pop_front();
+    }
+    routes.push_back((key, endpoint));
+}
+
+fn recall<K: PartialEq>(routes: &VecDeque<(K, usize)>, key: &K) -> Option<usize> {
+    routes.iter().find(|(k, _)| k == key).map(|(_, endpoint)| *endpoint)
+}
+
+/// Whether `error` says the endpoint, rather than the request, is at fault.
+fn is_endpoint_failure(error: &BlockchainError) -> bool {
+    matches!(
+        error,
+        BlockchainError::NetworkError(_)
+            | BlockchainError::RateLimited(_)
+            | BlockchainError::NodeBehind(_)
+            | BlockchainError::RpcError(_)
+    )
+}
+
+pub struct FailoverBackend {
+    endpoints: Vec<Endpoint>,
+    policy: HealthPolicy,
+    routes: Mutex<StickyRoutes>,
+}
+
+impl FailoverBackend {
+    /// Route across `endpoints`, given as `(url, backend)` in order of preference.
+    pub fn new(endpoints: Vec<(String, Arc<dyn ChainBackend>)>, policy: HealthPolicy) -> Result<Self> {
+        if endpoints.is_empty() {
+            return Err(BlockchainError::ValidationError(
+                "At least one RPC endpoint is required".to_string(),
+            ));
+        }
+        let endpoints = endpoints
+            .into_iter()
+            .map(|(url, backend)| Endpoint {
+                backend,
+                stats: Mutex::new(EndpointStats {
+                    url,
+                    healthy: true,
+                    requests: 0,
+                    errors: 0,
+                    rate_limited: 0,
+                    error_rate: 0.0,
+                    latency_ms: 0.0,
+                    slot: None,
+                    slot_lag: None,
+                    last_error: None,
+                }),
+            })
+            .collect();
+        Ok(Self {
+            endpoints,
+            policy,
+            routes: Mutex::new(StickyRoutes::default()),
+        })
+    }
+
+    pub fn stats(&self) -> Vec<EndpointStats> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| endpoint.stats.lock().unwrap().clone())
+            .collect()
+    }
+
+    /// Probe every endpoint's slot and latency and update which are healthy.
+    pub async fn check_health(&self) {
+        let timeout = Duration::from_millis(self.policy.max_latency_ms);
+        let mut probes = Vec::with_capacity(self.endpoints.len());
+        for endpoint in &self.endpoints {
+            let started = Instant::now();
+            let result = match tokio::time::timeout(timeout, endpoint.backend.get_slot()).await {
+                Ok(result) => result,
+                Err(_) => Err(BlockchainError::NetworkError(format!(
+                    "No response within {}ms",
+                    self.policy.max_latency_ms
+                ))),
+            };
+            probes.push((started.elapsed(), result));
+        }
+
+        let tip = probes
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().ok().copied())
+            .max();
+        for (index, (latency, result)) in probes.into_iter().enumerate() {
+            self.record(index, latency, &result);
+            let mut stats = self.endpoints[index].stats.lock().unwrap();
+            let was_healthy = stats.healthy;
+            match result {
+                Ok(slot) => {
+                    let lag = tip.unwrap_or(slot).saturating_sub(slot);
+                    stats.slot = Some(slot);
+                    stats.slot_lag = Some(lag);
+                    stats.healthy = lag <= self.policy.max_slot_lag
+                        && latency.as_millis() as u64 <= self.policy.max_latency_ms
+                        && stats.error_rate <= self.policy.max_error_rate;
+                }
+                Err(_) => stats.healthy = false,
+            }
+            if was_healthy != stats.healthy {
+                warn!(
+                    "RPC endpoint {} is now {} (slot lag {:?}, error rate {:.2})",
+                    stats.url,
+                    if stats.healthy { "healthy" } else { "unhealthy" },
+                    stats.slot_lag,
+                    stats.error_rate
+                );
+            }
+        }
+    }
+
+    /// Run `check_health` every `check_interval_ms` on the current runtime.
+    ///
+    /// The task holds only a weak reference and ends once the backend is
+    /// dropped. Returns `None` outside a Tokio runtime.
+    pub fn spawn_health_checks(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        let runtime = tokio::runtime::Handle::try_current().ok()?;
+        let backend: Weak<Self> = Arc::downgrade(self);
+        let interval = Duration::from_millis(self.policy.check_interval_ms.max(1));
+        Some(runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match backend.upgrade() {
+                    Some(backend) => backend.check_health().await,
+                    None => break,
+                }
+            }
+        }))
+    }
+
+    fn record<T>(&self, index: usize, latency: Duration, result: &Result<T>) {
+        let mut stats = self.endpoints[index].stats.lock().unwrap();
+        stats.requests += 1;
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        stats.latency_ms = if stats.requests == 1 {
+            latency_ms
+        } else {
+            stats.latency_ms + SMOOTHING * (latency_ms - stats.latency_ms)
+        };
+
+        let failed = match result {
+            Err(error) if is_endpoint_failure(error) => {
+                stats.errors += 1;
+                if matches!(error, BlockchainError::RateLimited(_)) {
+                    stats.rate_limited += 1;
+                }
+                stats.last_error = Some(error.to_string());
+                1.0
+            }
+            _ => 0.0,
+        };
+        stats.error_rate += SMOOTHING * (failed - stats.error_rate);
+        if stats.healthy && stats.error_rate > self.policy.max_error_rate {
+            warn!("RPC endpoint {} is now unhealthy (error rate {:.2})", stats.url, stats.error_rate);
+            stats.healthy = false;
+        }
+    }
+
+    /// Endpoints to try: `preferred` first, then healthy ones in configuration
+    /// order, then the unhealthy ones as a last resort.
+    fn order(&self, preferred: Option<usize>) -> Vec<usize> {
+        let healthy: Vec<bool> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.stats.lock().unwrap().healthy)
+            .collect();
+        let mut order = Vec::with_capacity(self.endpoints.len());
+        order.extend(preferred.filter(|&index| healthy[index]));
+        order.extend((0..healthy.len()).filter(|&index| healthy[index] && Some(index) != preferred));
+        order.extend(preferred.filter(|&index| !healthy[index]));
+        order.extend((0..healthy.len()).filter(|&index| !healthy[index] && Some(index) != preferred));
+        order
+    }
+
+    /// Run `call` against each endpoint in turn until one answers without a
+    /// transport failure. Returns the answer and the endpoint that gave it.
+    async fn route<T, F, Fut>(&self, preferred: Option<usize>, call: F) -> (usize, Result<T>)
+    where
+        F: Fn(Arc<dyn ChainBackend>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let order = self.order(preferred);
+        let last = order.len() - 1;
+        for (attempt, index) in order.into_iter().enumerate() {
+            let started = Instant::now();
+            let result = call(self.endpoints[index].backend.clone()).await;
+            self.record(index, started.elapsed(), &result);
+            match result {
+                Err(error) if attempt < last && is_endpoint_failure(&error) => {
+                    let url = self.endpoints[index].stats.lock().unwrap().url.clone();
+                    debug!("RPC endpoint {} failed, trying the next one: {}", url, error);
+                }
+                result => return (index, result),
+            }
+        }
+        unreachable!("there is always at least one endpoint")
+    }
+
+    async fn call<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        F: Fn(Arc<dyn ChainBackend>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.route(None, call).await.1
+    }
+
+    fn blockhash_route(&self, blockhash: &Hash) -> Option<usize> {
+        recall(&self.routes.lock().unwrap().blockhashes, blockhash)
+    }
+
+    /// Send through the endpoint that issued `blockhash`, remembering where
+    /// `signature` went so its status is asked there.
+    async fn send<F, Fut>(&self, blockhash: &Hash, signature: Option<&Signature>, call: F) -> Result<Signature>
+    where
+        F: Fn(Arc<dyn ChainBackend>) -> Fut,
+        Fut: Future<Output = Result<Signature>>,
+    {
+        // Sending the same signed transaction to another node cannot make it
+        // land twice, so failing over here is safe.
+        let (index, result) = self.route(self.blockhash_route(blockhash), call).await;
+        if let Some(signature) = signature {
+            remember(&mut self.routes.lock().unwrap().signatures, *signature, index);
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl ChainBackend for FailoverBackend {
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        self.call(|backend| async move { backend.get_balance(pubkey).await }).await
+    }
+
+    async fn get_account_data(&self, pubkey: &Pubkey) -> Result<Vec<u8>> {
+        self.call(|backend| async move { backend.get_account_data(pubkey).await }).await
+    }
+
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        self.call(|backend| async move { backend.get_account(pubkey).await }).await
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        self.call(|backend| async move { backend.get_multiple_accounts(pubkeys).await }).await
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        let (index, result) = self
+            .route(None, |backend| async move { backend.get_latest_blockhash().await })
+            .await;
+        if let Ok(blockhash) = &result {
+            remember(&mut self.routes.lock().unwrap().blockhashes, *blockhash, index);
+        }
+        result
+    }
+
+    async fn get_slot(&self) -> Result<u64> {
+        self.call(|backend| async move { backend.get_slot().await }).await
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        self.call(|backend| async move { backend.get_minimum_balance_for_rent_exemption(data_len).await })
+            .await
+    }
+
+    async fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        self.send(&transaction.message.recent_blockhash, transaction.signatures.first(), |backend| async move {
+            backend.send_and_confirm_transaction(transaction).await
+        })
+        .await
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        self.send(&transaction.message.recent_blockhash, transaction.signatures.first(), |backend| async move {
+            backend.send_transaction(transaction).await
+        })
+        .await
+    }
+
+    async fn send_and_confirm_versioned_transaction(&self, transaction: &VersionedTransaction) -> Result<Signature> {
+        self.send(transaction.message.recent_blockhash(), transaction.signatures.first(), |backend| async move {
+            backend.send_and_confirm_versioned_transaction(transaction).await
+        })
+        .await
+    }
+
+    async fn send_versioned_transaction(&self, transaction: &VersionedTransaction) -> Result<Signature> {
+        self.send(transaction.message.recent_blockhash(), transaction.signatures.first(), |backend| async move {
+            backend.send_versioned_transaction(transaction).await
+        })
+        .await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<SimulatedTransaction> {
+        let preferred = self.blockhash_route(transaction.message.recent_blockhash());
+        self.route(preferred, |backend| async move {
+            backend.simulate_transaction(transaction, addresses).await
+        })
+        .await
+        .1
+    }
+
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> Result<u64> {
+        let preferred = self.blockhash_route(message.recent_blockhash());
+        self.route(preferred, |backend| async move { backend.get_fee_for_message(message).await })
+            .await
+            .1
+    }
+
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool> {
+        let preferred = self.blockhash_route(blockhash);
+        self.route(preferred, |backend| async move { backend.is_blockhash_valid(blockhash).await })
+            .await
+            .1
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<std::result::Result<(), TransactionError>>> {
+        let preferred = recall(&self.routes.lock().unwrap().signatures, signature);
+        self.route(preferred, |backend| async move { backend.get_signature_status(signature).await })
+            .await
+            .1
+    }
+
+    async fn get_signature_statuses(&self, signatures: &[Signature]) -> Result<Vec<Option<SignatureStatus>>> {
+        // A batch is usually sent from one place; ask where the first one went
+        let preferred = signatures
+            .first()
+            .and_then(|signature| recall(&self.routes.lock().unwrap().signatures, signature));
+        self.route(preferred, |backend| async move { backend.get_signature_statuses(signatures).await })
+            .await
+            .1
+    }
+
+    async fn get_program_accounts(&self, program_id: &Pubkey) -> Result<Vec<(Pubkey, Account)>> {
+        self.call(|backend| async move { backend.get_program_accounts(program_id).await }).await
+    }
+
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        self.call(|backend| async move { backend.get_recent_prioritization_fees(accounts).await })
+            .await
+    }
+
+    fn endpoint_stats(&self) -> Vec<EndpointStats> {
+        self.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use solana_sdk::{
+        signature::{Keypair, Signer},
+        system_instruction,
+    };
+
+    fn failover(backends: &[Arc<MemoryBackend>]) -> FailoverBackend {
+        let endpoints = backends
+            .iter()
+            .enumerate()
+            .map(|(i, backend)| (format!("http://node-{}", i), backend.clone() as Arc<dyn ChainBackend>))
+            .collect();
+        FailoverBackend::new(endpoints, HealthPolicy::default()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_on_transport_errors_only() {
+        let primary = Arc::new(MemoryBackend::new());
+        let secondary = Arc::new(MemoryBackend::new());
+        let wallet = Pubkey::new_unique();
+        secondary.set_balance(wallet, 42);
+        let backend = failover(&[primary.clone(), secondary.clone()]);
+
+        // The primary answers, even though the answer is "no such account"
+        assert!(matches!(
+            backend.get_account_data(&wallet).await,
+            Err(BlockchainError::AccountNotFound(_))
+        ));
+
+        primary.set_outage(Some("connection refused"));
+        assert_eq!(backend.get_balance(&wallet).await.unwrap(), 42);
+
+        let stats = backend.endpoint_stats();
+        assert_eq!((stats[0].requests, stats[0].errors), (2, 1));
+        assert_eq!(stats[0].last_error.as_deref(), Some("Network error: connection refused"));
+        assert_eq!((stats[1].requests, stats[1].errors), (1, 0));
+
+        secondary.set_outage(Some("connection refused"));
+        assert!(matches!(backend.get_balance(&wallet).await, Err(BlockchainError::NetworkError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_send_and_status_stick_to_the_blockhash_endpoint() {
+        let primary = Arc::new(MemoryBackend::new());
+        let secondary = Arc::new(MemoryBackend::new());
+        secondary.advance_blockhash();
+        let payer = Keypair::new();
+        primary.set_balance(payer.pubkey(), 1_000_000);
+        secondary.set_balance(payer.pubkey(), 1_000_000);
+        let backend = failover(&[primary.clone(), secondary.clone()]);
+
+        // The blockhash comes from the secondary while the primary is down...
+        primary.set_outage(Some("timed out"));
+        let blockhash = backend.get_latest_blockhash().await.unwrap();
+        primary.set_outage(None);
+
+        // ...so the send and the status check go there too, although the
+        // primary is preferred again
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            blockhash,
+        );
+        let signature = backend.send_and_confirm_transaction(&transaction).await.unwrap();
+        assert!(backend.is_blockhash_valid(&blockhash).await.unwrap());
+        assert_eq!(backend.get_signature_status(&signature).await.unwrap(), Some(Ok(())));
+        assert!(primary.sent_transactions().is_empty());
+        assert_eq!(secondary.sent_transactions(), vec![transaction]);
+    }
+
+    #[tokio::test]
+    async fn test_health_checks_skip_lagging_and_failing_endpoints() {
+        let backends: Vec<_> = (0..3).map(|_| Arc::new(MemoryBackend::new())).collect();
+        backends[0].set_slot(1_000);
+        backends[1].set_slot(1_000 - HealthPolicy::default().max_slot_lag - 1);
+        backends[2].set_slot(1_000);
+        let wallet = Pubkey::new_unique();
+        backends[2].set_balance(wallet, 7);
+        let backend = failover(&backends);
+
+        backends[0].set_outage(Some("connection refused"));
+        backend.check_health().await;
+        let stats = backend.endpoint_stats();
+        assert!(!stats[0].healthy);
+        assert!(!stats[1].healthy);
+        assert_eq!(stats[1].slot_lag, Some(51));
+        assert!(stats[2].healthy);
+
+        // Unhealthy endpoints are not tried while a healthy one is left
+        assert_eq!(backend.get_balance(&wallet).await.unwrap(), 7);
+        assert_eq!(backend.endpoint_stats()[1].requests, 1);
+
+        // and come back once they catch up
+        backends[0].set_outage(None);
+        backends[1].set_slot(1_000);
+        backend.check_health().await;
+        assert!(backend.endpoint_stats().iter().all(|stats| stats.healthy));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_failures_mark_an_endpoint_unhealthy() {
+        let primary = Arc::new(MemoryBackend::new());
+        let secondary = Arc::new(MemoryBackend::new());
+        let backend = failover(&[primary.clone(), secondary.clone()]);
+
+        primary.set_outage(Some("connection reset"));
+        for _ in 0..4 {
+            backend.get_slot().await.unwrap();
+        }
+        let stats = backend.endpoint_stats();
+        assert!(!stats[0].healthy);
+        assert!(stats[0].error_rate > HealthPolicy::default().max_error_rate);
+
+        // Once unhealthy it is skipped without being tried
+        backend.get_slot().await.unwrap();
+        assert_eq!(backend.endpoint_stats()[0].requests, stats[0].requests);
+    }
+}
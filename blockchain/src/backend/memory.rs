@@ -0,0 +1,546 @@
+use async_trait::async_trait;
+use solana_sdk::{
+    account::Account,
+    address_lookup_table::state::AddressLookupTable,
+    hash::{hash, Hash},
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Signature,
+    system_program,
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{ChainBackend, SignatureStatus, SimulatedTransaction};
+use crate::config::Commitment;
+use crate::errors::{BlockchainError, Result};
+
+/// In-memory ledger implementing `ChainBackend`.
+///
+/// Transactions are signature- and blockhash-checked, charged a flat fee and
+/// recorded, but their instructions are not executed. Tests seed the accounts
+/// they expect the program to have written and inspect what was sent.
+pub struct MemoryBackend {
+    ledger: Mutex<Ledger>,
+}
+
+struct Ledger {
+    accounts: HashMap<Pubkey, Account>,
+    blockhash: Hash,
+    blockhash_count: u64,
+    transactions: Vec<Transaction>,
+    versioned_transactions: Vec<VersionedTransaction>,
+    statuses: HashMap<Signature, SignatureStatus>,
+    landing_commitment: Commitment,
+    send_failures: VecDeque<SendFailure>,
+    in_flight: Vec<VersionedTransaction>,
+    prioritization_fees: Vec<u64>,
+    simulations: VecDeque<SimulatedTransaction>,
+    simulated: Vec<VersionedTransaction>,
+    slot: u64,
+    outage: Option<String>,
+    latency: Duration,
+    requests: u64,
+}
+
+enum SendFailure {
+    /// The transaction never reaches the ledger.
+    Rejected(BlockchainError),
+    /// The transaction lands but the caller only sees the error.
+    Dropped(BlockchainError),
+    /// The transaction is accepted but never lands, like one a leader discarded.
+    Lost,
+    /// The transaction is still in flight when the caller sees the error, and
+    /// lands at the next blockhash check.
+    Late(BlockchainError),
+}
+
+impl MemoryBackend {
+    pub const FEE_PER_SIGNATURE: u64 = 5000;
+
+    pub fn new() -> Self {
+        Self {
+            ledger: Mutex::new(Ledger {
+                accounts: HashMap::new(),
+                blockhash: hash(&0u64.to_le_bytes()),
+                blockhash_count: 0,
+                transactions: Vec::new(),
+                versioned_transactions: Vec::new(),
+                statuses: HashMap::new(),
+                landing_commitment: Commitment::Finalized,
+                send_failures: VecDeque::new(),
+                in_flight: Vec::new(),
+                prioritization_fees: Vec::new(),
+                simulations: VecDeque::new(),
+                simulated: Vec::new(),
+                slot: 0,
+                outage: None,
+                latency: Duration::ZERO,
+                requests: 0,
+            }),
+        }
+    }
+
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
+        self.ledger.lock().unwrap().accounts.insert(pubkey, account);
+    }
+
+    /// Fund a system-owned wallet.
+    pub fn set_balance(&self, pubkey: Pubkey, lamports: u64) {
+        self.set_account(pubkey, Account::new(lamports, 0, &system_program::id()));
+    }
+
+    pub fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.ledger.lock().unwrap().accounts.get(pubkey).cloned()
+    }
+
+    /// Every transaction accepted so far, in submission order.
+    pub fn sent_transactions(&self) -> Vec<Transaction> {
+        self.ledger.lock().unwrap().transactions.clone()
+    }
+
+    /// Every v0 transaction accepted so far, in submission order. Legacy
+    /// ones, however sent, are in `sent_transactions`.
+    pub fn sent_versioned_transactions(&self) -> Vec<VersionedTransaction> {
+        self.ledger.lock().unwrap().versioned_transactions.clone()
+    }
+
+    /// Every transaction simulated so far, legacy or v0, in order.
+    pub fn simulated_transactions(&self) -> Vec<VersionedTransaction> {
+        self.ledger.lock().unwrap().simulated.clone()
+    }
+
+    /// Make the next submission fail with `error` instead of landing.
+    pub fn fail_next_send(&self, error: BlockchainError) {
+        self.ledger
+            .lock()
+            .unwrap()
+            .send_failures
+            .push_back(SendFailure::Rejected(error));
+    }
+
+    /// Land the next submission but report `error`, like a response lost in transit.
+    pub fn drop_next_response(&self, error: BlockchainError) {
+        self.ledger
+            .lock()
+            .unwrap()
+            .send_failures
+            .push_back(SendFailure::Dropped(error));
+    }
+
+    /// Accept the next submission without ever landing it.
+    pub fn lose_next_send(&self) {
+        self.ledger.lock().unwrap().send_failures.push_back(SendFailure::Lost);
+    }
+
+    /// Report `error` for the next submission while keeping it in flight. It
+    /// lands at the next `is_blockhash_valid` call, just before the blockhash
+    /// it was built on expires.
+    pub fn land_next_send_late(&self, error: BlockchainError) {
+        self.ledger
+            .lock()
+            .unwrap()
+            .send_failures
+            .push_back(SendFailure::Late(error));
+    }
+
+    /// Commitment transactions are reported at when they land, until
+    /// `set_commitment` moves them on. Finalized unless changed.
+    pub fn set_landing_commitment(&self, commitment: Commitment) {
+        self.ledger.lock().unwrap().landing_commitment = commitment;
+    }
+
+    /// Move a landed transaction to `commitment`, like the cluster voting on its slot.
+    pub fn set_commitment(&self, signature: &Signature, commitment: Commitment) {
+        if let Some(status) = self.ledger.lock().unwrap().statuses.get_mut(signature) {
+            status.commitment = commitment;
+        }
+    }
+
+    /// Return `simulation` from the next `simulate_transaction` call.
+    ///
+    /// Instructions are not executed, so by default a simulation only checks
+    /// that the transaction could be sent and reports accounts unchanged.
+    pub fn queue_simulation(&self, simulation: SimulatedTransaction) {
+        self.ledger.lock().unwrap().simulations.push_back(simulation);
+    }
+
+    /// Fees reported by `get_recent_prioritization_fees`, whatever accounts are asked about.
+    pub fn set_prioritization_fees(&self, fees: Vec<u64>) {
+        self.ledger.lock().unwrap().prioritization_fees = fees;
+    }
+
+    /// Slot reported by `get_slot`.
+    pub fn set_slot(&self, slot: u64) {
+        self.ledger.lock().unwrap().slot = slot;
+    }
+
+    /// Fail every call with a `NetworkError` carrying `reason` until cleared
+    /// with `None`, like an unreachable RPC node.
+    pub fn set_outage(&self, reason: Option<&str>) {
+        self.ledger.lock().unwrap().outage = reason.map(str::to_string);
+    }
+
+    /// Delay every call by `latency`, like a distant RPC node.
+    pub fn set_latency(&self, latency: Duration) {
+        self.ledger.lock().unwrap().latency = latency;
+    }
+
+    /// How many backend calls have been made so far.
+    pub fn request_count(&self) -> u64 {
+        self.ledger.lock().unwrap().requests
+    }
+
+    async fn available(&self) -> Result<()> {
+        let latency = {
+            let mut ledger = self.ledger.lock().unwrap();
+            ledger.requests += 1;
+            ledger.latency
+        };
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        match &self.ledger.lock().unwrap().outage {
+            Some(reason) => Err(BlockchainError::NetworkError(reason.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Check and record `transaction`, returning `None` if it was lost on the way.
+    fn land(&self, transaction: &VersionedTransaction) -> Result<Option<Signature>> {
+        let mut ledger = self.ledger.lock().unwrap();
+        let dropped = match ledger.send_failures.pop_front() {
+            Some(SendFailure::Rejected(error)) => return Err(error),
+            Some(SendFailure::Dropped(error)) => Some(error),
+            Some(SendFailure::Lost) => return Ok(None),
+            Some(SendFailure::Late(error)) => {
+                ledger.in_flight.push(transaction.clone());
+                return Err(error);
+            }
+            None => None,
+        };
+        let signature = ledger.commit(transaction)?;
+        match dropped {
+            Some(error) => Err(error),
+            None => Ok(Some(signature)),
+        }
+    }
+
+    /// Replace the latest blockhash, expiring every transaction built on the old one.
+    pub fn advance_blockhash(&self) -> Hash {
+        self.ledger.lock().unwrap().advance_blockhash()
+    }
+}
+
+impl Ledger {
+    fn commit(&mut self, transaction: &VersionedTransaction) -> Result<Signature> {
+        if !transaction.verify_with_results().into_iter().all(|verified| verified) {
+            return Err(BlockchainError::TransactionError(TransactionError::SignatureFailure.to_string()));
+        }
+        if *transaction.message.recent_blockhash() != self.blockhash {
+            return Err(BlockchainError::BlockhashExpired);
+        }
+        for lookup in transaction.message.address_table_lookups().unwrap_or_default() {
+            let table = self
+                .accounts
+                .get(&lookup.account_key)
+                .and_then(|account| AddressLookupTable::deserialize(&account.data).ok())
+                .ok_or_else(|| {
+                    BlockchainError::TransactionError(TransactionError::AddressLookupTableNotFound.to_string())
+                })?;
+            let in_range = |&index: &u8| (index as usize) < table.addresses.len();
+            if !lookup.writable_indexes.iter().chain(&lookup.readonly_indexes).all(in_range) {
+                return Err(BlockchainError::TransactionError(
+                    TransactionError::InvalidAddressLookupTableIndex.to_string(),
+                ));
+            }
+        }
+        let signature = transaction.signatures[0];
+        if self.statuses.contains_key(&signature) {
+            return Err(BlockchainError::TransactionError(
+                TransactionError::AlreadyProcessed.to_string(),
+            ));
+        }
+
+        let fee = MemoryBackend::FEE_PER_SIGNATURE * transaction.signatures.len() as u64;
+        let fee_payer = transaction.message.static_account_keys()[0];
+        let payer_account = self
+            .accounts
+            .get_mut(&fee_payer)
+            .filter(|account| account.lamports >= fee)
+            .ok_or(BlockchainError::InsufficientFunds)?;
+        payer_account.lamports -= fee;
+
+        match transaction.clone().into_legacy_transaction() {
+            Some(legacy) => self.transactions.push(legacy),
+            None => self.versioned_transactions.push(transaction.clone()),
+        }
+        let status = SignatureStatus {
+            slot: self.slot,
+            commitment: self.landing_commitment,
+            err: None,
+        };
+        self.statuses.insert(signature, status);
+        Ok(signature)
+    }
+
+    fn advance_blockhash(&mut self) -> Hash {
+        self.blockhash_count += 1;
+        self.blockhash = hash(&self.blockhash_count.to_le_bytes());
+        self.blockhash
+    }
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl ChainBackend for MemoryBackend {
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        self.available().await?;
+        Ok(self.get_account(pubkey).map(|a| a.lamports).unwrap_or(0))
+    }
+
+    async fn get_account_data(&self, pubkey: &Pubkey) -> Result<Vec<u8>> {
+        self.available().await?;
+        self.get_account(pubkey)
+            .map(|a| a.data)
+            .ok_or_else(|| BlockchainError::AccountNotFound(pubkey.to_string()))
+    }
+
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        self.available().await?;
+        Ok(MemoryBackend::get_account(self, pubkey))
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        self.available().await?;
+        let ledger = self.ledger.lock().unwrap();
+        Ok(pubkeys.iter().map(|pubkey| ledger.accounts.get(pubkey).cloned()).collect())
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        self.available().await?;
+        Ok(self.ledger.lock().unwrap().blockhash)
+    }
+
+    async fn get_slot(&self) -> Result<u64> {
+        self.available().await?;
+        Ok(self.ledger.lock().unwrap().slot)
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        self.available().await?;
+        Ok(Rent::default().minimum_balance(data_len))
+    }
+
+    async fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        self.send_and_confirm_versioned_transaction(&transaction.clone().into()).await
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        self.send_versioned_transaction(&transaction.clone().into()).await
+    }
+
+    async fn send_and_confirm_versioned_transaction(&self, transaction: &VersionedTransaction) -> Result<Signature> {
+        self.available().await?;
+        match self.land(transaction)? {
+            Some(signature) => Ok(signature),
+            // Confirmation gives up once the blockhash expires
+            None => Err(BlockchainError::BlockhashExpired),
+        }
+    }
+
+    async fn send_versioned_transaction(&self, transaction: &VersionedTransaction) -> Result<Signature> {
+        self.available().await?;
+        self.land(transaction)?;
+        Ok(transaction.signatures[0])
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<SimulatedTransaction> {
+        self.available().await?;
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger.simulated.push(transaction.clone());
+        if let Some(simulation) = ledger.simulations.pop_front() {
+            return Ok(simulation);
+        }
+
+        let fee = Self::FEE_PER_SIGNATURE * transaction.signatures.len() as u64;
+        let fee_payer = transaction.message.static_account_keys()[0];
+        let err = if !transaction.verify_with_results().into_iter().all(|verified| verified) {
+            Some(TransactionError::SignatureFailure)
+        } else if *transaction.message.recent_blockhash() != ledger.blockhash {
+            Some(TransactionError::BlockhashNotFound)
+        } else if ledger.accounts.get(&fee_payer).map_or(0, |a| a.lamports) < fee {
+            Some(TransactionError::InsufficientFundsForFee)
+        } else {
+            None
+        };
+
+        Ok(SimulatedTransaction {
+            err,
+            logs: Vec::new(),
+            units_consumed: Some(0),
+            accounts: Some(addresses.iter().map(|a| ledger.accounts.get(a).cloned()).collect()),
+        })
+    }
+
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> Result<u64> {
+        self.available().await?;
+        Ok(Self::FEE_PER_SIGNATURE * message.header().num_required_signatures as u64)
+    }
+
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool> {
+        self.available().await?;
+        let mut ledger = self.ledger.lock().unwrap();
+        let in_flight = std::mem::take(&mut ledger.in_flight);
+        if !in_flight.is_empty() {
+            for transaction in &in_flight {
+                ledger.commit(transaction)?;
+            }
+            ledger.advance_blockhash();
+        }
+        Ok(*blockhash == ledger.blockhash)
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<std::result::Result<(), TransactionError>>> {
+        self.available().await?;
+        let ledger = self.ledger.lock().unwrap();
+        Ok(ledger.statuses.get(signature).map(|status| status.err.clone().map_or(Ok(()), Err)))
+    }
+
+    async fn get_signature_statuses(&self, signatures: &[Signature]) -> Result<Vec<Option<SignatureStatus>>> {
+        self.available().await?;
+        let ledger = self.ledger.lock().unwrap();
+        Ok(signatures.iter().map(|signature| ledger.statuses.get(signature).cloned()).collect())
+    }
+
+    async fn get_program_accounts(&self, program_id: &Pubkey) -> Result<Vec<(Pubkey, Account)>> {
+        self.available().await?;
+        Ok(self
+            .ledger
+            .lock()
+            .unwrap()
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id)
+            .map(|(pubkey, account)| (*pubkey, account.clone()))
+            .collect())
+    }
+
+    async fn get_recent_prioritization_fees(&self, _accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        self.available().await?;
+        Ok(self.ledger.lock().unwrap().prioritization_fees.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        signature::{Keypair, Signer},
+        system_instruction,
+    };
+
+    fn transfer(payer: &Keypair, blockhash: Hash) -> Transaction {
+        Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1)],
+            Some(&payer.pubkey()),
+            &[payer],
+            blockhash,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_send_charges_fee_and_records_transaction() {
+        let backend = MemoryBackend::new();
+        let payer = Keypair::new();
+        backend.set_balance(payer.pubkey(), 1_000_000);
+
+        let blockhash = backend.get_latest_blockhash().await.unwrap();
+        let transaction = transfer(&payer, blockhash);
+        let signature = backend.send_and_confirm_transaction(&transaction).await.unwrap();
+
+        assert_eq!(signature, transaction.signatures[0]);
+        assert_eq!(backend.sent_transactions(), vec![transaction]);
+        assert_eq!(
+            backend.get_balance(&payer.pubkey()).await.unwrap(),
+            1_000_000 - MemoryBackend::FEE_PER_SIGNATURE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_rejects_expired_blockhash() {
+        let backend = MemoryBackend::new();
+        let payer = Keypair::new();
+        backend.set_balance(payer.pubkey(), 1_000_000);
+
+        let blockhash = backend.get_latest_blockhash().await.unwrap();
+        backend.advance_blockhash();
+        let result = backend
+            .send_and_confirm_transaction(&transfer(&payer, blockhash))
+            .await;
+
+        assert!(matches!(result, Err(BlockchainError::BlockhashExpired)));
+        assert!(backend.sent_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_response_still_lands() {
+        let backend = MemoryBackend::new();
+        let payer = Keypair::new();
+        backend.set_balance(payer.pubkey(), 1_000_000);
+        backend.drop_next_response(BlockchainError::NetworkError("connection reset".to_string()));
+
+        let transaction = transfer(&payer, backend.get_latest_blockhash().await.unwrap());
+        let result = backend.send_and_confirm_transaction(&transaction).await;
+        assert!(matches!(result, Err(BlockchainError::NetworkError(_))));
+        assert_eq!(
+            backend.get_signature_status(&transaction.signatures[0]).await.unwrap(),
+            Some(Ok(()))
+        );
+
+        // Resubmitting the same transaction is rejected rather than landing twice
+        assert!(backend.send_and_confirm_transaction(&transaction).await.is_err());
+        assert_eq!(backend.sent_transactions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_requires_fee_payer_funds() {
+        let backend = MemoryBackend::new();
+        let payer = Keypair::new();
+
+        let blockhash = backend.get_latest_blockhash().await.unwrap();
+        let result = backend
+            .send_and_confirm_transaction(&transfer(&payer, blockhash))
+            .await;
+
+        assert!(matches!(result, Err(BlockchainError::InsufficientFunds)));
+    }
+
+    #[tokio::test]
+    async fn test_get_program_accounts_filters_by_owner() {
+        let backend = MemoryBackend::new();
+        let program_id = Pubkey::new_unique();
+        let owned = Pubkey::new_unique();
+        backend.set_account(owned, Account::new(1, 8, &program_id));
+        backend.set_balance(Pubkey::new_unique(), 1);
+
+        let accounts = backend.get_program_accounts(&program_id).await.unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].0, owned);
+    }
+}
@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+
+use crate::config::Commitment;
+use crate::errors::Result;
+
+pub mod failover;
+pub mod memory;
+pub mod rpc;
+pub mod throttle;
+
+pub use failover::{EndpointStats, FailoverBackend, HealthPolicy};
+pub use memory::MemoryBackend;
+pub use rpc::RpcBackend;
+pub use throttle::{RateLimit, ThrottledBackend};
+
+/// Most accounts `getMultipleAccounts` returns per request.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Most signatures `getSignatureStatuses` accepts per request.
+pub const MAX_SIGNATURE_STATUSES: usize = 256;
+
+/// How far a landed transaction has got.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureStatus {
+    pub slot: u64,
+    pub commitment: Commitment,
+    /// Why the transaction failed, if it did; a failed transaction still lands and pays its fee.
+    pub err: Option<TransactionError>,
+}
+
+/// Raw outcome of running a transaction without committing it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedTransaction {
+    pub err: Option<TransactionError>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// Post-execution state of the requested addresses, in request order, or
+    /// `None` if the backend cannot report it.
+    pub accounts: Option<Vec<Option<Account>>>,
+}
+
+/// The chain operations `SolanaClient` depends on.
+///
+/// `RpcBackend` talks to a real cluster, `ThrottledBackend` paces the calls
+/// to one endpoint, `FailoverBackend` routes across several of them, and
+/// `MemoryBackend` is an in-memory ledger for exercising the managers offline.
+#[async_trait]
+pub trait ChainBackend: Send + Sync {
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64>;
+
+    async fn get_account_data(&self, pubkey: &Pubkey) -> Result<Vec<u8>>;
+
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>>;
+
+    /// Several accounts in one request, in the order asked for. At most
+    /// `MAX_MULTIPLE_ACCOUNTS` per call.
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>>;
+
+    async fn get_latest_blockhash(&self) -> Result<Hash>;
+
+    /// Slot the node has processed up to, used to tell how far behind it is.
+    async fn get_slot(&self) -> Result<u64>;
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64>;
+
+    async fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Signature>;
+
+    /// Hand `transaction` to the cluster without waiting for it to land.
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<Signature>;
+
+    /// `send_and_confirm_transaction` for a transaction that may be v0.
+    async fn send_and_confirm_versioned_transaction(&self, transaction: &VersionedTransaction) -> Result<Signature>;
+
+    /// `send_transaction` for a transaction that may be v0.
+    async fn send_versioned_transaction(&self, transaction: &VersionedTransaction) -> Result<Signature>;
+
+    /// Execute `transaction`, legacy or v0, against current state without
+    /// landing it, reporting the resulting state of `addresses`.
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<SimulatedTransaction>;
+
+    /// Lamports the cluster would charge to process `message`.
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> Result<u64>;
+
+    /// Whether transactions built on `blockhash` can still land.
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool>;
+
+    /// Outcome of a transaction the cluster has processed, or `None` if it has not landed.
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<std::result::Result<(), TransactionError>>>;
+
+    /// Where each of `signatures` stands, in the order asked for, `None` for
+    /// any that have not landed. At most `MAX_SIGNATURE_STATUSES` per call.
+    async fn get_signature_statuses(&self, signatures: &[Signature]) -> Result<Vec<Option<SignatureStatus>>>;
+
+    async fn get_program_accounts(&self, program_id: &Pubkey) -> Result<Vec<(Pubkey, Account)>>;
+
+    /// Per-compute-unit prices paid in recent slots by transactions writing any of `accounts`.
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>>;
+
+    /// Health and traffic per RPC endpoint, for backends that route across several.
+    fn endpoint_stats(&self) -> Vec<EndpointStats> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,272 @@
+use async_trait::async_trait;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_custom_error::{
+        JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED, JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    },
+    rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig},
+    rpc_request::RpcError,
+};
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+
+use super::{ChainBackend, SignatureStatus, SimulatedTransaction};
+use crate::config::Commitment;
+use crate::errors::{BlockchainError, Result};
+
+/// Backend that forwards every call to a Solana JSON-RPC endpoint.
+pub struct RpcBackend {
+    client: RpcClient,
+}
+
+impl RpcBackend {
+    pub fn new(rpc_url: String, commitment: CommitmentConfig) -> Self {
+        Self {
+            client: RpcClient::new_with_commitment(rpc_url, commitment),
+        }
+    }
+
+    pub fn get_client(&self) -> &RpcClient {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl ChainBackend for RpcBackend {
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        self.client
+            .get_balance(pubkey)
+            .await
+            .map_err(read_error)
+    }
+
+    async fn get_account_data(&self, pubkey: &Pubkey) -> Result<Vec<u8>> {
+        self.client
+            .get_account_data(pubkey)
+            .await
+            .map_err(|e| transport_error(&e).unwrap_or_else(|| BlockchainError::AccountNotFound(e.to_string())))
+    }
+
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        self.client
+            .get_account_with_commitment(pubkey, self.client.commitment())
+            .await
+            .map(|response| response.value)
+            .map_err(read_error)
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        self.client
+            .get_latest_blockhash()
+            .await
+            .map_err(read_error)
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        self.client.get_multiple_accounts(pubkeys).await.map_err(read_error)
+    }
+
+    async fn get_slot(&self) -> Result<u64> {
+        self.client.get_slot().await.map_err(read_error)
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        self.client
+            .get_minimum_balance_for_rent_exemption(data_len)
+            .await
+            .map_err(read_error)
+    }
+
+    async fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        self.client
+            .send_and_confirm_transaction(transaction)
+            .await
+            .map_err(classify_send_error)
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        self.client
+            .send_transaction(transaction)
+            .await
+            .map_err(classify_send_error)
+    }
+
+    async fn send_and_confirm_versioned_transaction(&self, transaction: &VersionedTransaction) -> Result<Signature> {
+        self.client
+            .send_and_confirm_transaction(transaction)
+            .await
+            .map_err(classify_send_error)
+    }
+
+    async fn send_versioned_transaction(&self, transaction: &VersionedTransaction) -> Result<Signature> {
+        self.client
+            .send_transaction(transaction)
+            .await
+            .map_err(classify_send_error)
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<SimulatedTransaction> {
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: true,
+            commitment: Some(self.client.commitment()),
+            accounts: Some(RpcSimulateTransactionAccountsConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                addresses: addresses.iter().map(|a| a.to_string()).collect(),
+            }),
+            ..RpcSimulateTransactionConfig::default()
+        };
+        let result = self
+            .client
+            .simulate_transaction_with_config(transaction, config)
+            .await
+            .map_err(read_error)?
+            .value;
+
+        Ok(SimulatedTransaction {
+            err: result.err,
+            logs: result.logs.unwrap_or_default(),
+            units_consumed: result.units_consumed,
+            accounts: result.accounts.map(|accounts| {
+                accounts
+                    .into_iter()
+                    .map(|account| account.and_then(|a| a.decode::<Account>()))
+                    .collect()
+            }),
+        })
+    }
+
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> Result<u64> {
+        let fee = match message {
+            VersionedMessage::Legacy(message) => self.client.get_fee_for_message(message).await,
+            VersionedMessage::V0(message) => self.client.get_fee_for_message(message).await,
+        };
+        fee.map_err(read_error)
+    }
+
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool> {
+        self.client
+            .is_blockhash_valid(blockhash, self.client.commitment())
+            .await
+            .map_err(read_error)
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<std::result::Result<(), TransactionError>>> {
+        // Processed, and searched beyond the recent status cache, so a
+        // transaction that has landed at all is never taken for a lost one
+        self.client
+            .get_signature_status_with_commitment_and_history(signature, CommitmentConfig::processed(), true)
+            .await
+            .map_err(read_error)
+    }
+
+    async fn get_signature_statuses(&self, signatures: &[Signature]) -> Result<Vec<Option<SignatureStatus>>> {
+        let statuses = self
+            .client
+            .get_signature_statuses(signatures)
+            .await
+            .map_err(read_error)?
+            .value;
+
+        Ok(statuses
+            .into_iter()
+            .map(|status| {
+                status.map(|status| {
+                    let commitment = if status.satisfies_commitment(CommitmentConfig::finalized()) {
+                        Commitment::Finalized
+                    } else if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                        Commitment::Confirmed
+                    } else {
+                        Commitment::Processed
+                    };
+                    SignatureStatus {
+                        slot: status.slot,
+                        commitment,
+                        err: status.err,
+                    }
+                })
+            })
+            .collect())
+    }
+
+    async fn get_program_accounts(&self, program_id: &Pubkey) -> Result<Vec<(Pubkey, Account)>> {
+        self.client
+            .get_program_accounts(program_id)
+            .await
+            .map_err(read_error)
+    }
+
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        let fees = self
+            .client
+            .get_recent_prioritization_fees(accounts)
+            .await
+            .map_err(read_error)?;
+        Ok(fees.into_iter().map(|fee| fee.prioritization_fee).collect())
+    }
+}
+
+/// Map a failed submission onto the error the retry pipeline classifies.
+fn classify_send_error(error: ClientError) -> BlockchainError {
+    if let Some(transaction_error) = error.get_transaction_error() {
+        return match transaction_error {
+            TransactionError::BlockhashNotFound => BlockchainError::BlockhashExpired,
+            TransactionError::InsufficientFundsForFee => BlockchainError::InsufficientFunds,
+            TransactionError::InstructionError(..) => {
+                BlockchainError::ProgramError(transaction_error.to_string())
+            }
+            other => BlockchainError::TransactionError(other.to_string()),
+        };
+    }
+
+    if let Some(transport_error) = transport_error(&error) {
+        return transport_error;
+    }
+    match error.kind() {
+        // Raised when confirmation gives up, usually because the blockhash expired
+        ClientErrorKind::RpcError(RpcError::ForUser(message))
+            if message.starts_with("unable to confirm transaction") =>
+        {
+            BlockchainError::BlockhashExpired
+        }
+        _ => BlockchainError::TransactionError(error.to_string()),
+    }
+}
+
+/// Map a failed read, keeping transport failures apart so routing can fail over.
+fn read_error(error: ClientError) -> BlockchainError {
+    transport_error(&error).unwrap_or_else(|| BlockchainError::RpcError(error.to_string()))
+}
+
+/// Failures of the endpoint rather than of the request.
+fn transport_error(error: &ClientError) -> Option<BlockchainError> {
+    match error.kind() {
+        ClientErrorKind::Io(e) => Some(BlockchainError::NetworkError(e.to_string())),
+        ClientErrorKind::Reqwest(e) if e.status().map(|s| s.as_u16()) == Some(429) => {
+            Some(BlockchainError::RateLimited(e.to_string()))
+        }
+        ClientErrorKind::Reqwest(e) => Some(BlockchainError::NetworkError(e.to_string())),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, message, .. })
+            if *code == JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+                || *code == JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED =>
+        {
+            Some(BlockchainError::NodeBehind(message.clone()))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,352 @@
+//! Pacing the calls made to one RPC endpoint.
+//!
+//! Public endpoints throttle by IP well below what a report over many
+//! campaigns can fire, so every call waits for a token from a per-endpoint
+//! bucket, and identical account reads already in flight share one request
+//! instead of each sending their own.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash as StdHash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+use super::{ChainBackend, EndpointStats, SignatureStatus, SimulatedTransaction};
+use crate::errors::Result;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    /// Sustained requests per second to each endpoint; 0 disables the limit.
+    pub requests_per_second: u32,
+    /// Requests that may go out back to back after a quiet spell.
+    pub burst: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        // The public clusters allow 100 requests per 10 seconds per IP
+        Self {
+            requests_per_second: 10,
+            burst: 10,
+        }
+    }
+}
+
+/// Token bucket refilled at `requests_per_second`.
+///
+/// Waiters queue on the async mutex, so they are served in arrival order.
+struct RateLimiter {
+    limit: RateLimit,
+    bucket: tokio::sync::Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    fn new(limit: RateLimit) -> Self {
+        let capacity = limit.burst.max(1) as f64;
+        Self {
+            limit,
+            bucket: tokio::sync::Mutex::new((capacity, Instant::now())),
+        }
+    }
+
+    async fn acquire(&self) {
+        if self.limit.requests_per_second == 0 {
+            return;
+        }
+        let rate = self.limit.requests_per_second as f64;
+        let capacity = self.limit.burst.max(1) as f64;
+
+        let mut bucket = self.bucket.lock().await;
+        let (tokens, refilled_at) = &mut *bucket;
+        let now = Instant::now();
+        *tokens = (*tokens + now.duration_since(*refilled_at).as_secs_f64() * rate).min(capacity);
+        *refilled_at = now;
+        if *tokens < 1.0 {
+            tokio::time::sleep(Duration::from_secs_f64((1.0 - *tokens) / rate)).await;
+            *tokens = 1.0;
+            *refilled_at = Instant::now();
+        }
+        *tokens -= 1.0;
+    }
+}
+
+type InFlight<K, V> = Mutex<HashMap<K, Arc<OnceCell<Result<V>>>>>;
+
+/// Shares one fetch between concurrent callers asking for the same key.
+///
+/// Nothing is cached: the entry is removed as soon as the caller that
+/// started the fetch is done with it.
+struct Coalescer<K, V> {
+    in_flight: InFlight<K, V>,
+}
+
+impl<K: Eq + StdHash + Clone, V: Clone> Coalescer<K, V> {
+    fn new() -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn run<F, Fut>(&self, key: K, fetch: F) -> Result<V>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V>>,
+    {
+        let (cell, _leader) = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key) {
+                Some(cell) => (cell.clone(), None),
+                None => {
+                    let cell = Arc::new(OnceCell::new());
+                    in_flight.insert(key.clone(), cell.clone());
+                    (cell, Some(Leader { in_flight: &self.in_flight, key }))
+                }
+            }
+        };
+        // If the leader is cancelled mid-fetch, a waiting caller runs its own
+        cell.get_or_init(fetch).await.clone()
+    }
+}
+
+/// Clears the in-flight entry when the caller that created it finishes or is dropped.
+struct Leader<'a, K: Eq + StdHash, V> {
+    in_flight: &'a InFlight<K, V>,
+    key: K,
+}
+
+impl<K: Eq + StdHash, V> Drop for Leader<'_, K, V> {
+    fn drop(&mut self) {
+        self.in_flight.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// Wraps the backend for one endpoint with a rate limit and read coalescing.
+pub struct ThrottledBackend {
+    inner: Arc<dyn ChainBackend>,
+    limiter: RateLimiter,
+    balances: Coalescer<Pubkey, u64>,
+    account_data: Coalescer<Pubkey, Vec<u8>>,
+    accounts: Coalescer<Pubkey, Option<Account>>,
+    multiple_accounts: Coalescer<Vec<Pubkey>, Vec<Option<Account>>>,
+    program_accounts: Coalescer<Pubkey, Vec<(Pubkey, Account)>>,
+}
+
+impl ThrottledBackend {
+    pub fn new(inner: Arc<dyn ChainBackend>, limit: RateLimit) -> Self {
+        Self {
+            inner,
+            limiter: RateLimiter::new(limit),
+            balances: Coalescer::new(),
+            account_data: Coalescer::new(),
+            accounts: Coalescer::new(),
+            multiple_accounts: Coalescer::new(),
+            program_accounts: Coalescer::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl ChainBackend for ThrottledBackend {
+    async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
+        self.balances
+            .run(*pubkey, || async {
+                self.limiter.acquire().await;
+                self.inner.get_balance(pubkey).await
+            })
+            .await
+    }
+
+    async fn get_account_data(&self, pubkey: &Pubkey) -> Result<Vec<u8>> {
+        self.account_data
+            .run(*pubkey, || async {
+                self.limiter.acquire().await;
+                self.inner.get_account_data(pubkey).await
+            })
+            .await
+    }
+
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        self.accounts
+            .run(*pubkey, || async {
+                self.limiter.acquire().await;
+                self.inner.get_account(pubkey).await
+            })
+            .await
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        self.multiple_accounts
+            .run(pubkeys.to_vec(), || async {
+                self.limiter.acquire().await;
+                self.inner.get_multiple_accounts(pubkeys).await
+            })
+            .await
+    }
+
+    async fn get_latest_blockhash(&self) -> Result<Hash> {
+        self.limiter.acquire().await;
+        self.inner.get_latest_blockhash().await
+    }
+
+    async fn get_slot(&self) -> Result<u64> {
+        self.limiter.acquire().await;
+        self.inner.get_slot().await
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        self.limiter.acquire().await;
+        self.inner.get_minimum_balance_for_rent_exemption(data_len).await
+    }
+
+    async fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        self.limiter.acquire().await;
+        self.inner.send_and_confirm_transaction(transaction).await
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        self.limiter.acquire().await;
+        self.inner.send_transaction(transaction).await
+    }
+
+    async fn send_and_confirm_versioned_transaction(&self, transaction: &VersionedTransaction) -> Result<Signature> {
+        self.limiter.acquire().await;
+        self.inner.send_and_confirm_versioned_transaction(transaction).await
+    }
+
+    async fn send_versioned_transaction(&self, transaction: &VersionedTransaction) -> Result<Signature> {
+        self.limiter.acquire().await;
+        self.inner.send_versioned_transaction(transaction).await
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        addresses: &[Pubkey],
+    ) -> Result<SimulatedTransaction> {
+        self.limiter.acquire().await;
+        self.inner.simulate_transaction(transaction, addresses).await
+    }
+
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> Result<u64> {
+        self.limiter.acquire().await;
+        self.inner.get_fee_for_message(message).await
+    }
+
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool> {
+        self.limiter.acquire().await;
+        self.inner.is_blockhash_valid(blockhash).await
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<std::result::Result<(), TransactionError>>> {
+        self.limiter.acquire().await;
+        self.inner.get_signature_status(signature).await
+    }
+
+    async fn get_signature_statuses(&self, signatures: &[Signature]) -> Result<Vec<Option<SignatureStatus>>> {
+        self.limiter.acquire().await;
+        self.inner.get_signature_statuses(signatures).await
+    }
+
+    async fn get_program_accounts(&self, program_id: &Pubkey) -> Result<Vec<(Pubkey, Account)>> {
+        self.program_accounts
+            .run(*program_id, || async {
+                self.limiter.acquire().await;
+                self.inner.get_program_accounts(program_id).await
+            })
+            .await
+    }
+
+    async fn get_recent_prioritization_fees(&self, accounts: &[Pubkey]) -> Result<Vec<u64>> {
+        self.limiter.acquire().await;
+        self.inner.get_recent_prioritization_fees(accounts).await
+    }
+
+    fn endpoint_stats(&self) -> Vec<EndpointStats> {
+        self.inner.endpoint_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use crate::errors::BlockchainError;
+
+    fn throttled(limit: RateLimit) -> (ThrottledBackend, Arc<MemoryBackend>) {
+        let memory = Arc::new(MemoryBackend::new());
+        (ThrottledBackend::new(memory.clone(), limit), memory)
+    }
+
+    #[tokio::test]
+    async fn test_paces_requests_after_the_burst() {
+        let (backend, _) = throttled(RateLimit {
+            requests_per_second: 50,
+            burst: 2,
+        });
+
+        let started = Instant::now();
+        for _ in 0..2 {
+            backend.get_slot().await.unwrap();
+        }
+        assert!(started.elapsed() < Duration::from_millis(20));
+
+        // Five more at 50 per second take at least 100ms
+        for _ in 0..5 {
+            backend.get_slot().await.unwrap();
+        }
+        assert!(started.elapsed() >= Duration::from_millis(95), "{:?}", started.elapsed());
+
+        let (unlimited, _) = throttled(RateLimit {
+            requests_per_second: 0,
+            burst: 0,
+        });
+        let started = Instant::now();
+        for _ in 0..50 {
+            unlimited.get_slot().await.unwrap();
+        }
+        assert!(started.elapsed() < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_coalesces_identical_reads_in_flight() {
+        let (backend, memory) = throttled(RateLimit::default());
+        memory.set_latency(Duration::from_millis(50));
+        let wallet = Pubkey::new_unique();
+        memory.set_balance(wallet, 42);
+        let other = Pubkey::new_unique();
+
+        let (a, b, c, d) = tokio::join!(
+            backend.get_balance(&wallet),
+            backend.get_balance(&wallet),
+            backend.get_balance(&wallet),
+            backend.get_balance(&other),
+        );
+        assert_eq!((a.unwrap(), b.unwrap(), c.unwrap(), d.unwrap()), (42, 42, 42, 0));
+        assert_eq!(memory.request_count(), 2);
+
+        // Errors are shared too, and nothing outlives the request
+        memory.set_outage(Some("connection reset"));
+        let (a, b) = tokio::join!(backend.get_account(&wallet), backend.get_account(&wallet));
+        assert!(matches!(a, Err(BlockchainError::NetworkError(_))));
+        assert!(matches!(b, Err(BlockchainError::NetworkError(_))));
+        assert_eq!(memory.request_count(), 3);
+        memory.set_outage(None);
+        assert!(backend.get_account(&wallet).await.unwrap().is_some());
+        assert_eq!(memory.request_count(), 4);
+    }
+}
@@ -0,0 +1,185 @@
+//! Manage the encrypted keystore the blockchain service unlocks at startup.
+
+use mkt4u_blockchain::{
+    keypair::{KeypairSource, SeedPhraseOptions},
+    keystore::{self, Keystore},
+};
+use solana_sdk::signature::{Keypair, Signer};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+const USAGE: &str = "\
+Usage: mkt4u-keystore [--keystore <path>] <command>
+
+Commands:
+  create <name> [--import] [--derivation-path <path>]
+      Add a signer. Generates a new keypair unless --import is given, in which
+      case a keypair file path, JSON array, base58 key or seed phrase is read
+      from a hidden prompt.
+  list
+      Show every signer's public key, including retired ones.
+  rotate <name>
+      Replace a signer with a new keypair. The old one is kept as retired.
+
+The keystore path defaults to $MKT4U_KEYSTORE. The password is read from
+$MKT4U_KEYSTORE_PASSWORD or $MKT4U_KEYSTORE_PASSWORD_FILE, or prompted for.
+Standard signer names: payer, campaign-authority, reward-treasury.";
+
+type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(mut args: Vec<String>) -> CliResult<()> {
+    let path = match take_option(&mut args, "--keystore")? {
+        Some(path) => PathBuf::from(path),
+        None => std::env::var(keystore::KEYSTORE_ENV)
+            .map(PathBuf::from)
+            .map_err(|_| format!("No keystore given: pass --keystore or set {}\n\n{}", keystore::KEYSTORE_ENV, USAGE))?,
+    };
+
+    match args.first().map(String::as_str) {
+        Some("create") => {
+            let import = take_flag(&mut args, "--import");
+            let derivation_path = take_option(&mut args, "--derivation-path")?;
+            let name = positional(&args, "create")?;
+            create(&path, &name, import, derivation_path)
+        }
+        Some("list") => {
+            if args.len() > 1 {
+                return Err("list takes no arguments".into());
+            }
+            list(&path)
+        }
+        Some("rotate") => {
+            let name = positional(&args, "rotate")?;
+            rotate(&path, &name)
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn create(path: &Path, name: &str, import: bool, derivation_path: Option<String>) -> CliResult<()> {
+    let (mut keystore, password) = if path.exists() {
+        let keystore = Keystore::open(path)?;
+        (keystore, password(false)?)
+    } else {
+        let password = password(true)?;
+        println!("Creating keystore {}", path.display());
+        (Keystore::new(&password)?, password)
+    };
+    let key = keystore.unlock(&password)?;
+
+    let keypair = if import {
+        let value = Zeroizing::new(rpassword::prompt_password(
+            "Keypair (file path, JSON array, base58 or seed phrase): ",
+        )?);
+        let source = KeypairSource::detect(&value);
+        let seed = SeedPhraseOptions {
+            passphrase: match source {
+                KeypairSource::SeedPhrase(_) => {
+                    Zeroizing::new(rpassword::prompt_password("BIP39 passphrase (empty for none): ")?)
+                }
+                _ => Zeroizing::new(String::new()),
+            },
+            derivation_path,
+        };
+        source.load(&seed)?
+    } else {
+        Keypair::new()
+    };
+
+    keystore.insert(&key, name, &keypair)?;
+    keystore.save(path)?;
+    println!("{} {}", name, keypair.pubkey());
+    Ok(())
+}
+
+fn list(path: &Path) -> CliResult<()> {
+    let keystore = Keystore::open(path)?;
+    for entry in keystore.entries() {
+        let created = format_timestamp(entry.created_at);
+        match entry.retired_at {
+            None => println!("{:<20} {:<44} created {}", entry.name, entry.pubkey, created),
+            Some(retired) => println!(
+                "{:<20} {:<44} created {}, retired {}",
+                entry.name,
+                entry.pubkey,
+                created,
+                format_timestamp(retired)
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn rotate(path: &Path, name: &str) -> CliResult<()> {
+    let mut keystore = Keystore::open(path)?;
+    let key = keystore.unlock(&password(false)?)?;
+    let previous = keystore
+        .entries()
+        .into_iter()
+        .find(|entry| entry.name == name && entry.retired_at.is_none())
+        .map(|entry| entry.pubkey);
+
+    let rotated = keystore.rotate(&key, name)?;
+    keystore.save(path)?;
+    println!("{} {} -> {}", name, previous.unwrap_or_default(), rotated);
+    println!("Move any funds or authorities from the old key before discarding it.");
+    Ok(())
+}
+
+/// The password from the environment, or prompted (twice when `confirm`).
+fn password(confirm: bool) -> CliResult<Zeroizing<String>> {
+    if let Ok(password) = keystore::password_from_env() {
+        return Ok(password);
+    }
+    let password = Zeroizing::new(rpassword::prompt_password("Keystore password: ")?);
+    if confirm {
+        let again = Zeroizing::new(rpassword::prompt_password("Repeat password: ")?);
+        if *again != *password {
+            return Err("Passwords do not match".into());
+        }
+        if password.is_empty() {
+            return Err("Password must not be empty".into());
+        }
+    }
+    Ok(password)
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+/// Remove `--flag` from `args`, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let found = args.iter().position(|arg| arg == flag);
+    found.map(|i| args.remove(i)).is_some()
+}
+
+/// Remove `--option <value>` from `args`, returning the value.
+fn take_option(args: &mut Vec<String>, option: &str) -> CliResult<Option<String>> {
+    let Some(i) = args.iter().position(|arg| arg == option) else {
+        return Ok(None);
+    };
+    args.remove(i);
+    if i >= args.len() {
+        return Err(format!("{} needs a value", option).into());
+    }
+    Ok(Some(args.remove(i)))
+}
+
+/// The single argument following `command`.
+fn positional(args: &[String], command: &str) -> CliResult<String> {
+    match args {
+        [_, name] if !name.starts_with("--") => Ok(name.clone()),
+        _ => Err(format!("Usage: mkt4u-keystore {} <name>", command).into()),
+    }
+}
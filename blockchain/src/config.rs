@@ -0,0 +1,710 @@
+//! Service configuration: network presets layered with a config file and env vars.
+//!
+//! Values are resolved in increasing priority: the preset for the selected
+//! network, then the file named by `MKT4U_CONFIG` (TOML or JSON), then
+//! `SOLANA_*` environment variables (plus `MKT4U_KEYSTORE` and `MKT4U_SIGNER_*`).
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+use crate::backend::{HealthPolicy, RateLimit};
+use crate::errors::{BlockchainError, Result};
+use crate::fees::ComputeBudget;
+use crate::keypair::SeedPhraseOptions;
+use crate::keystore::KEYSTORE_ENV;
+use crate::signer::SigningPolicy;
+use crate::submit::RetryPolicy;
+use crate::tracker::ConfirmationPolicy;
+
+/// Env var naming the config file to load.
+pub const CONFIG_FILE_ENV: &str = "MKT4U_CONFIG";
+
+/// Program ids that only ever appear in templates and examples.
+const PLACEHOLDER_PROGRAM_IDS: &[&str] = &[
+    // System Program, also `Pubkey::default()`
+    "11111111111111111111111111111111",
+    // Anchor's template program id
+    "Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Network {
+    Localnet,
+    Devnet,
+    Testnet,
+    MainnetBeta,
+    /// Any other cluster; `rpc_url` must be given explicitly.
+    Custom,
+}
+
+impl Network {
+    /// Default JSON-RPC endpoint, `None` for `Custom`.
+    pub fn rpc_url(self) -> Option<&'static str> {
+        match self {
+            Self::Localnet => Some("http://127.0.0.1:8899"),
+            Self::Devnet => Some("https://api.devnet.solana.com"),
+            Self::Testnet => Some("https://api.testnet.solana.com"),
+            Self::MainnetBeta => Some("https://api.mainnet-beta.solana.com"),
+            Self::Custom => None,
+        }
+    }
+
+    /// Default PubSub endpoint, `None` for `Custom`.
+    pub fn ws_url(self) -> Option<&'static str> {
+        match self {
+            Self::Localnet => Some("ws://127.0.0.1:8900"),
+            Self::Devnet => Some("wss://api.devnet.solana.com"),
+            Self::Testnet => Some("wss://api.testnet.solana.com"),
+            Self::MainnetBeta => Some("wss://api.mainnet-beta.solana.com"),
+            Self::Custom => None,
+        }
+    }
+
+    pub fn commitment(self) -> Commitment {
+        match self {
+            // A single local validator never forks, so waiting buys nothing
+            Self::Localnet => Commitment::Processed,
+            _ => Commitment::Confirmed,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = BlockchainError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "localnet" | "localhost" => Ok(Self::Localnet),
+            "devnet" => Ok(Self::Devnet),
+            "testnet" => Ok(Self::Testnet),
+            "mainnet-beta" | "mainnet" => Ok(Self::MainnetBeta),
+            "custom" => Ok(Self::Custom),
+            other => Err(BlockchainError::ValidationError(format!("Unknown network: {}", other))),
+        }
+    }
+}
+
+/// Ordered from least to most final.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl Commitment {
+    pub fn config(self) -> CommitmentConfig {
+        match self {
+            Self::Processed => CommitmentConfig::processed(),
+            Self::Confirmed => CommitmentConfig::confirmed(),
+            Self::Finalized => CommitmentConfig::finalized(),
+        }
+    }
+}
+
+impl FromStr for Commitment {
+    type Err = BlockchainError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "processed" => Ok(Self::Processed),
+            "confirmed" => Ok(Self::Confirmed),
+            "finalized" => Ok(Self::Finalized),
+            other => Err(BlockchainError::ValidationError(format!("Unknown commitment: {}", other))),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockchainConfig {
+    pub network: Network,
+    pub rpc_url: String,
+    /// Further RPC endpoints to fail over to, in order of preference.
+    #[serde(default)]
+    pub fallback_rpc_urls: Vec<String>,
+    /// When an RPC endpoint is considered unfit to route to.
+    #[serde(default)]
+    pub health: HealthPolicy,
+    /// Requests per second allowed to each RPC endpoint.
+    #[serde(default)]
+    pub rate_limit: RateLimit,
+    pub ws_url: String,
+    pub commitment: Commitment,
+    /// How sent transactions are tracked, and how final each kind must get.
+    #[serde(default)]
+    pub confirmations: ConfirmationPolicy,
+    pub program_id: String,
+    /// Address lookup table holding the program's frequently used accounts.
+    /// When set and usable, transactions are sent as v0 through it.
+    pub lookup_table: Option<String>,
+    /// A keypair file path, JSON byte array, base58 string or BIP39 seed
+    /// phrase; see `keypair::KeypairSource`.
+    pub payer_keypair: Option<String>,
+    /// Derivation path applied when `payer_keypair` is a seed phrase.
+    pub payer_derivation_path: Option<String>,
+    /// BIP39 passphrase applied when `payer_keypair` is a seed phrase.
+    pub payer_passphrase: Option<String>,
+    /// Encrypted keystore to take the payer from instead of `payer_keypair`;
+    /// see `keystore`.
+    pub keystore: Option<String>,
+    /// Signing daemon holding the payer key instead of this process.
+    pub remote_signer: Option<RemoteSignerConfig>,
+    /// Limits on what the payer signs, whichever of the above holds it.
+    pub signing_policy: Option<SigningPolicy>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Budget for every transaction unless a caller overrides it.
+    #[serde(default)]
+    pub compute_budget: ComputeBudget,
+    /// Simulate transactions instead of sending them.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl Default for BlockchainConfig {
+    fn default() -> Self {
+        Self::for_network(Network::Devnet)
+    }
+}
+
+impl BlockchainConfig {
+    /// Preset endpoints and commitment for `network`, with a placeholder program id.
+    ///
+    /// `Custom` starts with empty URLs that must be filled in.
+    pub fn for_network(network: Network) -> Self {
+        Self {
+            network,
+            rpc_url: network.rpc_url().unwrap_or_default().to_string(),
+            fallback_rpc_urls: Vec::new(),
+            health: HealthPolicy::default(),
+            rate_limit: RateLimit::default(),
+            ws_url: network.ws_url().unwrap_or_default().to_string(),
+            commitment: network.commitment(),
+            confirmations: ConfirmationPolicy::default(),
+            program_id: PLACEHOLDER_PROGRAM_IDS[0].to_string(),
+            lookup_table: None,
+            payer_keypair: None,
+            payer_derivation_path: None,
+            payer_passphrase: None,
+            keystore: None,
+            remote_signer: None,
+            signing_policy: None,
+            retry: RetryPolicy::default(),
+            compute_budget: ComputeBudget::default(),
+            dry_run: false,
+        }
+    }
+
+    /// Resolve the config from `MKT4U_CONFIG` (if set) and the process environment.
+    pub fn load() -> Result<Self> {
+        let file = match std::env::var(CONFIG_FILE_ENV) {
+            Ok(path) => ConfigLayer::from_file(Path::new(&path))?,
+            Err(_) => ConfigLayer::default(),
+        };
+        let env = ConfigLayer::from_vars(|name| std::env::var(name).ok())?;
+        Self::from_layers(file, env)
+    }
+
+    /// Apply `file` then `env` on top of the preset for whichever network they select.
+    pub fn from_layers(file: ConfigLayer, env: ConfigLayer) -> Result<Self> {
+        let layer = file.merge(env);
+        let network = layer.network.unwrap_or(Network::Devnet);
+        let mut config = Self::for_network(network);
+
+        if let Some(rpc_url) = layer.rpc_url {
+            config.rpc_url = rpc_url;
+        }
+        if let Some(fallback_rpc_urls) = layer.fallback_rpc_urls {
+            config.fallback_rpc_urls = fallback_rpc_urls;
+        }
+        if let Some(health) = layer.health {
+            config.health = health;
+        }
+        if let Some(rate_limit) = layer.rate_limit {
+            config.rate_limit = rate_limit;
+        }
+        config.ws_url = match layer.ws_url {
+            Some(ws_url) => ws_url,
+            None if network == Network::Custom => websocket_url_for(&config.rpc_url),
+            None => config.ws_url,
+        };
+        if let Some(commitment) = layer.commitment {
+            config.commitment = commitment;
+        }
+        if let Some(confirmations) = layer.confirmations {
+            config.confirmations = confirmations;
+        }
+        if let Some(program_id) = layer.program_id {
+            config.program_id = program_id;
+        }
+        if layer.lookup_table.is_some() {
+            config.lookup_table = layer.lookup_table;
+        }
+        if layer.payer_keypair.is_some() {
+            config.payer_keypair = layer.payer_keypair;
+        }
+        if layer.payer_derivation_path.is_some() {
+            config.payer_derivation_path = layer.payer_derivation_path;
+        }
+        if layer.payer_passphrase.is_some() {
+            config.payer_passphrase = layer.payer_passphrase;
+        }
+        if layer.keystore.is_some() {
+            config.keystore = layer.keystore;
+        }
+        if layer.remote_signer.is_some() {
+            config.remote_signer = layer.remote_signer;
+        }
+        if layer.signing_policy.is_some() {
+            config.signing_policy = layer.signing_policy;
+        }
+        if let Some(retry) = layer.retry {
+            config.retry = retry;
+        }
+        if let Some(compute_budget) = layer.compute_budget {
+            config.compute_budget = compute_budget;
+        }
+        if let Some(dry_run) = layer.dry_run {
+            config.dry_run = dry_run;
+        }
+
+        Ok(config)
+    }
+
+    /// Whether this config points at mainnet, by preset or by URL.
+    pub fn is_mainnet(&self) -> bool {
+        self.network == Network::MainnetBeta || self.rpc_url.contains("mainnet")
+    }
+
+    /// Seed phrase inputs for loading `payer_keypair`.
+    pub fn seed_phrase_options(&self) -> SeedPhraseOptions {
+        SeedPhraseOptions {
+            passphrase: Zeroizing::new(self.payer_passphrase.clone().unwrap_or_default()),
+            derivation_path: self.payer_derivation_path.clone(),
+        }
+    }
+
+    /// Check the config is usable before anything is sent with it.
+    pub fn validate(&self) -> Result<()> {
+        if self.rpc_url.is_empty() {
+            return Err(BlockchainError::ValidationError(
+                "rpc_url is required for a custom network".to_string(),
+            ));
+        }
+        if self.fallback_rpc_urls.iter().any(|url| url.trim().is_empty()) {
+            return Err(BlockchainError::ValidationError(
+                "fallback_rpc_urls must not contain empty entries".to_string(),
+            ));
+        }
+        Pubkey::from_str(&self.program_id)
+            .map_err(|e| BlockchainError::InvalidPubkey(format!("program_id: {}", e)))?;
+        if let Some(lookup_table) = &self.lookup_table {
+            Pubkey::from_str(lookup_table)
+                .map_err(|e| BlockchainError::InvalidPubkey(format!("lookup_table: {}", e)))?;
+        }
+        if self.is_mainnet() && PLACEHOLDER_PROGRAM_IDS.contains(&self.program_id.as_str()) {
+            return Err(BlockchainError::ValidationError(format!(
+                "Refusing to run against mainnet with placeholder program id {}",
+                self.program_id
+            )));
+        }
+        let payer_sources = [
+            self.payer_keypair.is_some(),
+            self.keystore.is_some(),
+            self.remote_signer.is_some(),
+        ];
+        if payer_sources.iter().filter(|&&set| set).count() > 1 {
+            return Err(BlockchainError::ValidationError(
+                "Set only one of payer_keypair, keystore and remote_signer".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Secrets are redacted so the config can be logged.
+impl fmt::Debug for BlockchainConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockchainConfig")
+            .field("network", &self.network)
+            .field("rpc_url", &self.rpc_url)
+            .field("fallback_rpc_urls", &self.fallback_rpc_urls)
+            .field("health", &self.health)
+            .field("rate_limit", &self.rate_limit)
+            .field("ws_url", &self.ws_url)
+            .field("commitment", &self.commitment)
+            .field("confirmations", &self.confirmations)
+            .field("program_id", &self.program_id)
+            .field("lookup_table", &self.lookup_table)
+            .field("payer_keypair", &redacted(&self.payer_keypair))
+            .field("payer_derivation_path", &self.payer_derivation_path)
+            .field("payer_passphrase", &redacted(&self.payer_passphrase))
+            .field("keystore", &self.keystore)
+            .field("remote_signer", &self.remote_signer)
+            .field("signing_policy", &self.signing_policy)
+            .field("retry", &self.retry)
+            .field("compute_budget", &self.compute_budget)
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+
+/// Where to reach a signing daemon and the key it is expected to sign with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteSignerConfig {
+    /// `unix:<socket path>` or an `http(s)://` URL.
+    pub url: String,
+    pub pubkey: String,
+}
+
+/// One partial source of configuration; unset fields fall through to lower layers.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigLayer {
+    pub network: Option<Network>,
+    pub rpc_url: Option<String>,
+    pub fallback_rpc_urls: Option<Vec<String>>,
+    pub health: Option<HealthPolicy>,
+    pub rate_limit: Option<RateLimit>,
+    pub ws_url: Option<String>,
+    pub commitment: Option<Commitment>,
+    pub confirmations: Option<ConfirmationPolicy>,
+    pub program_id: Option<String>,
+    pub lookup_table: Option<String>,
+    pub payer_keypair: Option<String>,
+    pub payer_derivation_path: Option<String>,
+    pub payer_passphrase: Option<String>,
+    pub keystore: Option<String>,
+    pub remote_signer: Option<RemoteSignerConfig>,
+    pub signing_policy: Option<SigningPolicy>,
+    pub retry: Option<RetryPolicy>,
+    pub compute_budget: Option<ComputeBudget>,
+    pub dry_run: Option<bool>,
+}
+
+impl ConfigLayer {
+    /// Parse a `.toml` or `.json` file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            BlockchainError::ValidationError(format!("Cannot read {}: {}", path.display(), e))
+        })?;
+        let parse_error =
+            |e: String| BlockchainError::SerializationError(format!("{}: {}", path.display(), e));
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents).map_err(|e| parse_error(e.to_string())),
+            Some("json") => serde_json::from_str(&contents).map_err(|e| parse_error(e.to_string())),
+            _ => Err(BlockchainError::ValidationError(format!(
+                "Config file must be .toml or .json: {}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Read the `SOLANA_*`, `MKT4U_KEYSTORE` and `MKT4U_SIGNER_*` variables through `var`, so tests need not touch the process env.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let remote_signer = match (var("MKT4U_SIGNER_URL"), var("MKT4U_SIGNER_PUBKEY")) {
+            (Some(url), Some(pubkey)) => Some(RemoteSignerConfig { url, pubkey }),
+            (None, None) => None,
+            _ => {
+                return Err(BlockchainError::ValidationError(
+                    "MKT4U_SIGNER_URL and MKT4U_SIGNER_PUBKEY must be set together".to_string(),
+                ))
+            }
+        };
+        Ok(Self {
+            network: var("SOLANA_NETWORK").map(|v| v.parse()).transpose()?,
+            rpc_url: var("SOLANA_RPC_URL"),
+            fallback_rpc_urls: var("SOLANA_RPC_FALLBACK_URLS")
+                .map(|urls| urls.split(',').map(|url| url.trim().to_string()).collect()),
+            health: None,
+            rate_limit: None,
+            ws_url: var("SOLANA_WS_URL"),
+            commitment: var("SOLANA_COMMITMENT").map(|v| v.parse()).transpose()?,
+            confirmations: None,
+            program_id: var("SOLANA_PROGRAM_ID"),
+            lookup_table: var("SOLANA_LOOKUP_TABLE"),
+            payer_keypair: var("SOLANA_PRIVATE_KEY"),
+            payer_derivation_path: var("SOLANA_DERIVATION_PATH"),
+            payer_passphrase: var("SOLANA_SEED_PASSPHRASE"),
+            keystore: var(KEYSTORE_ENV),
+            remote_signer,
+            signing_policy: None,
+            retry: None,
+            compute_budget: None,
+            dry_run: var("SOLANA_DRY_RUN").map(|v| v == "true"),
+        })
+    }
+
+    /// Fields set in `over` win.
+    pub fn merge(self, over: Self) -> Self {
+        Self {
+            network: over.network.or(self.network),
+            rpc_url: over.rpc_url.or(self.rpc_url),
+            fallback_rpc_urls: over.fallback_rpc_urls.or(self.fallback_rpc_urls),
+            health: over.health.or(self.health),
+            rate_limit: over.rate_limit.or(self.rate_limit),
+            ws_url: over.ws_url.or(self.ws_url),
+            commitment: over.commitment.or(self.commitment),
+            confirmations: over.confirmations.or(self.confirmations),
+            program_id: over.program_id.or(self.program_id),
+            lookup_table: over.lookup_table.or(self.lookup_table),
+            payer_keypair: over.payer_keypair.or(self.payer_keypair),
+            payer_derivation_path: over.payer_derivation_path.or(self.payer_derivation_path),
+            payer_passphrase: over.payer_passphrase.or(self.payer_passphrase),
+            keystore: over.keystore.or(self.keystore),
+            remote_signer: over.remote_signer.or(self.remote_signer),
+            signing_policy: over.signing_policy.or(self.signing_policy),
+            retry: over.retry.or(self.retry),
+            compute_budget: over.compute_budget.or(self.compute_budget),
+            dry_run: over.dry_run.or(self.dry_run),
+        }
+    }
+}
+
+impl fmt::Debug for ConfigLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigLayer")
+            .field("network", &self.network)
+            .field("rpc_url", &self.rpc_url)
+            .field("fallback_rpc_urls", &self.fallback_rpc_urls)
+            .field("health", &self.health)
+            .field("rate_limit", &self.rate_limit)
+            .field("ws_url", &self.ws_url)
+            .field("commitment", &self.commitment)
+            .field("confirmations", &self.confirmations)
+            .field("program_id", &self.program_id)
+            .field("lookup_table", &self.lookup_table)
+            .field("payer_keypair", &redacted(&self.payer_keypair))
+            .field("payer_derivation_path", &self.payer_derivation_path)
+            .field("payer_passphrase", &redacted(&self.payer_passphrase))
+            .field("keystore", &self.keystore)
+            .field("remote_signer", &self.remote_signer)
+            .field("signing_policy", &self.signing_policy)
+            .field("retry", &self.retry)
+            .field("compute_budget", &self.compute_budget)
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+
+fn redacted(secret: &Option<String>) -> Option<&'static str> {
+    secret.as_ref().map(|_| "<redacted>")
+}
+
+/// PubSub URL served alongside `rpc_url`, following the validator's port convention.
+fn websocket_url_for(rpc_url: &str) -> String {
+    let Ok(mut url) = reqwest::Url::parse(rpc_url) else {
+        return String::new();
+    };
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    let _ = url.set_scheme(scheme);
+    if let Some(port) = url.port() {
+        let _ = url.set_port(Some(port + 1));
+    }
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+    fn env(vars: &[(&str, &str)]) -> ConfigLayer {
+        let vars: HashMap<String, String> =
+            vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        ConfigLayer::from_vars(|name| vars.get(name).cloned()).unwrap()
+    }
+
+    #[test]
+    fn test_presets() {
+        let config = BlockchainConfig::for_network(Network::Localnet);
+        assert_eq!(config.rpc_url, "http://127.0.0.1:8899");
+        assert_eq!(config.ws_url, "ws://127.0.0.1:8900");
+        assert_eq!(config.commitment, Commitment::Processed);
+
+        let config = BlockchainConfig::for_network(Network::MainnetBeta);
+        assert_eq!(config.ws_url, "wss://api.mainnet-beta.solana.com");
+        assert_eq!(config.commitment, Commitment::Confirmed);
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let file: ConfigLayer = toml::from_str(
+            r#"
+            network = "testnet"
+            program_id = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+            commitment = "finalized"
+
+            [compute_budget]
+            unit_limit = 100000
+            priority_fee = { mode = "fixed", micro_lamports = 5000 }
+            "#,
+        )
+        .unwrap();
+        let config = BlockchainConfig::from_layers(
+            file,
+            env(&[("SOLANA_NETWORK", "devnet"), ("SOLANA_DRY_RUN", "true")]),
+        )
+        .unwrap();
+
+        assert_eq!(config.network, Network::Devnet);
+        assert_eq!(config.rpc_url, "https://api.devnet.solana.com");
+        assert_eq!(config.commitment, Commitment::Finalized);
+        assert_eq!(config.program_id, PROGRAM_ID);
+        assert_eq!(config.compute_budget.unit_limit, Some(100_000));
+        assert!(config.dry_run);
+    }
+
+    #[test]
+    fn test_json_file() {
+        let path = std::env::temp_dir().join(format!("mkt4u-config-{}.json", std::process::id()));
+        std::fs::write(&path, r#"{ "network": "localnet", "rpc_url": "http://10.0.0.5:8899" }"#).unwrap();
+        let layer = ConfigLayer::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let config = BlockchainConfig::from_layers(layer.unwrap(), ConfigLayer::default()).unwrap();
+        assert_eq!(config.network, Network::Localnet);
+        assert_eq!(config.rpc_url, "http://10.0.0.5:8899");
+        assert_eq!(config.ws_url, "ws://127.0.0.1:8900");
+    }
+
+    #[test]
+    fn test_example_file_parses() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config.example.toml");
+        let config = BlockchainConfig::from_layers(
+            ConfigLayer::from_file(&path).unwrap(),
+            ConfigLayer::default(),
+        )
+        .unwrap();
+        assert_eq!(config.network, Network::Devnet);
+        assert!(matches!(config.compute_budget.priority_fee, crate::fees::PriorityFee::Auto { .. }));
+        assert_eq!(config.confirmations, ConfirmationPolicy::default());
+    }
+
+    #[test]
+    fn test_fallback_endpoints() {
+        let file: ConfigLayer = toml::from_str(
+            r#"
+            fallback_rpc_urls = ["https://a.example.com"]
+
+            [health]
+            max_slot_lag = 10
+            "#,
+        )
+        .unwrap();
+        let config = BlockchainConfig::from_layers(file.clone(), ConfigLayer::default()).unwrap();
+        assert_eq!(config.fallback_rpc_urls, vec!["https://a.example.com"]);
+        assert_eq!(config.health.max_slot_lag, 10);
+        assert_eq!(config.health.max_latency_ms, HealthPolicy::default().max_latency_ms);
+
+        let config = BlockchainConfig::from_layers(
+            file,
+            env(&[("SOLANA_RPC_FALLBACK_URLS", "https://b.example.com, https://c.example.com")]),
+        )
+        .unwrap();
+        assert_eq!(config.fallback_rpc_urls, vec!["https://b.example.com", "https://c.example.com"]);
+        assert!(config.validate().is_ok());
+
+        let config = BlockchainConfig::from_layers(
+            ConfigLayer::default(),
+            env(&[("SOLANA_RPC_FALLBACK_URLS", "https://b.example.com,")]),
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_custom_network() {
+        let config = BlockchainConfig::from_layers(
+            ConfigLayer::default(),
+            env(&[("SOLANA_NETWORK", "custom"), ("SOLANA_RPC_URL", "https://rpc.example.com:8899")]),
+        )
+        .unwrap();
+        assert_eq!(config.ws_url, "wss://rpc.example.com:8900/");
+        assert!(config.validate().is_ok());
+
+        let missing_url = BlockchainConfig::from_layers(
+            ConfigLayer::default(),
+            env(&[("SOLANA_NETWORK", "custom")]),
+        )
+        .unwrap();
+        assert!(missing_url.validate().is_err());
+    }
+
+    #[test]
+    fn test_refuses_mainnet_placeholder_program() {
+        let config = BlockchainConfig::for_network(Network::MainnetBeta);
+        assert!(matches!(config.validate(), Err(BlockchainError::ValidationError(_))));
+
+        // A mainnet URL counts as mainnet whatever the preset says
+        let config = BlockchainConfig {
+            rpc_url: "https://solana-mainnet.example.com".to_string(),
+            ..BlockchainConfig::for_network(Network::Custom)
+        };
+        assert!(config.validate().is_err());
+
+        let config = BlockchainConfig {
+            program_id: PROGRAM_ID.to_string(),
+            ..BlockchainConfig::for_network(Network::MainnetBeta)
+        };
+        assert!(config.validate().is_ok());
+
+        // Placeholders are fine off mainnet
+        assert!(BlockchainConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_refuses_two_payer_sources() {
+        let config = BlockchainConfig::from_layers(
+            ConfigLayer::default(),
+            env(&[("SOLANA_PRIVATE_KEY", "~/.config/solana/id.json"), ("MKT4U_KEYSTORE", "keys.json")]),
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        let config = BlockchainConfig::from_layers(
+            ConfigLayer::default(),
+            env(&[
+                ("MKT4U_KEYSTORE", "keys.json"),
+                ("MKT4U_SIGNER_URL", "unix:/run/mkt4u-signer.sock"),
+                ("MKT4U_SIGNER_PUBKEY", PROGRAM_ID),
+            ]),
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        let half_signer = ConfigLayer::from_vars(|name| (name == "MKT4U_SIGNER_URL").then(|| "http://signer".to_string()));
+        assert!(half_signer.is_err());
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let layer = env(&[
+            ("SOLANA_PRIVATE_KEY", "abandon abandon about"),
+            ("SOLANA_SEED_PASSPHRASE", "hunter2"),
+            ("SOLANA_DERIVATION_PATH", "m/44'/501'/0'/0'"),
+        ]);
+        let config = BlockchainConfig::from_layers(ConfigLayer::default(), layer.clone()).unwrap();
+        assert_eq!(config.seed_phrase_options().passphrase.as_str(), "hunter2");
+
+        for debug in [format!("{:?}", layer), format!("{:?}", config)] {
+            assert!(!debug.contains("abandon") && !debug.contains("hunter2"), "{}", debug);
+            assert!(debug.contains("m/44'/501'/0'/0'"));
+        }
+    }
+
+    #[test]
+    fn test_rejects_unknown_values() {
+        assert!(ConfigLayer::from_vars(|name| (name == "SOLANA_NETWORK").then(|| "moon".to_string())).is_err());
+        assert!(toml::from_str::<ConfigLayer>("rpc = \"http://x\"").is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum BlockchainError {
     #[error("RPC error: {0}")]
     RpcError(String),
@@ -14,6 +14,9 @@ pub enum BlockchainError {
     #[error("Invalid public key: {0}")]
     InvalidPubkey(String),
 
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+
     #[error("Serialization error: {0}")]
     SerializationError(String),
 
@@ -28,6 +31,18 @@ pub enum BlockchainError {
 
     #[error("Network error: {0}")]
     NetworkError(String),
+
+    #[error("Blockhash expired before the transaction landed")]
+    BlockhashExpired,
+
+    #[error("RPC node is behind the cluster: {0}")]
+    NodeBehind(String),
+
+    #[error("Rate limited by RPC node: {0}")]
+    RateLimited(String),
+
+    #[error("Signing policy refused the transaction: {0}")]
+    PolicyViolation(String),
 }
 
 pub type Result<T> = std::result::Result<T, BlockchainError>;
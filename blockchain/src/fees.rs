@@ -0,0 +1,201 @@
+//! Compute budget and priority fees attached to outgoing transactions.
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, instruction::Instruction, pubkey::Pubkey,
+    signature::Signature,
+};
+
+use crate::backend::ChainBackend;
+use crate::errors::Result;
+
+/// How much to bid per compute unit, in micro-lamports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum PriorityFee {
+    None,
+    Fixed { micro_lamports: u64 },
+    /// Bid the given percentile of fees recently paid to write the same
+    /// accounts, capped at `max_micro_lamports`.
+    Auto { percentile: u8, max_micro_lamports: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComputeBudget {
+    /// Compute units to request; the runtime default applies when unset.
+    pub unit_limit: Option<u32>,
+    pub priority_fee: PriorityFee,
+}
+
+impl Default for ComputeBudget {
+    fn default() -> Self {
+        Self {
+            unit_limit: None,
+            priority_fee: PriorityFee::None,
+        }
+    }
+}
+
+/// A sent transaction together with the budget it was sent with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionReceipt {
+    pub signature: String,
+    pub compute_unit_limit: Option<u32>,
+    /// Micro-lamports per compute unit, zero when no priority fee was paid.
+    pub compute_unit_price: u64,
+    /// The transaction was only simulated; `signature` never landed.
+    pub dry_run: bool,
+}
+
+impl TransactionReceipt {
+    pub fn new(signature: Signature, budget: &ResolvedBudget) -> Self {
+        Self {
+            signature: signature.to_string(),
+            compute_unit_limit: budget.unit_limit,
+            compute_unit_price: budget.unit_price,
+            dry_run: false,
+        }
+    }
+
+    pub fn dry_run(signature: Signature, budget: &ResolvedBudget) -> Self {
+        Self {
+            dry_run: true,
+            ..Self::new(signature, budget)
+        }
+    }
+}
+
+/// Concrete values chosen for one transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedBudget {
+    pub unit_limit: Option<u32>,
+    pub unit_price: u64,
+}
+
+impl ResolvedBudget {
+    /// ComputeBudget instructions to place ahead of the transaction's own.
+    pub fn instructions(&self) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+        if let Some(units) = self.unit_limit {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(units));
+        }
+        if self.unit_price > 0 {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(self.unit_price));
+        }
+        instructions
+    }
+}
+
+impl ComputeBudget {
+    /// Pick the limit and price for a transaction carrying `instructions`.
+    pub async fn resolve(
+        &self,
+        backend: &dyn ChainBackend,
+        instructions: &[Instruction],
+    ) -> Result<ResolvedBudget> {
+        let unit_price = match &self.priority_fee {
+            PriorityFee::None => 0,
+            PriorityFee::Fixed { micro_lamports } => *micro_lamports,
+            PriorityFee::Auto {
+                percentile,
+                max_micro_lamports,
+            } => {
+                let fees = backend
+                    .get_recent_prioritization_fees(&writable_accounts(instructions))
+                    .await?;
+                percentile_of(fees, *percentile).min(*max_micro_lamports)
+            }
+        };
+
+        Ok(ResolvedBudget {
+            unit_limit: self.unit_limit,
+            unit_price,
+        })
+    }
+}
+
+/// Fee markets are local to written accounts, so only those are sampled.
+fn writable_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut accounts: Vec<Pubkey> = Vec::new();
+    for meta in instructions.iter().flat_map(|ix| &ix.accounts) {
+        if meta.is_writable && !accounts.contains(&meta.pubkey) {
+            accounts.push(meta.pubkey);
+        }
+    }
+    accounts
+}
+
+fn percentile_of(mut fees: Vec<u64>, percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let index = (fees.len() - 1) * percentile.min(100) as usize / 100;
+    fees[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use solana_sdk::{compute_budget, system_instruction};
+
+    #[tokio::test]
+    async fn test_fixed_budget_prepends_instructions() {
+        let backend = MemoryBackend::new();
+        let budget = ComputeBudget {
+            unit_limit: Some(50_000),
+            priority_fee: PriorityFee::Fixed { micro_lamports: 1_000 },
+        };
+
+        let resolved = budget.resolve(&backend, &[]).await.unwrap();
+        assert_eq!(resolved, ResolvedBudget { unit_limit: Some(50_000), unit_price: 1_000 });
+        assert_eq!(
+            resolved.instructions(),
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(50_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_default_budget_adds_nothing() {
+        let backend = MemoryBackend::new();
+        let resolved = ComputeBudget::default().resolve(&backend, &[]).await.unwrap();
+        assert!(resolved.instructions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_auto_fee_uses_percentile_and_cap() {
+        let backend = MemoryBackend::new();
+        backend.set_prioritization_fees(vec![0, 10, 500, 20, 30]);
+        let payer = Pubkey::new_unique();
+        let instructions = [system_instruction::transfer(&payer, &Pubkey::new_unique(), 1)];
+
+        let median = ComputeBudget {
+            unit_limit: None,
+            priority_fee: PriorityFee::Auto { percentile: 50, max_micro_lamports: 1_000 },
+        };
+        assert_eq!(median.resolve(&backend, &instructions).await.unwrap().unit_price, 20);
+
+        let capped = ComputeBudget {
+            unit_limit: None,
+            priority_fee: PriorityFee::Auto { percentile: 100, max_micro_lamports: 100 },
+        };
+        let resolved = capped.resolve(&backend, &instructions).await.unwrap();
+        assert_eq!(resolved.unit_price, 100);
+        assert!(resolved.instructions().iter().all(|ix| ix.program_id == compute_budget::id()));
+    }
+
+    #[test]
+    fn test_writable_accounts_are_deduplicated() {
+        let payer = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let instructions = [
+            system_instruction::transfer(&payer, &recipient, 1),
+            system_instruction::transfer(&payer, &recipient, 2),
+        ];
+        assert_eq!(writable_accounts(&instructions), vec![payer, recipient]);
+    }
+}
@@ -0,0 +1,84 @@
+//! Shared fixtures for the managers' unit tests.
+//!
+//! `MemoryBackend` records transactions without running the program, so
+//! these tests cover how the managers encode instructions and decode
+//! accounts. What the program does with them is tested in `tests/program.rs`.
+
+use borsh::BorshSerialize;
+use mkt4u_program::state::{CampaignAccount, CampaignData, CampaignStatus, MilestoneApproval};
+use solana_sdk::{
+    account::Account,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use std::sync::Arc;
+
+use crate::{backend::MemoryBackend, BlockchainConfig, SolanaClient};
+
+/// Client backed by a fresh `MemoryBackend` with a funded payer and a random program id
+pub(crate) fn memory_client() -> (SolanaClient, Arc<MemoryBackend>, Keypair) {
+    let backend = Arc::new(MemoryBackend::new());
+    let payer = Keypair::new();
+    backend.set_balance(payer.pubkey(), 10 * LAMPORTS_PER_SOL);
+
+    let config = BlockchainConfig {
+        program_id: Pubkey::new_unique().to_string(),
+        payer_keypair: Some(payer.to_base58_string()),
+        ..BlockchainConfig::default()
+    };
+    let client = SolanaClient::with_backend(config, backend.clone()).unwrap();
+    (client, backend, payer)
+}
+
+pub(crate) fn sample_campaign() -> CampaignData {
+    CampaignData {
+        id: "test-campaign-1".to_string(),
+        title: "Test Campaign".to_string(),
+        creator: "test-creator".to_string(),
+        target_amount: 1000000, // 1 SOL in lamports
+        current_amount: 0,
+        start_time: 0,
+        end_time: 86400,
+        status: CampaignStatus::Active,
+        metadata_uri: "https://example.com/metadata".to_string(),
+        milestones: Vec::new(),
+        milestone_approval: MilestoneApproval::default(),
+        mint: None,
+        decimals: CampaignData::SOL_DECIMALS,
+    }
+}
+
+/// Account owned by `program_id` holding `state` Borsh-encoded and
+/// zero-padded to at least `len` bytes, as the program would have written it
+pub(crate) fn program_account<T: BorshSerialize>(program_id: &Pubkey, state: &T, len: usize) -> Account {
+    let mut data = borsh::to_vec(state).unwrap();
+    if data.len() < len {
+        data.resize(len, 0);
+    }
+    Account {
+        lamports: 1,
+        data,
+        owner: *program_id,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Store `state` at a fresh address, or at `address` if given, and return it
+pub(crate) fn store_account<T: BorshSerialize>(
+    backend: &MemoryBackend,
+    program_id: &Pubkey,
+    address: Option<Pubkey>,
+    state: &T,
+    len: usize,
+) -> Pubkey {
+    let address = address.unwrap_or_else(Pubkey::new_unique);
+    backend.set_account(address, program_account(program_id, state, len));
+    address
+}
+
+/// Store a campaign account and return its address
+pub(crate) fn store_campaign(backend: &MemoryBackend, program_id: &Pubkey, state: &CampaignAccount) -> Pubkey {
+    store_account(backend, program_id, None, state, CampaignAccount::LEN)
+}
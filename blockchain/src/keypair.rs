@@ -0,0 +1,273 @@
+//! Loading the payer keypair from the formats Solana tooling produces.
+//!
+//! Error messages describe what was wrong with the input but never include
+//! it, so a misconfigured secret cannot end up in the logs.
+
+use bip39::{Language, Mnemonic, Seed};
+use solana_sdk::{
+    derivation_path::DerivationPath,
+    signature::{keypair_from_seed, keypair_from_seed_and_derivation_path, Keypair, Signer},
+};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::errors::{BlockchainError, Result};
+
+const KEYPAIR_LENGTH: usize = 64;
+
+/// Where a keypair comes from, as detected from a config value.
+#[derive(Clone, PartialEq, Eq)]
+pub enum KeypairSource {
+    /// A JSON byte-array file, as written by `solana-keygen new`.
+    File(PathBuf),
+    /// The contents of such a file, inline.
+    JsonArray(String),
+    /// 64 bytes in base58, as exported by browser wallets.
+    Base58(String),
+    /// A BIP39 mnemonic.
+    SeedPhrase(String),
+}
+
+/// Extra inputs used only when the source is a seed phrase.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SeedPhraseOptions {
+    /// BIP39 passphrase, empty when the wallet has none. Wiped on drop.
+    pub passphrase: Zeroizing<String>,
+    /// e.g. `m/44'/501'/0'/0'`. Without one the seed is used directly,
+    /// matching `solana-keygen recover`.
+    pub derivation_path: Option<String>,
+}
+
+impl Default for SeedPhraseOptions {
+    fn default() -> Self {
+        Self { passphrase: Zeroizing::new(String::new()), derivation_path: None }
+    }
+}
+
+impl KeypairSource {
+    /// Classify `value` by its shape; nothing is read or decoded yet.
+    pub fn detect(value: &str) -> Self {
+        let value = value.trim();
+        if value.starts_with('[') {
+            Self::JsonArray(value.to_string())
+        } else if value.split_whitespace().nth(1).is_some() {
+            Self::SeedPhrase(value.to_string())
+        } else if looks_like_path(value) {
+            Self::File(expand_home(value))
+        } else {
+            Self::Base58(value.to_string())
+        }
+    }
+
+    /// Short name of the format, safe to log.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::File(_) => "keypair file",
+            Self::JsonArray(_) => "JSON keypair",
+            Self::Base58(_) => "base58 keypair",
+            Self::SeedPhrase(_) => "seed phrase",
+        }
+    }
+
+    pub fn load(&self, seed: &SeedPhraseOptions) -> Result<Keypair> {
+        match self {
+            Self::File(path) => {
+                let contents = std::fs::read_to_string(path).map_err(|e| {
+                    invalid(format!("Cannot read keypair file {}: {}", path.display(), e))
+                })?;
+                from_json(&contents)
+                    .map_err(|e| invalid(format!("Keypair file {}: {}", path.display(), e)))
+            }
+            Self::JsonArray(json) => from_json(json).map_err(invalid),
+            Self::Base58(encoded) => {
+                let bytes = bs58::decode(encoded).into_vec().map_err(|_| {
+                    invalid("Base58 keypair contains characters outside the base58 alphabet".to_string())
+                })?;
+                from_bytes(&bytes).map_err(|e| invalid(format!("Base58 keypair {}", e)))
+            }
+            Self::SeedPhrase(phrase) => from_seed_phrase(phrase, seed),
+        }
+    }
+}
+
+/// Secrets are never printed; paths are.
+impl fmt::Debug for KeypairSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => f.debug_tuple("File").field(path).finish(),
+            _ => write!(f, "{}(<redacted>)", self.kind()),
+        }
+    }
+}
+
+impl fmt::Debug for SeedPhraseOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeedPhraseOptions")
+            .field("passphrase", &"<redacted>")
+            .field("derivation_path", &self.derivation_path)
+            .finish()
+    }
+}
+
+/// Detect the format of `value` and load the keypair it describes.
+pub fn read_keypair(value: &str, seed: &SeedPhraseOptions) -> Result<Keypair> {
+    KeypairSource::detect(value).load(seed)
+}
+
+fn invalid(message: String) -> BlockchainError {
+    BlockchainError::InvalidKeypair(message)
+}
+
+fn looks_like_path(value: &str) -> bool {
+    // None of these can appear in base58
+    value.contains(['/', '\\', '.', '~']) || Path::new(value).is_file()
+}
+
+fn expand_home(value: &str) -> PathBuf {
+    match (value.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(value),
+    }
+}
+
+fn from_json(json: &str) -> std::result::Result<Keypair, String> {
+    // serde_json's messages quote the offending value, so they are dropped
+    let bytes: Vec<u8> = serde_json::from_str(json)
+        .map_err(|_| "expected a JSON array of 64 integers between 0 and 255".to_string())?;
+    from_bytes(&bytes)
+}
+
+pub(crate) fn from_bytes(bytes: &[u8]) -> std::result::Result<Keypair, String> {
+    if bytes.len() != KEYPAIR_LENGTH {
+        return Err(format!("has {} bytes, expected {}", bytes.len(), KEYPAIR_LENGTH));
+    }
+    // `Keypair::from_bytes` trusts the public half; check it matches the secret
+    let keypair = keypair_from_seed(&bytes[..32]).map_err(|_| "has an invalid secret key".to_string())?;
+    if keypair.pubkey().as_ref() != &bytes[32..] {
+        return Err("has a public key that does not match its secret key".to_string());
+    }
+    Ok(keypair)
+}
+
+fn from_seed_phrase(phrase: &str, options: &SeedPhraseOptions) -> Result<Keypair> {
+    let words = phrase.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mnemonic = Mnemonic::from_phrase(&words, Language::English)
+        .map_err(|e| invalid(format!("Seed phrase is not valid BIP39: {}", e)))?;
+    let seed = Seed::new(&mnemonic, &options.passphrase);
+
+    match &options.derivation_path {
+        None => keypair_from_seed(seed.as_bytes()),
+        Some(path) => {
+            let path = DerivationPath::from_absolute_path_str(path)
+                .map_err(|e| invalid(format!("Invalid derivation path {}: {}", path, e)))?;
+            keypair_from_seed_and_derivation_path(seed.as_bytes(), Some(path))
+        }
+    }
+    .map_err(|e| invalid(format!("Cannot derive keypair from seed phrase: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The BIP39 test vector mnemonic; never holds funds
+    const PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn json(keypair: &Keypair) -> String {
+        serde_json::to_string(&keypair.to_bytes().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_detect() {
+        assert!(matches!(KeypairSource::detect(" [1, 2, 3] "), KeypairSource::JsonArray(_)));
+        assert!(matches!(KeypairSource::detect(PHRASE), KeypairSource::SeedPhrase(_)));
+        assert!(matches!(KeypairSource::detect("/etc/solana/id.json"), KeypairSource::File(_)));
+        assert!(matches!(KeypairSource::detect("id.json"), KeypairSource::File(_)));
+        let base58 = Keypair::new().to_base58_string();
+        assert!(matches!(KeypairSource::detect(&base58), KeypairSource::Base58(_)));
+    }
+
+    #[test]
+    fn test_loads_each_format() {
+        let keypair = Keypair::new();
+        let none = SeedPhraseOptions::default();
+
+        let path = std::env::temp_dir().join(format!("mkt4u-keypair-{}.json", std::process::id()));
+        std::fs::write(&path, json(&keypair)).unwrap();
+        let from_file = read_keypair(path.to_str().unwrap(), &none);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(from_file.unwrap().pubkey(), keypair.pubkey());
+        assert_eq!(read_keypair(&json(&keypair), &none).unwrap().pubkey(), keypair.pubkey());
+        assert_eq!(read_keypair(&keypair.to_base58_string(), &none).unwrap().pubkey(), keypair.pubkey());
+    }
+
+    #[test]
+    fn test_seed_phrase_matches_solana_keygen() {
+        let direct = read_keypair(PHRASE, &SeedPhraseOptions::default()).unwrap();
+        let expected = keypair_from_seed(Seed::new(
+            &Mnemonic::from_phrase(PHRASE, Language::English).unwrap(),
+            "",
+        )
+        .as_bytes())
+        .unwrap();
+        assert_eq!(direct.pubkey(), expected.pubkey());
+
+        let derived = read_keypair(
+            PHRASE,
+            &SeedPhraseOptions {
+                passphrase: Zeroizing::new(String::new()),
+                derivation_path: Some("m/44'/501'/0'/0'".to_string()),
+            },
+        )
+        .unwrap();
+        assert_ne!(derived.pubkey(), direct.pubkey());
+
+        let with_passphrase = read_keypair(
+            PHRASE,
+            &SeedPhraseOptions { passphrase: Zeroizing::new("hunter2".to_string()), derivation_path: None },
+        )
+        .unwrap();
+        assert_ne!(with_passphrase.pubkey(), direct.pubkey());
+    }
+
+    #[test]
+    fn test_errors_do_not_echo_secret() {
+        let none = SeedPhraseOptions::default();
+        let keypair = Keypair::new();
+        let base58 = keypair.to_base58_string();
+
+        let mut tampered = keypair.to_bytes();
+        tampered[40] ^= 1;
+        let cases = [
+            json(&keypair).replace(']', ",300]"),
+            format!("{}0", &base58[..base58.len() - 1]),
+            bs58::encode(&tampered).into_string(),
+            bs58::encode(&keypair.to_bytes()[..32]).into_string(),
+            PHRASE.replace("about", "abandon"),
+            PHRASE.replace("about", "zzzz"),
+        ];
+        for secret in cases {
+            let message = read_keypair(&secret, &none).unwrap_err().to_string();
+            assert!(message.starts_with("Invalid keypair: "), "{}", message);
+            for word in secret.split([' ', ',', '[', ']']).filter(|w| w.len() > 3) {
+                assert!(!message.contains(word), "{} leaks {}", message, word);
+            }
+        }
+
+        let missing = read_keypair("/nonexistent/id.json", &none).unwrap_err().to_string();
+        assert!(missing.contains("/nonexistent/id.json"));
+
+        let bad_path = SeedPhraseOptions { passphrase: Zeroizing::new(String::new()), derivation_path: Some("m/x".to_string()) };
+        assert!(read_keypair(PHRASE, &bad_path).unwrap_err().to_string().contains("derivation path"));
+    }
+
+    #[test]
+    fn test_debug_redacts() {
+        let source = KeypairSource::detect(PHRASE);
+        assert!(!format!("{:?}", source).contains("abandon"));
+        let options = SeedPhraseOptions { passphrase: Zeroizing::new("hunter2".to_string()), derivation_path: None };
+        assert!(!format!("{:?}", options).contains("hunter2"));
+    }
+}
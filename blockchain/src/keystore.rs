@@ -0,0 +1,413 @@
+//! Password-protected keystore holding the service's named signers.
+//!
+//! The file is JSON. A key is stretched from the password with
+//! PBKDF2-HMAC-SHA256 and every keypair is sealed with AES-256-GCM-SIV,
+//! bound to its name and public key so entries cannot be swapped. Public
+//! keys stay readable, so the keystore can be listed without the password.
+//! Derived keys and decrypted bytes are zeroized when dropped, as are the
+//! `Keypair`s handed out.
+
+use aes_gcm_siv::aead::{Aead, NewAead, Payload};
+use aes_gcm_siv::{Aes256GcmSiv, Nonce};
+use hmac::Hmac;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::errors::{BlockchainError, Result};
+
+/// Signer that pays for and signs every manager transaction.
+pub const PAYER: &str = "payer";
+pub const CAMPAIGN_AUTHORITY: &str = "campaign-authority";
+pub const REWARD_TREASURY: &str = "reward-treasury";
+
+/// Env var naming the keystore file the service unlocks at startup.
+pub const KEYSTORE_ENV: &str = "MKT4U_KEYSTORE";
+pub const PASSWORD_ENV: &str = "MKT4U_KEYSTORE_PASSWORD";
+/// Alternative to `PASSWORD_ENV` for secrets mounted as files.
+pub const PASSWORD_FILE_ENV: &str = "MKT4U_KEYSTORE_PASSWORD_FILE";
+
+/// OWASP's 2023 recommendation for PBKDF2-HMAC-SHA256.
+pub const DEFAULT_KDF_ITERATIONS: u32 = 600_000;
+
+const VERSION: u8 = 1;
+const KDF_ALGORITHM: &str = "pbkdf2-sha256";
+const VERIFIER_AAD: &[u8] = b"mkt4u-keystore";
+
+#[derive(Serialize, Deserialize)]
+pub struct Keystore {
+    version: u8,
+    kdf: Kdf,
+    /// An empty message sealed under the key, so a wrong password is caught
+    /// even while the keystore has no signers.
+    verifier: Sealed,
+    signers: BTreeMap<String, SealedSigner>,
+    /// Keys replaced by `rotate`, kept so funds left on them can be moved.
+    #[serde(default)]
+    retired: Vec<RetiredSigner>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Kdf {
+    algorithm: String,
+    iterations: u32,
+    salt: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SealedSigner {
+    pubkey: String,
+    created_at: i64,
+    #[serde(flatten)]
+    sealed: Sealed,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RetiredSigner {
+    name: String,
+    retired_at: i64,
+    #[serde(flatten)]
+    signer: SealedSigner,
+}
+
+/// What `Keystore::entries` reports; never includes key material.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeystoreEntry {
+    pub name: String,
+    pub pubkey: String,
+    pub created_at: i64,
+    /// Set for keys replaced by a rotation.
+    pub retired_at: Option<i64>,
+}
+
+/// Encryption key derived from the password, valid for the keystore it came from.
+pub struct KeystoreKey {
+    key: Zeroizing<[u8; 32]>,
+    salt: String,
+}
+
+impl Keystore {
+    /// An empty keystore protected by `password`.
+    pub fn new(password: &str) -> Result<Self> {
+        Self::with_iterations(password, DEFAULT_KDF_ITERATIONS)
+    }
+
+    pub fn with_iterations(password: &str, iterations: u32) -> Result<Self> {
+        if iterations == 0 {
+            return Err(BlockchainError::ValidationError(
+                "Keystore KDF needs at least one iteration".to_string(),
+            ));
+        }
+        let kdf = Kdf {
+            algorithm: KDF_ALGORITHM.to_string(),
+            iterations,
+            salt: bs58::encode(rand::random::<[u8; 16]>()).into_string(),
+        };
+        let key = kdf.derive(password)?;
+        Ok(Self {
+            version: VERSION,
+            verifier: seal(&key, VERIFIER_AAD, &[])?,
+            kdf,
+            signers: BTreeMap::new(),
+            retired: Vec::new(),
+        })
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            BlockchainError::ValidationError(format!("Cannot read keystore {}: {}", path.display(), e))
+        })?;
+        let keystore: Self = serde_json::from_str(&contents).map_err(|e| {
+            BlockchainError::SerializationError(format!("Keystore {}: {}", path.display(), e))
+        })?;
+        if keystore.version != VERSION || keystore.kdf.algorithm != KDF_ALGORITHM {
+            return Err(BlockchainError::SerializationError(format!(
+                "Keystore {} has unsupported version {} ({})",
+                path.display(),
+                keystore.version,
+                keystore.kdf.algorithm
+            )));
+        }
+        Ok(keystore)
+    }
+
+    /// Write atomically, readable only by the owner on Unix.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let write_error = |e: std::io::Error| {
+            BlockchainError::ValidationError(format!("Cannot write keystore {}: {}", path.display(), e))
+        };
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+
+        let temp = path.with_extension("tmp");
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&temp).map_err(write_error)?;
+        file.write_all(json.as_bytes()).map_err(write_error)?;
+        file.sync_all().map_err(write_error)?;
+        fs::rename(&temp, path).map_err(write_error)
+    }
+
+    /// Derive the key from `password`, failing if it is the wrong one.
+    pub fn unlock(&self, password: &str) -> Result<KeystoreKey> {
+        let key = self.kdf.derive(password)?;
+        open(&key, VERIFIER_AAD, &self.verifier).map_err(|_| {
+            BlockchainError::InvalidKeypair("Wrong keystore password or corrupted keystore".to_string())
+        })?;
+        Ok(key)
+    }
+
+    /// Active signers by name, then retired ones oldest first.
+    pub fn entries(&self) -> Vec<KeystoreEntry> {
+        let active = self.signers.iter().map(|(name, signer)| KeystoreEntry {
+            name: name.clone(),
+            pubkey: signer.pubkey.clone(),
+            created_at: signer.created_at,
+            retired_at: None,
+        });
+        let retired = self.retired.iter().map(|retired| KeystoreEntry {
+            name: retired.name.clone(),
+            pubkey: retired.signer.pubkey.clone(),
+            created_at: retired.signer.created_at,
+            retired_at: Some(retired.retired_at),
+        });
+        active.chain(retired).collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.signers.contains_key(name)
+    }
+
+    /// Store `keypair` as `name`, which must not already exist.
+    pub fn insert(&mut self, key: &KeystoreKey, name: &str, keypair: &Keypair) -> Result<()> {
+        self.check_key(key)?;
+        if name.is_empty() {
+            return Err(BlockchainError::ValidationError("Signer name must not be empty".to_string()));
+        }
+        if self.contains(name) {
+            return Err(BlockchainError::ValidationError(format!(
+                "Keystore already has a signer named {}; rotate it instead",
+                name
+            )));
+        }
+        let signer = SealedSigner::seal(key, name, keypair)?;
+        self.signers.insert(name.to_string(), signer);
+        Ok(())
+    }
+
+    /// Decrypt the signer stored as `name`.
+    pub fn signer(&self, key: &KeystoreKey, name: &str) -> Result<Keypair> {
+        self.check_key(key)?;
+        let signer = self.signers.get(name).ok_or_else(|| {
+            BlockchainError::InvalidKeypair(format!("Keystore has no signer named {}", name))
+        })?;
+        signer.open(key, name)
+    }
+
+    /// Replace `name` with a freshly generated keypair and return its public key.
+    ///
+    /// The old keypair is kept as a retired entry.
+    pub fn rotate(&mut self, key: &KeystoreKey, name: &str) -> Result<Pubkey> {
+        // Opening proves the key before anything is replaced
+        self.signer(key, name)?;
+
+        let keypair = Keypair::new();
+        let replacement = SealedSigner::seal(key, name, &keypair)?;
+        let previous = self.signers.insert(name.to_string(), replacement);
+        self.retired.extend(previous.map(|signer| RetiredSigner {
+            name: name.to_string(),
+            retired_at: chrono::Utc::now().timestamp(),
+            signer,
+        }));
+        Ok(keypair.pubkey())
+    }
+
+    fn check_key(&self, key: &KeystoreKey) -> Result<()> {
+        if key.salt != self.kdf.salt {
+            return Err(BlockchainError::ValidationError(
+                "Key was unlocked from a different keystore".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Kdf {
+    fn derive(&self, password: &str) -> Result<KeystoreKey> {
+        let salt = bs58::decode(&self.salt)
+            .into_vec()
+            .map_err(|e| BlockchainError::SerializationError(format!("Keystore salt: {}", e)))?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), &salt, self.iterations, key.as_mut());
+        Ok(KeystoreKey {
+            key,
+            salt: self.salt.clone(),
+        })
+    }
+}
+
+impl SealedSigner {
+    fn seal(key: &KeystoreKey, name: &str, keypair: &Keypair) -> Result<Self> {
+        let pubkey = keypair.pubkey().to_string();
+        let bytes = Zeroizing::new(keypair.to_bytes());
+        Ok(Self {
+            sealed: seal(key, signer_aad(name, &pubkey).as_bytes(), bytes.as_ref())?,
+            pubkey,
+            created_at: chrono::Utc::now().timestamp(),
+        })
+    }
+
+    fn open(&self, key: &KeystoreKey, name: &str) -> Result<Keypair> {
+        let bytes = open(key, signer_aad(name, &self.pubkey).as_bytes(), &self.sealed).map_err(|_| {
+            BlockchainError::InvalidKeypair(format!("Keystore entry {} is corrupted", name))
+        })?;
+        crate::keypair::from_bytes(&bytes)
+            .map_err(|e| BlockchainError::InvalidKeypair(format!("Keystore entry {} {}", name, e)))
+    }
+}
+
+/// Password for the service keystore, from `PASSWORD_ENV` or `PASSWORD_FILE_ENV`.
+pub fn password_from_env() -> Result<Zeroizing<String>> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(Zeroizing::new(password));
+    }
+    if let Ok(path) = std::env::var(PASSWORD_FILE_ENV) {
+        let contents = Zeroizing::new(fs::read_to_string(&path).map_err(|e| {
+            BlockchainError::ValidationError(format!("Cannot read keystore password file {}: {}", path, e))
+        })?);
+        return Ok(Zeroizing::new(contents.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    Err(BlockchainError::ValidationError(format!(
+        "Keystore is locked: set {} or {}",
+        PASSWORD_ENV, PASSWORD_FILE_ENV
+    )))
+}
+
+fn signer_aad(name: &str, pubkey: &str) -> String {
+    format!("{}:{}", name, pubkey)
+}
+
+fn cipher(key: &KeystoreKey) -> Aes256GcmSiv {
+    Aes256GcmSiv::new_from_slice(key.key.as_ref()).expect("keystore keys are 32 bytes")
+}
+
+fn seal(key: &KeystoreKey, aad: &[u8], plaintext: &[u8]) -> Result<Sealed> {
+    let nonce = rand::random::<[u8; 12]>();
+    let ciphertext = cipher(key)
+        .encrypt(&Nonce::from(nonce), Payload { msg: plaintext, aad })
+        .map_err(|_| BlockchainError::SerializationError("Keystore encryption failed".to_string()))?;
+    Ok(Sealed {
+        nonce: bs58::encode(nonce).into_string(),
+        ciphertext: bs58::encode(ciphertext).into_string(),
+    })
+}
+
+/// Fails if the key, associated data or ciphertext do not match.
+fn open(key: &KeystoreKey, aad: &[u8], sealed: &Sealed) -> std::result::Result<Zeroizing<Vec<u8>>, ()> {
+    let nonce = bs58::decode(&sealed.nonce).into_vec().map_err(|_| ())?;
+    let nonce: [u8; 12] = nonce.try_into().map_err(|_| ())?;
+    let ciphertext = bs58::decode(&sealed.ciphertext).into_vec().map_err(|_| ())?;
+    cipher(key)
+        .decrypt(&Nonce::from(nonce), Payload { msg: &ciphertext, aad })
+        .map(Zeroizing::new)
+        .map_err(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keeps the tests fast; real keystores use the default
+    const ITERATIONS: u32 = 1_000;
+
+    fn keystore_with_payer() -> (Keystore, Keypair) {
+        let mut keystore = Keystore::with_iterations("correct horse", ITERATIONS).unwrap();
+        let key = keystore.unlock("correct horse").unwrap();
+        let payer = Keypair::new();
+        keystore.insert(&key, PAYER, &payer).unwrap();
+        (keystore, payer)
+    }
+
+    #[test]
+    fn test_round_trip_through_file() {
+        let (mut keystore, payer) = keystore_with_payer();
+        let key = keystore.unlock("correct horse").unwrap();
+        let treasury = Keypair::new();
+        keystore.insert(&key, REWARD_TREASURY, &treasury).unwrap();
+
+        let path = std::env::temp_dir().join(format!("mkt4u-keystore-{}.json", std::process::id()));
+        keystore.save(&path).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let reopened = Keystore::open(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(!contents.contains(&payer.to_base58_string()));
+        let reopened = reopened.unwrap();
+        let key = reopened.unlock("correct horse").unwrap();
+        assert_eq!(reopened.signer(&key, PAYER).unwrap().pubkey(), payer.pubkey());
+        assert_eq!(reopened.signer(&key, REWARD_TREASURY).unwrap().pubkey(), treasury.pubkey());
+        assert_eq!(
+            reopened.entries().iter().map(|e| e.name.as_str()).collect::<Vec<_>>(),
+            vec![PAYER, REWARD_TREASURY]
+        );
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let empty = Keystore::with_iterations("correct horse", ITERATIONS).unwrap();
+        assert!(matches!(empty.unlock("battery staple"), Err(BlockchainError::InvalidKeypair(_))));
+
+        let (keystore, _) = keystore_with_payer();
+        assert!(keystore.unlock("battery staple").is_err());
+
+        // A key from another keystore is refused rather than used to encrypt
+        let other = empty.unlock("correct horse").unwrap();
+        assert!(keystore.signer(&other, PAYER).is_err());
+    }
+
+    #[test]
+    fn test_entries_cannot_be_swapped() {
+        let (mut keystore, _) = keystore_with_payer();
+        let key = keystore.unlock("correct horse").unwrap();
+        keystore.insert(&key, CAMPAIGN_AUTHORITY, &Keypair::new()).unwrap();
+
+        let authority = keystore.signers.remove(CAMPAIGN_AUTHORITY).unwrap();
+        keystore.signers.insert(PAYER.to_string(), authority);
+        assert!(matches!(keystore.signer(&key, PAYER), Err(BlockchainError::InvalidKeypair(_))));
+    }
+
+    #[test]
+    fn test_rotate_keeps_retired_key() {
+        let (mut keystore, payer) = keystore_with_payer();
+        let key = keystore.unlock("correct horse").unwrap();
+        assert!(keystore.insert(&key, PAYER, &Keypair::new()).is_err());
+
+        let rotated = keystore.rotate(&key, PAYER).unwrap();
+        assert_ne!(rotated, payer.pubkey());
+        assert_eq!(keystore.signer(&key, PAYER).unwrap().pubkey(), rotated);
+
+        let entries = keystore.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].pubkey, rotated.to_string());
+        assert_eq!(entries[1].pubkey, payer.pubkey().to_string());
+        assert!(entries[1].retired_at.is_some());
+
+        assert!(keystore.rotate(&key, "unknown").is_err());
+    }
+}
@@ -1,130 +1,425 @@
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
 use solana_sdk::{
-    commitment_config::CommitmentConfig,
+    account::Account,
+    address_lookup_table::AddressLookupTableAccount,
     pubkey::Pubkey,
-    signature::{Keypair, Signature},
-    transaction::Transaction,
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    signature::Signature,
+    transaction::{Transaction, VersionedTransaction},
 };
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 
+pub mod backend;
+pub mod config;
 pub mod campaigns;
 pub mod analytics;
 pub mod rewards;
 pub mod errors;
+pub mod fees;
+pub mod keypair;
+pub mod keystore;
+pub mod lookup_tables;
+pub mod nonce;
+pub mod signer;
+pub mod simulate;
+pub mod submit;
+pub mod subscriptions;
+pub mod tracker;
 
+#[cfg(test)]
+mod fixtures;
+
+use crate::backend::{ChainBackend, EndpointStats, FailoverBackend, RpcBackend, ThrottledBackend};
 use crate::errors::BlockchainError;
+use crate::fees::{ComputeBudget, ResolvedBudget, TransactionReceipt};
+use crate::keypair::KeypairSource;
+use crate::keystore::Keystore;
+use crate::signer::{LocalSigner, PolicySigner, RemoteSigner, TransactionSigner};
+use crate::simulate::SimulationReport;
+use crate::tracker::{ConfirmationTracker, TrackedTransaction};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BlockchainConfig {
-    pub rpc_url: String,
-    pub network: String,
-    pub program_id: String,
-    pub payer_keypair: Option<String>,
-}
+pub use crate::config::{BlockchainConfig, Commitment, Network};
 
-impl Default for BlockchainConfig {
-    fn default() -> Self {
-        Self {
-            rpc_url: "https://api.mainnet-beta.solana.com".to_string(),
-            network: "mainnet-beta".to_string(),
-            program_id: "11111111111111111111111111111111".to_string(),
-            payer_keypair: None,
-        }
-    }
+/// Handle to the chain shared by every manager.
+///
+/// Cloning is cheap: all clones use the same backend connection and the same
+/// payer signer, so a service builds one client and hands clones around.
+#[derive(Clone)]
+pub struct SolanaClient {
+    inner: Arc<ClientInner>,
+    compute_budget: ComputeBudget,
 }
 
-pub struct SolanaClient {
-    client: RpcClient,
+struct ClientInner {
+    backend: Arc<dyn ChainBackend>,
     config: BlockchainConfig,
-    payer: Option<Keypair>,
+    payer: Option<Arc<dyn TransactionSigner>>,
+    tracker: ConfirmationTracker,
+    lookup_table: Option<Pubkey>,
 }
 
 impl SolanaClient {
+    /// Connect to `rpc_url`, failing over to `fallback_rpc_urls` when it is unfit.
+    /// Each endpoint is held to `rate_limit` on its own
     pub fn new(config: BlockchainConfig) -> Result<Self> {
-        let client = RpcClient::new_with_commitment(
-            config.rpc_url.clone(),
-            CommitmentConfig::confirmed(),
-        );
+        let endpoints = std::iter::once(&config.rpc_url)
+            .chain(&config.fallback_rpc_urls)
+            .map(|url| {
+                let backend = RpcBackend::new(url.clone(), config.commitment.config());
+                let backend = ThrottledBackend::new(Arc::new(backend), config.rate_limit.clone());
+                (url.clone(), Arc::new(backend) as Arc<dyn ChainBackend>)
+            })
+            .collect();
+        let backend = Arc::new(FailoverBackend::new(endpoints, config.health.clone())?);
+        if !config.fallback_rpc_urls.is_empty() {
+            backend.spawn_health_checks();
+        }
 
-        let payer = if let Some(keypair_str) = &config.payer_keypair {
-            Some(Self::keypair_from_string(keypair_str)?)
-        } else {
-            None
+        Self::with_backend(config, backend)
+    }
+
+    /// Create a client on top of any `ChainBackend`, e.g. `MemoryBackend` in tests
+    pub fn with_backend(config: BlockchainConfig, backend: Arc<dyn ChainBackend>) -> Result<Self> {
+        config.validate()?;
+
+        let payer = Self::load_payer(&config)?;
+        Self::build(config, backend, payer)
+    }
+
+    /// Create a client whose payer is `signer` rather than one named in `config`
+    pub fn with_signer(
+        config: BlockchainConfig,
+        backend: Arc<dyn ChainBackend>,
+        signer: Arc<dyn TransactionSigner>,
+    ) -> Result<Self> {
+        config.validate()?;
+
+        Self::build(config, backend, Some(signer))
+    }
+
+    fn build(
+        config: BlockchainConfig,
+        backend: Arc<dyn ChainBackend>,
+        payer: Option<Arc<dyn TransactionSigner>>,
+    ) -> Result<Self> {
+        let payer = match (payer, &config.signing_policy) {
+            (Some(payer), Some(policy)) => {
+                Some(Arc::new(PolicySigner::new(payer, policy.clone())?) as Arc<dyn TransactionSigner>)
+            }
+            (payer, _) => payer,
         };
 
+        let tracker = ConfirmationTracker::new(backend.clone(), config.confirmations.poll_interval());
+        let lookup_table = config
+            .lookup_table
+            .as_deref()
+            .map(Pubkey::from_str)
+            .transpose()
+            .map_err(|e| BlockchainError::InvalidPubkey(format!("lookup_table: {}", e)))?;
         Ok(Self {
-            client,
-            config,
-            payer,
+            compute_budget: config.compute_budget.clone(),
+            inner: Arc::new(ClientInner {
+                backend,
+                config,
+                payer,
+                tracker,
+                lookup_table,
+            }),
         })
     }
 
+    /// Build a client from `MKT4U_CONFIG` and `SOLANA_*` env vars, see `config`
     pub fn from_env() -> Result<Self> {
         dotenv::dotenv().ok();
-        
-        let config = BlockchainConfig {
-            rpc_url: std::env::var("SOLANA_RPC_URL")
-                .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string()),
-            network: std::env::var("SOLANA_NETWORK")
-                .unwrap_or_else(|_| "mainnet-beta".to_string()),
-            program_id: std::env::var("SOLANA_PROGRAM_ID")
-                .unwrap_or_else(|_| "11111111111111111111111111111111".to_string()),
-            payer_keypair: std::env::var("SOLANA_PRIVATE_KEY").ok(),
-        };
 
+        let config = BlockchainConfig::load()?;
+        log::info!("Using {:?} at {}", config.network, config.rpc_url);
         Self::new(config)
     }
 
-    fn keypair_from_string(keypair_str: &str) -> Result<Keypair> {
-        let bytes = bs58::decode(keypair_str)
-            .into_vec()
-            .map_err(|e| BlockchainError::InvalidKeypair(e.to_string()))?;
-        
-        if bytes.len() != 64 {
-            return Err(BlockchainError::InvalidKeypair("Invalid keypair length".to_string()).into());
-        }
-
-        Ok(Keypair::from_bytes(&bytes)
-            .map_err(|e| BlockchainError::InvalidKeypair(e.to_string()))?)
+    /// The payer from `payer_keypair`, from the keystore unlocked with the
+    /// password in `MKT4U_KEYSTORE_PASSWORD(_FILE)`, or behind `remote_signer`
+    fn load_payer(config: &BlockchainConfig) -> Result<Option<Arc<dyn TransactionSigner>>> {
+        let payer: Arc<dyn TransactionSigner> = if let Some(value) = &config.payer_keypair {
+            let source = KeypairSource::detect(value);
+            let payer = LocalSigner::new(source.load(&config.seed_phrase_options())?);
+            log::info!("Loaded payer {} from {}", payer.pubkey(), source.kind());
+            Arc::new(payer)
+        } else if let Some(path) = &config.keystore {
+            let keystore = Keystore::open(Path::new(path))?;
+            let key = keystore.unlock(&keystore::password_from_env()?)?;
+            let payer = LocalSigner::new(keystore.signer(&key, keystore::PAYER)?);
+            log::info!("Loaded payer {} from keystore {}", payer.pubkey(), path);
+            Arc::new(payer)
+        } else if let Some(remote) = &config.remote_signer {
+            let pubkey = Pubkey::from_str(&remote.pubkey)
+                .map_err(|e| BlockchainError::InvalidPubkey(format!("remote signer: {}", e)))?;
+            log::info!("Payer {} signs through {}", pubkey, remote.url);
+            Arc::new(RemoteSigner::new(&remote.url, pubkey)?)
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(payer))
     }
 
     pub fn get_program_id(&self) -> Result<Pubkey> {
-        Pubkey::from_str(&self.config.program_id)
+        Pubkey::from_str(&self.inner.config.program_id)
             .map_err(|e| BlockchainError::InvalidPubkey(e.to_string()).into())
     }
 
     pub async fn get_balance(&self, pubkey: &Pubkey) -> Result<u64> {
-        self.client
-            .get_balance(pubkey)
-            .map_err(|e| BlockchainError::RpcError(e.to_string()).into())
+        Ok(self.inner.backend.get_balance(pubkey).await?)
     }
 
+    pub async fn get_account_data(&self, pubkey: &Pubkey) -> Result<Vec<u8>> {
+        Ok(self.inner.backend.get_account_data(pubkey).await?)
+    }
+
+    /// Read many accounts in as few requests as possible, in the order given
+    pub async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let mut accounts = Vec::with_capacity(pubkeys.len());
+        for chunk in pubkeys.chunks(backend::MAX_MULTIPLE_ACCOUNTS) {
+            accounts.extend(self.inner.backend.get_multiple_accounts(chunk).await?);
+        }
+        Ok(accounts)
+    }
+
+    pub async fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(self.inner.backend.get_latest_blockhash().await?)
+    }
+
+    pub async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64> {
+        Ok(self.inner.backend.get_minimum_balance_for_rent_exemption(data_len).await?)
+    }
+
+    /// Send an already signed transaction, retrying transient failures.
+    ///
+    /// The client cannot re-sign it, so an expired blockhash is returned as
+    /// `BlockchainError::BlockhashExpired`.
     pub async fn send_transaction(&self, transaction: &Transaction) -> Result<Signature> {
-        self.client
-            .send_and_confirm_transaction(transaction)
-            .map_err(|e| BlockchainError::TransactionError(e.to_string()).into())
+        let backend = self.inner.backend.as_ref();
+        let not_resignable = |_| async { Ok(None) };
+        Ok(submit::submit(backend, &self.inner.config.retry, transaction.clone(), not_resignable).await?)
+    }
+
+    /// Have the payer sign `instructions` and send them behind this handle's
+    /// compute budget, re-signing on a fresh blockhash if the first one
+    /// expires before the transaction lands.
+    ///
+    /// In dry-run mode the transaction is simulated instead and a failing
+    /// simulation is returned as an error.
+    pub async fn send_instructions(&self, instructions: &[Instruction]) -> Result<TransactionReceipt> {
+        let (payer, budget, all_instructions) = self.prepare(instructions).await?;
+        let backend = self.inner.backend.as_ref();
+        let blockhash = backend.get_latest_blockhash().await?;
+        let lookup_tables = self.lookup_tables().await;
+        let sign = |blockhash| signer::sign_versioned_transaction(payer, &all_instructions, blockhash, &lookup_tables);
+        let transaction = sign(blockhash).await?;
+        if self.inner.config.dry_run {
+            return self.dry_run(&transaction, &lookup_tables, &budget).await;
+        }
+
+        let resign = |blockhash| async move { sign(blockhash).await.map(Some) };
+        let signature = submit::submit(backend, &self.inner.config.retry, transaction, resign).await?;
+
+        log::debug!(
+            "Transaction {} sent with compute unit limit {:?} at {} micro-lamports",
+            signature, budget.unit_limit, budget.unit_price
+        );
+        Ok(TransactionReceipt::new(signature, &budget))
+    }
+
+    /// Sign and send `instructions` like `send_instructions`, but return as
+    /// soon as the cluster accepts them and track the transaction up to
+    /// `commitment` in the background.
+    pub async fn send_instructions_tracked(
+        &self,
+        instructions: &[Instruction],
+        commitment: Commitment,
+    ) -> Result<TrackedTransaction> {
+        let (payer, budget, all_instructions) = self.prepare(instructions).await?;
+        let backend = self.inner.backend.as_ref();
+        let blockhash = backend.get_latest_blockhash().await?;
+        let lookup_tables = self.lookup_tables().await;
+        let sign = |blockhash| signer::sign_versioned_transaction(payer, &all_instructions, blockhash, &lookup_tables);
+        let transaction = sign(blockhash).await?;
+        if self.inner.config.dry_run {
+            let receipt = self.dry_run(&transaction, &lookup_tables, &budget).await?;
+            let (_, updates) = tokio::sync::mpsc::unbounded_channel();
+            return Ok(TrackedTransaction { receipt, target: commitment, updates });
+        }
+
+        let resign = |blockhash| async move { sign(blockhash).await.map(Some) };
+        let sent = submit::submit_unconfirmed(backend, &self.inner.config.retry, transaction, resign).await?;
+        let signature = sent.signatures[0];
+        let updates = self.inner.tracker.track(signature, *sent.message.recent_blockhash(), commitment);
+
+        log::debug!("Transaction {} sent, tracking until {:?}", signature, commitment);
+        Ok(TrackedTransaction {
+            receipt: TransactionReceipt::new(signature, &budget),
+            target: commitment,
+            updates,
+        })
+    }
+
+    /// Build an unsigned transaction carrying `instructions` behind this
+    /// handle's compute budget that stays valid until `nonce_account` is
+    /// advanced, so it can be signed hours after it was built.
+    ///
+    /// The payer pays for it. When the nonce's authority is someone else,
+    /// they have to sign it as well.
+    pub async fn build_durable_transaction(&self, instructions: &[Instruction], nonce_account: &Pubkey) -> Result<Transaction> {
+        let (payer, _, all_instructions) = self.prepare(instructions).await?;
+        let nonce = nonce::fetch_nonce(self.inner.backend.as_ref(), nonce_account).await?;
+
+        let mut message = Message::new_with_nonce(all_instructions, Some(&payer.pubkey()), nonce_account, &nonce.authority);
+        message.recent_blockhash = nonce.blockhash();
+        Ok(Transaction::new_unsigned(message))
+    }
+
+    /// Add the payer's signature to a transaction from `build_durable_transaction`
+    pub async fn sign_durable_transaction(&self, transaction: &Transaction) -> Result<Transaction> {
+        let payer = self.get_payer()
+            .ok_or(BlockchainError::InvalidKeypair("No payer configured".to_string()))?;
+        Ok(signer::sign_as_fee_payer(payer, transaction).await?)
     }
 
-    pub fn get_client(&self) -> &RpcClient {
-        &self.client
+    /// Send a fully signed transaction from `build_durable_transaction`.
+    ///
+    /// It cannot expire while its nonce is unused, so a failed send is
+    /// retried with the same transaction rather than a re-signed one.
+    pub async fn send_durable_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        transaction.verify().map_err(|e| {
+            BlockchainError::TransactionError(format!("Durable transaction is not fully signed: {}", e))
+        })?;
+
+        let backend = self.inner.backend.as_ref();
+        let resend = |_| {
+            let transaction = transaction.clone();
+            async move { Ok(Some(transaction)) }
+        };
+        Ok(submit::submit(backend, &self.inner.config.retry, transaction.clone(), resend).await?)
     }
 
-    pub fn get_payer(&self) -> Option<&Keypair> {
-        self.payer.as_ref()
+    /// The configured lookup table, or none if it is unset or unusable, in
+    /// which case messages fall back to legacy
+    async fn lookup_tables(&self) -> Vec<AddressLookupTableAccount> {
+        let Some(table) = &self.inner.lookup_table else {
+            return Vec::new();
+        };
+        match lookup_tables::fetch_lookup_table(self.inner.backend.as_ref(), table).await {
+            Ok(table) => vec![table],
+            Err(e) => {
+                log::warn!("Sending legacy transactions, lookup table {} is unusable: {}", table, e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Simulate `transaction` in place of sending it, failing if the simulation does
+    async fn dry_run(
+        &self,
+        transaction: &VersionedTransaction,
+        lookup_tables: &[AddressLookupTableAccount],
+        budget: &ResolvedBudget,
+    ) -> Result<TransactionReceipt> {
+        let report = simulate::simulate(self.inner.backend.as_ref(), transaction, lookup_tables, budget).await?;
+        if let Some(err) = report.err {
+            return Err(BlockchainError::ProgramError(format!("Simulation failed: {}", err)).into());
+        }
+        log::info!(
+            "Dry run: transaction {} would consume {:?} compute units and cost {} lamports",
+            transaction.signatures[0], report.units_consumed, report.fee
+        );
+        Ok(TransactionReceipt::dry_run(transaction.signatures[0], budget))
+    }
+
+    /// Sign `instructions` exactly as `send_instructions` would and report
+    /// what they would do, without sending anything.
+    pub async fn simulate_instructions(&self, instructions: &[Instruction]) -> Result<SimulationReport> {
+        let (payer, budget, all_instructions) = self.prepare(instructions).await?;
+
+        let backend = self.inner.backend.as_ref();
+        let blockhash = backend.get_latest_blockhash().await?;
+        let lookup_tables = self.lookup_tables().await;
+        let transaction =
+            signer::sign_versioned_transaction(payer, &all_instructions, blockhash, &lookup_tables).await?;
+        Ok(simulate::simulate(backend, &transaction, &lookup_tables, &budget).await?)
+    }
+
+    /// Resolve the compute budget and put its instructions ahead of `instructions`
+    async fn prepare(&self, instructions: &[Instruction]) -> Result<(&dyn TransactionSigner, ResolvedBudget, Vec<Instruction>)> {
+        let payer = self.get_payer()
+            .ok_or(BlockchainError::InvalidKeypair("No payer configured".to_string()))?;
+
+        let budget = self.compute_budget.resolve(self.inner.backend.as_ref(), instructions).await?;
+        let mut all_instructions = budget.instructions();
+        all_instructions.extend_from_slice(instructions);
+        Ok((payer, budget, all_instructions))
+    }
+
+    /// A handle on the same connection and payer that sends with `budget`
+    pub fn with_compute_budget(&self, budget: ComputeBudget) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            compute_budget: budget,
+        }
+    }
+
+    pub fn get_config(&self) -> &BlockchainConfig {
+        &self.inner.config
+    }
+
+    pub fn get_compute_budget(&self) -> &ComputeBudget {
+        &self.compute_budget
+    }
+
+    pub async fn get_program_accounts(&self) -> Result<Vec<(Pubkey, Account)>> {
+        let program_id = self.get_program_id()?;
+        Ok(self.inner.backend.get_program_accounts(&program_id).await?)
+    }
+
+    pub fn get_backend(&self) -> &dyn ChainBackend {
+        self.inner.backend.as_ref()
+    }
+
+    /// Health and traffic per RPC endpoint; empty for single-node test backends
+    pub fn endpoint_stats(&self) -> Vec<EndpointStats> {
+        self.inner.backend.endpoint_stats()
+    }
+
+    /// Tracker following transactions sent with `send_instructions_tracked`;
+    /// signatures sent elsewhere can be handed to it too
+    pub fn confirmations(&self) -> &ConfirmationTracker {
+        &self.inner.tracker
+    }
+
+    pub fn get_payer(&self) -> Option<&dyn TransactionSigner> {
+        self.inner.payer.as_deref()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixtures::memory_client;
+    use solana_sdk::{native_token::LAMPORTS_PER_SOL, signature::{Keypair, Signer}, system_instruction};
 
     #[test]
     fn test_config_creation() {
         let config = BlockchainConfig::default();
-        assert_eq!(config.network, "mainnet-beta");
-        assert_eq!(config.rpc_url, "https://api.mainnet-beta.solana.com");
+        assert_eq!(config.network, Network::Devnet);
+        assert_eq!(config.rpc_url, "https://api.devnet.solana.com");
+    }
+
+    #[test]
+    fn test_client_refuses_invalid_config() {
+        let config = BlockchainConfig::for_network(Network::MainnetBeta);
+        assert!(SolanaClient::new(config).is_err());
     }
 
     #[tokio::test]
@@ -133,4 +428,96 @@ mod tests {
         let client = SolanaClient::new(config);
         assert!(client.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_client_reads_through_backend() {
+        let (client, backend, payer) = memory_client();
+
+        assert_eq!(client.get_payer().unwrap().pubkey(), payer.pubkey());
+        assert_eq!(
+            client.get_balance(&payer.pubkey()).await.unwrap(),
+            backend.get_account(&payer.pubkey()).unwrap().lamports
+        );
+        assert!(client.get_account_data(&Pubkey::new_unique()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_clones_share_connection_and_payer() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SolanaClient>();
+
+        let (client, backend, payer) = memory_client();
+        let payer_pubkey = payer.pubkey();
+        let shared = client.clone();
+        assert!(Arc::ptr_eq(&client.inner, &shared.inner));
+        assert_eq!(shared.get_payer().unwrap().pubkey(), payer.pubkey());
+
+        // Spend from one handle and observe it through the other
+        let handle = tokio::spawn(async move {
+            let blockhash = shared.get_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 0)],
+                Some(&payer.pubkey()),
+                &[&payer],
+                blockhash,
+            );
+            shared.send_transaction(&transaction).await.unwrap()
+        });
+        handle.await.unwrap();
+
+        assert_eq!(backend.sent_transactions().len(), 1);
+        assert_eq!(
+            client.get_balance(&payer_pubkey).await.unwrap(),
+            10 * LAMPORTS_PER_SOL - backend::MemoryBackend::FEE_PER_SIGNATURE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_behind_policy() {
+        let backend = Arc::new(backend::MemoryBackend::new());
+        let payer = Keypair::new();
+        backend.set_balance(payer.pubkey(), LAMPORTS_PER_SOL);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let daemon = signer::SigningDaemon::new(Arc::new(LocalSigner::new(payer.insecure_clone())));
+        let server = tokio::spawn(daemon.serve_http(listener));
+
+        let config = BlockchainConfig {
+            program_id: Pubkey::new_unique().to_string(),
+            remote_signer: Some(config::RemoteSignerConfig { url, pubkey: payer.pubkey().to_string() }),
+            signing_policy: Some(signer::SigningPolicy { max_lamports: Some(1_000), ..Default::default() }),
+            ..BlockchainConfig::default()
+        };
+        let client = SolanaClient::with_backend(config, backend.clone()).unwrap();
+        assert_eq!(client.get_payer().unwrap().pubkey(), payer.pubkey());
+
+        let allowed = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000);
+        let receipt = client.send_instructions(&[allowed]).await.unwrap();
+        assert_eq!(backend.sent_transactions()[0].signatures[0].to_string(), receipt.signature);
+
+        let refused = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_001);
+        let error = client.send_instructions(&[refused]).await.unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(BlockchainError::PolicyViolation(_))));
+        assert_eq!(backend.sent_transactions().len(), 1);
+        server.abort();
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_rpc_calls_do_not_block_runtime() {
+        // Accepts connections but never answers, like a stalled RPC node
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = BlockchainConfig {
+            rpc_url: format!("http://{}", listener.local_addr().unwrap()),
+            ..BlockchainConfig::default()
+        };
+        let client = SolanaClient::new(config).unwrap();
+
+        // On a single-threaded runtime a blocking call would starve the timer
+        let pubkey = Pubkey::new_unique();
+        tokio::select! {
+            _ = client.get_balance(&pubkey) => panic!("stalled RPC call should still be pending"),
+            _ = tokio::time::sleep(std::time::Duration::from_millis(100)) => {}
+        }
+    }
 }
@@ -0,0 +1,255 @@
+//! Address lookup tables for v0 transactions.
+//!
+//! A legacy message spells out every account it touches, which caps a
+//! transaction at a few dozen accounts. A v0 message can refer to accounts by
+//! their index in an on-chain lookup table instead, so a batch over many
+//! campaigns fits in one transaction. The client sends v0 through the table
+//! named by `lookup_table` and falls back to legacy messages whenever that
+//! table cannot be read or has been deactivated.
+
+use anyhow::Result;
+use solana_sdk::{
+    address_lookup_table::{instruction, state::AddressLookupTable, AddressLookupTableAccount},
+    pubkey::Pubkey,
+    system_program,
+};
+
+use crate::{
+    backend::ChainBackend,
+    errors::{self, BlockchainError},
+    fees::TransactionReceipt,
+    SolanaClient,
+};
+
+/// Addresses added per extend transaction, keeping it well under the packet size.
+pub const ADDRESSES_PER_EXTEND: usize = 20;
+
+pub struct LookupTableManager {
+    client: SolanaClient,
+}
+
+impl LookupTableManager {
+    pub fn new(client: SolanaClient) -> Self {
+        Self { client }
+    }
+
+    /// Create an empty lookup table owned by the payer and return its address.
+    ///
+    /// It can be extended straight away, but addresses added to it can only be
+    /// looked up from the slot after they were added.
+    pub async fn create_lookup_table(&self) -> Result<Pubkey> {
+        let payer = self.payer_pubkey()?;
+        // The address is derived from a recent slot the cluster must still remember
+        let recent_slot = self.client.get_backend().get_slot().await?;
+        let (create_table_ix, table) = instruction::create_lookup_table(payer, payer, recent_slot);
+
+        let receipt = self.client.send_instructions(&[create_table_ix]).await?;
+
+        log::info!("Lookup table created: {} with signature: {}", table, receipt.signature);
+        Ok(table)
+    }
+
+    /// Add whichever of `addresses` the table does not hold yet, in as many
+    /// transactions as it takes
+    pub async fn extend_lookup_table(&self, table: &Pubkey, addresses: &[Pubkey]) -> Result<Vec<TransactionReceipt>> {
+        let payer = self.payer_pubkey()?;
+        let current = self.get_lookup_table(table).await?;
+
+        let mut new_addresses: Vec<Pubkey> = Vec::new();
+        for address in addresses {
+            if address != table && !current.addresses.contains(address) && !new_addresses.contains(address) {
+                new_addresses.push(*address);
+            }
+        }
+
+        let mut receipts = Vec::new();
+        for chunk in new_addresses.chunks(ADDRESSES_PER_EXTEND) {
+            let extend_table_ix = instruction::extend_lookup_table(*table, payer, Some(payer), chunk.to_vec());
+            receipts.push(self.client.send_instructions(&[extend_table_ix]).await?);
+        }
+
+        log::info!("Lookup table {} extended by {} addresses", table, new_addresses.len());
+        Ok(receipts)
+    }
+
+    /// Accounts the program's transactions keep touching: every campaign and
+    /// reward program account, and the System Program they are created through
+    pub async fn program_addresses(&self) -> Result<Vec<Pubkey>> {
+        let accounts = self.client.get_program_accounts().await?;
+        let mut addresses = vec![system_program::id()];
+        addresses.extend(accounts.into_iter().map(|(address, _)| address));
+        Ok(addresses)
+    }
+
+    pub async fn get_lookup_table(&self, table: &Pubkey) -> Result<AddressLookupTableAccount> {
+        Ok(fetch_lookup_table(self.client.get_backend(), table).await?)
+    }
+
+    fn payer_pubkey(&self) -> Result<Pubkey> {
+        let payer = self.client.get_payer()
+            .ok_or(BlockchainError::InvalidKeypair("No payer keypair configured".to_string()))?;
+        Ok(payer.pubkey())
+    }
+}
+
+/// Read `table`, refusing one that has been deactivated and will soon be closed.
+pub async fn fetch_lookup_table(backend: &dyn ChainBackend, table: &Pubkey) -> errors::Result<AddressLookupTableAccount> {
+    let data = backend.get_account_data(table).await?;
+    let state = AddressLookupTable::deserialize(&data)
+        .map_err(|e| BlockchainError::SerializationError(format!("Lookup table {}: {}", table, e)))?;
+    if state.meta.deactivation_slot != u64::MAX {
+        return Err(BlockchainError::ValidationError(format!("Lookup table {} is deactivated", table)));
+    }
+    Ok(AddressLookupTableAccount {
+        key: *table,
+        addresses: state.addresses.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+    use std::sync::Arc;
+
+    use solana_sdk::{
+        account::Account,
+        address_lookup_table::{self, state::LookupTableMeta},
+        program_utils::limited_deserialize,
+        signature::Signer,
+        system_instruction,
+    };
+
+    use crate::{backend::MemoryBackend, fixtures::memory_client, BlockchainConfig};
+
+    fn seed_table(backend: &MemoryBackend, meta: LookupTableMeta, addresses: Vec<Pubkey>) -> Pubkey {
+        let table = Pubkey::new_unique();
+        let data = AddressLookupTable { meta, addresses: Cow::Owned(addresses) }.serialize_for_tests().unwrap();
+        backend.set_account(table, Account {
+            lamports: 1,
+            data,
+            owner: address_lookup_table::program::id(),
+            executable: false,
+            rent_epoch: 0,
+        });
+        table
+    }
+
+    fn client_with_table(backend: Arc<MemoryBackend>, payer: &solana_sdk::signature::Keypair, table: Pubkey) -> SolanaClient {
+        let config = BlockchainConfig {
+            program_id: Pubkey::new_unique().to_string(),
+            payer_keypair: Some(payer.to_base58_string()),
+            lookup_table: Some(table.to_string()),
+            ..BlockchainConfig::default()
+        };
+        SolanaClient::with_backend(config, backend).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sends_v0_through_lookup_table() {
+        let (_, backend, payer) = memory_client();
+        let recipients: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let table = seed_table(&backend, LookupTableMeta::default(), recipients.clone());
+        let client = client_with_table(backend.clone(), &payer, table);
+
+        let transfers: Vec<_> = recipients
+            .iter()
+            .map(|recipient| system_instruction::transfer(&payer.pubkey(), recipient, 1))
+            .collect();
+        let receipt = client.send_instructions(&transfers).await.unwrap();
+
+        assert!(backend.sent_transactions().is_empty());
+        let sent = backend.sent_versioned_transactions();
+        assert_eq!(sent[0].signatures[0].to_string(), receipt.signature);
+        let lookups = sent[0].message.address_table_lookups().unwrap();
+        assert_eq!(lookups.len(), 1);
+        assert_eq!(lookups[0].account_key, table);
+        assert_eq!(lookups[0].writable_indexes.len(), recipients.len());
+    }
+
+    #[tokio::test]
+    async fn test_simulates_the_v0_message_it_would_send() {
+        let (_, backend, payer) = memory_client();
+        let recipient = Pubkey::new_unique();
+        let table = seed_table(&backend, LookupTableMeta::default(), vec![recipient]);
+        let config = BlockchainConfig {
+            program_id: Pubkey::new_unique().to_string(),
+            payer_keypair: Some(payer.to_base58_string()),
+            lookup_table: Some(table.to_string()),
+            dry_run: true,
+            ..BlockchainConfig::default()
+        };
+        let client = SolanaClient::with_backend(config, backend.clone()).unwrap();
+        let transfer = system_instruction::transfer(&payer.pubkey(), &recipient, 1);
+
+        let report = client.simulate_instructions(std::slice::from_ref(&transfer)).await.unwrap();
+        assert!(report.succeeded());
+        client.send_instructions(&[transfer]).await.unwrap();
+
+        let simulated = backend.simulated_transactions();
+        assert_eq!(simulated.len(), 2);
+        for transaction in &simulated {
+            let lookups = transaction.message.address_table_lookups().unwrap();
+            assert_eq!(lookups[0].account_key, table);
+            assert!(!transaction.message.static_account_keys().contains(&recipient));
+        }
+        assert!(backend.sent_transactions().is_empty() && backend.sent_versioned_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_legacy_without_usable_table() {
+        let (_, backend, payer) = memory_client();
+        let recipient = Pubkey::new_unique();
+        let deactivated = LookupTableMeta { deactivation_slot: 0, ..LookupTableMeta::default() };
+
+        let missing = Pubkey::new_unique();
+        for (lamports, table) in [(1, missing), (2, seed_table(&backend, deactivated, vec![recipient]))] {
+            let client = client_with_table(backend.clone(), &payer, table);
+            client
+                .send_instructions(&[system_instruction::transfer(&payer.pubkey(), &recipient, lamports)])
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(backend.sent_transactions().len(), 2);
+        assert!(backend.sent_versioned_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_and_extend_lookup_table() {
+        let (client, backend, payer) = memory_client();
+        backend.set_slot(42);
+        let manager = LookupTableManager::new(client);
+
+        let table = manager.create_lookup_table().await.unwrap();
+        assert_eq!(table, address_lookup_table::instruction::derive_lookup_table_address(&payer.pubkey(), 42).0);
+        let create = &backend.sent_transactions()[0];
+        let program_index = create.message.instructions.last().unwrap().program_id_index as usize;
+        assert_eq!(create.message.account_keys[program_index], address_lookup_table::program::id());
+
+        // The memory backend does not execute the create, so seed what it would leave
+        let existing = Pubkey::new_unique();
+        let table = seed_table(&backend, LookupTableMeta::new(payer.pubkey()), vec![existing]);
+        let mut addresses: Vec<Pubkey> = (0..ADDRESSES_PER_EXTEND + 5).map(|_| Pubkey::new_unique()).collect();
+        addresses.push(existing);
+        addresses.push(addresses[0]);
+
+        let receipts = manager.extend_lookup_table(&table, &addresses).await.unwrap();
+        assert_eq!(receipts.len(), 2);
+        let sent = backend.sent_transactions();
+        let added: usize = sent[1..]
+            .iter()
+            .map(|transaction| {
+                let extend = transaction.message.instructions.last().unwrap();
+                match limited_deserialize(&extend.data).unwrap() {
+                    address_lookup_table::instruction::ProgramInstruction::ExtendLookupTable { new_addresses } => {
+                        assert!(!new_addresses.contains(&existing));
+                        new_addresses.len()
+                    }
+                    other => panic!("unexpected instruction {:?}", other),
+                }
+            })
+            .sum();
+        assert_eq!(added, ADDRESSES_PER_EXTEND + 5);
+    }
+}
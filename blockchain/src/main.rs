@@ -1,12 +1,11 @@
 use mkt4u_blockchain::{
-    SolanaClient, BlockchainConfig,
-    campaigns::{CampaignManager, CampaignData},
+    SolanaClient,
+    campaigns::{CampaignManager, CampaignData, CampaignStatus, Milestone, MilestoneApproval},
     analytics::{AnalyticsManager, AnalyticsData},
     rewards::{RewardsManager, RewardProgram, RewardType, RewardCriteria},
 };
 use serde::{Deserialize, Serialize};
 use std::env;
-use tokio;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct ApiResponse<T> {
@@ -20,9 +19,20 @@ struct CreateCampaignRequest {
     id: String,
     title: String,
     creator: String,
+    /// In lamports, or in base units of `mint` for a token campaign
     target_amount: u64,
     duration_days: u32,
     metadata_uri: String,
+    #[serde(default)]
+    milestones: Vec<Milestone>,
+    #[serde(default)]
+    milestone_approval: MilestoneApproval,
+    /// SPL mint to raise, such as USDC, instead of SOL
+    #[serde(default)]
+    mint: Option<String>,
+    /// `draft` to create the campaign without opening it to contributions yet
+    #[serde(default)]
+    status: CampaignStatus,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,8 +59,13 @@ async fn handle_create_campaign(
         current_amount: 0,
         start_time,
         end_time,
-        is_active: true,
+        status: request.status,
         metadata_uri: request.metadata_uri,
+        milestones: request.milestones,
+        milestone_approval: request.milestone_approval,
+        mint: request.mint,
+        // Set from the mint by the program
+        decimals: CampaignData::SOL_DECIMALS,
     };
 
     match campaign_manager.create_campaign(&campaign_data).await {
@@ -116,7 +131,7 @@ async fn handle_record_analytics(
     }
 }
 
-async fn initialize_blockchain_service() -> Result<(CampaignManager, AnalyticsManager, RewardsManager), Box<dyn std::error::Error>> {
+async fn initialize_blockchain_service() -> Result<(SolanaClient, CampaignManager, AnalyticsManager, RewardsManager), Box<dyn std::error::Error>> {
     // Load environment variables
     dotenv::dotenv().ok();
     
@@ -132,14 +147,14 @@ async fn initialize_blockchain_service() -> Result<(CampaignManager, AnalyticsMa
     let program_id = client.get_program_id()?;
     log::info!("Connected to Solana network with program ID: {}", program_id);
     
-    // Initialize managers
+    // Initialize managers sharing the one connection and payer
     let campaign_manager = CampaignManager::new(client.clone());
     let analytics_manager = AnalyticsManager::new(client.clone());
-    let rewards_manager = RewardsManager::new(client);
+    let rewards_manager = RewardsManager::new(client.clone());
     
     log::info!("Blockchain service initialized successfully");
     
-    Ok((campaign_manager, analytics_manager, rewards_manager))
+    Ok((client, campaign_manager, analytics_manager, rewards_manager))
 }
 
 async fn create_sample_reward_program(rewards_manager: &RewardsManager) -> Result<String, Box<dyn std::error::Error>> {
@@ -169,7 +184,7 @@ async fn create_sample_reward_program(rewards_manager: &RewardsManager) -> Resul
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize the blockchain service
-    let (campaign_manager, analytics_manager, rewards_manager) = initialize_blockchain_service().await?;
+    let (client, campaign_manager, analytics_manager, rewards_manager) = initialize_blockchain_service().await?;
     
     // Create a sample reward program
     if env::var("CREATE_SAMPLE_PROGRAM").unwrap_or_default() == "true" {
@@ -187,6 +202,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             target_amount: 5_000_000, // 5 SOL
             duration_days: 30,
             metadata_uri: "https://mkt4u.com/campaign/metadata/001".to_string(),
+            milestones: Vec::new(),
+            milestone_approval: MilestoneApproval::default(),
+            mint: None,
+            status: CampaignStatus::Active,
         };
         
         let campaign_result = handle_create_campaign(&campaign_manager, create_request).await;
@@ -221,5 +240,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     loop {
         tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
         log::debug!("Blockchain service heartbeat");
+        for stats in client.endpoint_stats() {
+            log::debug!("RPC endpoint: {}", serde_json::to_string(&stats)?);
+        }
     }
 }
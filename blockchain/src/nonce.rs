@@ -0,0 +1,243 @@
+//! Durable nonce accounts for transactions signed long after they are built.
+//!
+//! A transaction on a recent blockhash expires within a couple of minutes.
+//! One built against a nonce account instead carries the nonce's stored
+//! value as its blockhash and advances the nonce as its first instruction,
+//! so it stays valid until it lands or the nonce is advanced some other way.
+//! Unsigned transactions travel as base64 of their wire format, which any
+//! Solana wallet or the CLI can sign as well.
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use solana_sdk::{
+    account_utils::StateMut,
+    nonce::{
+        state::{Data, Versions},
+        State,
+    },
+    packet::PACKET_DATA_SIZE,
+    program_utils::limited_deserialize,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    transaction::Transaction,
+};
+
+use crate::{
+    backend::ChainBackend,
+    errors::{self, BlockchainError},
+    fees::TransactionReceipt,
+    SolanaClient,
+};
+
+pub struct NonceManager {
+    client: SolanaClient,
+}
+
+impl NonceManager {
+    pub fn new(client: SolanaClient) -> Self {
+        Self { client }
+    }
+
+    /// Create a rent-exempt nonce account at the address derived from the
+    /// payer and `seed`, with the payer as its authority.
+    ///
+    /// Deriving the address means only the payer has to sign, so the usual
+    /// signers, remote ones included, can create it.
+    pub async fn create_nonce_account(&self, seed: &str) -> Result<Pubkey> {
+        let payer = self.payer_pubkey()?;
+        let nonce_account = self.find_nonce_address(seed)?;
+        let lamports = self.client.get_minimum_balance_for_rent_exemption(State::size()).await?;
+
+        let create_nonce_ixs = system_instruction::create_nonce_account_with_seed(
+            &payer,
+            &nonce_account,
+            &payer,
+            seed,
+            &payer,
+            lamports,
+        );
+        let receipt = self.client.send_instructions(&create_nonce_ixs).await?;
+
+        log::info!("Nonce account created: {} with signature: {}", nonce_account, receipt.signature);
+        Ok(nonce_account)
+    }
+
+    /// Address of the payer's nonce account for `seed`
+    pub fn find_nonce_address(&self, seed: &str) -> Result<Pubkey> {
+        let payer = self.payer_pubkey()?;
+        Pubkey::create_with_seed(&payer, seed, &system_program::id())
+            .map_err(|e| BlockchainError::ValidationError(format!("Nonce seed {:?}: {}", seed, e)).into())
+    }
+
+    /// Move the nonce on, invalidating every transaction built against its
+    /// current value that has not landed yet
+    pub async fn advance_nonce(&self, nonce_account: &Pubkey) -> Result<TransactionReceipt> {
+        let payer = self.payer_pubkey()?;
+        let advance_nonce_ix = system_instruction::advance_nonce_account(nonce_account, &payer);
+
+        let receipt = self.client.send_instructions(&[advance_nonce_ix]).await?;
+
+        log::info!("Nonce account {} advanced with signature: {}", nonce_account, receipt.signature);
+        Ok(receipt)
+    }
+
+    /// Current value and authority of a nonce account
+    pub async fn get_nonce(&self, nonce_account: &Pubkey) -> Result<Data> {
+        Ok(fetch_nonce(self.client.get_backend(), nonce_account).await?)
+    }
+
+    fn payer_pubkey(&self) -> Result<Pubkey> {
+        let payer = self.client.get_payer()
+            .ok_or(BlockchainError::InvalidKeypair("No payer keypair configured".to_string()))?;
+        Ok(payer.pubkey())
+    }
+}
+
+/// Read an initialized nonce account.
+pub async fn fetch_nonce(backend: &dyn ChainBackend, nonce_account: &Pubkey) -> errors::Result<Data> {
+    let account = backend
+        .get_account(nonce_account)
+        .await?
+        .ok_or_else(|| BlockchainError::AccountNotFound(nonce_account.to_string()))?;
+    if account.owner != system_program::id() {
+        return Err(BlockchainError::ValidationError(format!("{} is not a nonce account", nonce_account)));
+    }
+
+    let versions: Versions = account
+        .state()
+        .map_err(|e| BlockchainError::SerializationError(format!("Nonce account {}: {}", nonce_account, e)))?;
+    match versions.state() {
+        State::Initialized(data) => Ok(data.clone()),
+        State::Uninitialized => Err(BlockchainError::ValidationError(format!(
+            "Nonce account {} is not initialized",
+            nonce_account
+        ))),
+    }
+}
+
+/// Encode a transaction, signed or not, for signing elsewhere
+pub fn serialize_transaction(transaction: &Transaction) -> errors::Result<String> {
+    let bytes = bincode::serialize(transaction).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    Ok(BASE64.encode(bytes))
+}
+
+/// Decode a transaction encoded by `serialize_transaction`
+pub fn deserialize_transaction(encoded: &str) -> errors::Result<Transaction> {
+    let bytes = BASE64
+        .decode(encoded.trim())
+        .map_err(|e| BlockchainError::SerializationError(format!("Transaction is not base64: {}", e)))?;
+    if bytes.len() > PACKET_DATA_SIZE {
+        return Err(BlockchainError::SerializationError(format!(
+            "Transaction is {} bytes, more than fits in a packet",
+            bytes.len()
+        )));
+    }
+    limited_deserialize(&bytes).map_err(|e| BlockchainError::SerializationError(format!("Transaction: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        account::Account,
+        hash::Hash,
+        nonce::state::DurableNonce,
+        signature::{Keypair, Signature, Signer},
+        system_instruction::SystemInstruction,
+    };
+
+    use crate::fixtures::memory_client;
+
+    fn nonce_account(authority: &Pubkey, blockhash: &Hash) -> Account {
+        let data = Data::new(*authority, DurableNonce::from_blockhash(blockhash), 5_000);
+        let versions = Versions::new(State::Initialized(data));
+        Account {
+            lamports: 1_447_680,
+            data: bincode::serialize(&versions).unwrap(),
+            owner: system_program::id(),
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_nonce_account_at_seeded_address() {
+        let (client, backend, payer) = memory_client();
+        let manager = NonceManager::new(client);
+
+        let nonce = manager.create_nonce_account("payouts").await.unwrap();
+        assert_eq!(nonce, Pubkey::create_with_seed(&payer.pubkey(), "payouts", &system_program::id()).unwrap());
+
+        // Only the payer signs the create and initialize pair
+        let sent = backend.sent_transactions();
+        assert_eq!(sent[0].signatures.len(), 1);
+        let initialize = sent[0].message.instructions.last().unwrap();
+        assert_eq!(
+            limited_deserialize::<SystemInstruction>(&initialize.data).unwrap(),
+            SystemInstruction::InitializeNonceAccount(payer.pubkey())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_nonce_rejects_other_accounts() {
+        let (client, backend, payer) = memory_client();
+        let manager = NonceManager::new(client);
+        let blockhash = Hash::new_unique();
+
+        let nonce = Pubkey::new_unique();
+        backend.set_account(nonce, nonce_account(&payer.pubkey(), &blockhash));
+        let data = manager.get_nonce(&nonce).await.unwrap();
+        assert_eq!(data.authority, payer.pubkey());
+        assert_eq!(data.blockhash(), *DurableNonce::from_blockhash(&blockhash).as_hash());
+
+        assert!(manager.get_nonce(&payer.pubkey()).await.is_err());
+        assert!(manager.get_nonce(&Pubkey::new_unique()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_durable_transaction_round_trips_unsigned() {
+        let (client, backend, payer) = memory_client();
+        let nonce = Pubkey::new_unique();
+        backend.set_account(nonce, nonce_account(&payer.pubkey(), &Hash::new_unique()));
+        let stored = fetch_nonce(backend.as_ref(), &nonce).await.unwrap();
+
+        let transfer = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+        let unsigned = client.build_durable_transaction(&[transfer], &nonce).await.unwrap();
+        assert_eq!(unsigned.message.recent_blockhash, stored.blockhash());
+        assert_eq!(unsigned.signatures, vec![Signature::default()]);
+        let advance = &unsigned.message.instructions[0];
+        assert_eq!(
+            limited_deserialize::<SystemInstruction>(&advance.data).unwrap(),
+            SystemInstruction::AdvanceNonceAccount
+        );
+
+        let encoded = serialize_transaction(&unsigned).unwrap();
+        let decoded = deserialize_transaction(&encoded).unwrap();
+        assert_eq!(decoded, unsigned);
+
+        let signed = client.sign_durable_transaction(&decoded).await.unwrap();
+        assert!(signed.verify().is_ok());
+        assert!(deserialize_transaction("not a transaction").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sign_durable_transaction_requires_other_signers() {
+        let (client, backend, payer) = memory_client();
+        let nonce = Pubkey::new_unique();
+        let authority = Keypair::new();
+        backend.set_account(nonce, nonce_account(&authority.pubkey(), &Hash::new_unique()));
+
+        let transfer = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+        let unsigned = client.build_durable_transaction(&[transfer], &nonce).await.unwrap();
+        assert_eq!(unsigned.signatures.len(), 2);
+
+        // The payer signs its part; the nonce authority still has to sign offline
+        let partly_signed = client.sign_durable_transaction(&unsigned).await.unwrap();
+        assert!(client.send_durable_transaction(&partly_signed).await.is_err());
+
+        let mut signed = partly_signed.clone();
+        signed.partial_sign(&[&authority], signed.message.recent_blockhash);
+        assert!(signed.verify().is_ok());
+        assert_eq!(signed.signatures[0], partly_signed.signatures[0]);
+    }
+}
@@ -0,0 +1,189 @@
+//! Who signs for the payer.
+//!
+//! `SolanaClient` builds every transaction message itself and only asks a
+//! `TransactionSigner` for the payer's signature, so the key can live in this
+//! process, in a separate signing daemon, or behind a policy check without
+//! the managers knowing which.
+
+use async_trait::async_trait;
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount,
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, Message, VersionedMessage},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, VersionedTransaction},
+};
+
+use crate::errors::{BlockchainError, Result};
+
+pub mod policy;
+pub mod remote;
+
+pub use policy::{PolicySigner, SigningPolicy};
+pub use remote::{RemoteSigner, SigningDaemon};
+
+#[async_trait]
+pub trait TransactionSigner: Send + Sync {
+    fn pubkey(&self) -> Pubkey;
+
+    /// Sign `message`, legacy or v0, which names this signer as its fee payer.
+    async fn sign_message(&self, message: &VersionedMessage) -> Result<Signature>;
+}
+
+/// A keypair held in this process.
+pub struct LocalSigner(Keypair);
+
+impl LocalSigner {
+    pub fn new(keypair: Keypair) -> Self {
+        Self(keypair)
+    }
+
+    pub fn keypair(&self) -> &Keypair {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for LocalSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.0.pubkey()
+    }
+
+    async fn sign_message(&self, message: &VersionedMessage) -> Result<Signature> {
+        Ok(self.0.sign_message(&message.serialize()))
+    }
+}
+
+/// Build a transaction paid for and signed only by `payer`.
+///
+/// The returned signature is checked, so a remote signer holding a different
+/// key is caught here rather than by the cluster.
+pub async fn sign_transaction(
+    payer: &dyn TransactionSigner,
+    instructions: &[Instruction],
+    blockhash: Hash,
+) -> Result<Transaction> {
+    let message = Message::new_with_blockhash(instructions, Some(&payer.pubkey()), &blockhash);
+    let signature = sign_as_payer(payer, &VersionedMessage::Legacy(message.clone())).await?;
+    Ok(Transaction {
+        signatures: vec![signature],
+        message,
+    })
+}
+
+/// Build a v0 transaction paid for and signed only by `payer`, loading
+/// accounts through `lookup_tables` where it can. Without tables the message
+/// is legacy, for clusters or callers that have none.
+pub async fn sign_versioned_transaction(
+    payer: &dyn TransactionSigner,
+    instructions: &[Instruction],
+    blockhash: Hash,
+    lookup_tables: &[AddressLookupTableAccount],
+) -> Result<VersionedTransaction> {
+    let payer_pubkey = payer.pubkey();
+    let message = if lookup_tables.is_empty() {
+        VersionedMessage::Legacy(Message::new_with_blockhash(instructions, Some(&payer_pubkey), &blockhash))
+    } else {
+        let message = v0::Message::try_compile(&payer_pubkey, instructions, lookup_tables, blockhash)
+            .map_err(|e| BlockchainError::TransactionError(format!("Cannot compile v0 message: {}", e)))?;
+        VersionedMessage::V0(message)
+    };
+    let signature = sign_as_payer(payer, &message).await?;
+    Ok(VersionedTransaction {
+        signatures: vec![signature],
+        message,
+    })
+}
+
+/// Add `payer`'s signature to a transaction it pays for, such as one built
+/// against a durable nonce, leaving any other signatures it needs in place
+/// or still to be added elsewhere.
+pub async fn sign_as_fee_payer(payer: &dyn TransactionSigner, transaction: &Transaction) -> Result<Transaction> {
+    let payer_pubkey = payer.pubkey();
+    if transaction.message.account_keys.first() != Some(&payer_pubkey) {
+        return Err(BlockchainError::TransactionError(format!(
+            "Transaction is not paid for by {}",
+            payer_pubkey
+        )));
+    }
+
+    let signature = sign_checked(payer, &VersionedMessage::Legacy(transaction.message.clone())).await?;
+    let mut signed = transaction.clone();
+    signed
+        .signatures
+        .resize(transaction.message.header.num_required_signatures as usize, Signature::default());
+    signed.signatures[0] = signature;
+    Ok(signed)
+}
+
+/// The payer's signature over `message`, checked, when the payer is its only signer.
+async fn sign_as_payer(payer: &dyn TransactionSigner, message: &VersionedMessage) -> Result<Signature> {
+    let required = message.header().num_required_signatures;
+    if required != 1 {
+        return Err(BlockchainError::TransactionError(format!(
+            "Transaction needs {} signatures but only the payer can sign",
+            required
+        )));
+    }
+    sign_checked(payer, message).await
+}
+
+async fn sign_checked(payer: &dyn TransactionSigner, message: &VersionedMessage) -> Result<Signature> {
+    let payer_pubkey = payer.pubkey();
+    let signature = payer.sign_message(message).await?;
+    if !signature.verify(payer_pubkey.as_ref(), &message.serialize()) {
+        return Err(BlockchainError::InvalidKeypair(format!(
+            "Signer returned a signature that does not verify for {}",
+            payer_pubkey
+        )));
+    }
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::system_instruction;
+
+    /// Claims one key but signs with another
+    struct Impostor(Pubkey, LocalSigner);
+
+    #[async_trait]
+    impl TransactionSigner for Impostor {
+        fn pubkey(&self) -> Pubkey {
+            self.0
+        }
+
+        async fn sign_message(&self, message: &VersionedMessage) -> Result<Signature> {
+            self.1.sign_message(message).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_keypair_matches_sdk_signing() {
+        let payer = Keypair::new();
+        let instructions = [system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1)];
+        let blockhash = Hash::new_unique();
+
+        let signer = LocalSigner::new(payer.insecure_clone());
+        let signed = sign_transaction(&signer, &instructions, blockhash).await.unwrap();
+        let expected = Transaction::new_signed_with_payer(&instructions, Some(&payer.pubkey()), &[&payer], blockhash);
+        assert_eq!(signed, expected);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_signatures_and_extra_signers() {
+        let claimed = LocalSigner::new(Keypair::new());
+        let impostor = Impostor(claimed.pubkey(), LocalSigner::new(Keypair::new()));
+        let instructions = [system_instruction::transfer(&claimed.pubkey(), &Pubkey::new_unique(), 1)];
+        let result = sign_transaction(&impostor, &instructions, Hash::new_unique()).await;
+        assert!(matches!(result, Err(BlockchainError::InvalidKeypair(_))));
+
+        let other = Pubkey::new_unique();
+        let instructions = [system_instruction::transfer(&other, &Pubkey::new_unique(), 1)];
+        let result = sign_transaction(&claimed, &instructions, Hash::new_unique()).await;
+        assert!(matches!(result, Err(BlockchainError::TransactionError(_))));
+    }
+}
@@ -0,0 +1,189 @@
+//! A signer that refuses messages outside a configured policy.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    compute_budget, message::VersionedMessage, program_utils::limited_deserialize,
+    pubkey::Pubkey, signature::Signature, system_instruction::SystemInstruction, system_program,
+};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::TransactionSigner;
+use crate::errors::{BlockchainError, Result};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SigningPolicy {
+    /// Programs the payer may sign for; empty allows any. The compute budget
+    /// program is always allowed.
+    #[serde(default)]
+    pub allowed_programs: Vec<String>,
+    /// Most lamports one transaction may move out of the payer through the
+    /// System Program.
+    pub max_lamports: Option<u64>,
+    /// Highest priority fee the payer may bid, in micro-lamports per compute unit.
+    pub max_compute_unit_price: Option<u64>,
+}
+
+/// Wraps another signer and only forwards messages `policy` allows.
+pub struct PolicySigner {
+    inner: Arc<dyn TransactionSigner>,
+    policy: SigningPolicy,
+    allowed_programs: Vec<Pubkey>,
+}
+
+impl PolicySigner {
+    pub fn new(inner: Arc<dyn TransactionSigner>, policy: SigningPolicy) -> Result<Self> {
+        let allowed_programs = policy
+            .allowed_programs
+            .iter()
+            .map(|program| {
+                Pubkey::from_str(program)
+                    .map_err(|e| BlockchainError::InvalidPubkey(format!("allowed program {}: {}", program, e)))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            inner,
+            policy,
+            allowed_programs,
+        })
+    }
+
+    /// Why `message` breaks the policy, if it does.
+    pub fn check(&self, message: &VersionedMessage) -> Result<()> {
+        let payer = self.inner.pubkey();
+        let mut lamports_out: u64 = 0;
+
+        // Programs and signers are never loaded from lookup tables, so the
+        // static keys are enough to tell what the payer is agreeing to
+        let keys = message.static_account_keys();
+        for instruction in message.instructions() {
+            let program_id = keys[instruction.program_id_index as usize];
+            if program_id == compute_budget::id() {
+                if let (Some(max), Some(price)) =
+                    (self.policy.max_compute_unit_price, compute_unit_price(&instruction.data))
+                {
+                    if price > max {
+                        return Err(violation(format!(
+                            "compute unit price {} exceeds the limit of {}",
+                            price, max
+                        )));
+                    }
+                }
+                continue;
+            }
+            if !self.allowed_programs.is_empty() && !self.allowed_programs.contains(&program_id) {
+                return Err(violation(format!("program {} is not allowed", program_id)));
+            }
+            if program_id == system_program::id() {
+                let from = instruction.accounts.first().and_then(|&i| keys.get(i as usize)).copied();
+                if from == Some(payer) {
+                    lamports_out = lamports_out.saturating_add(system_lamports(&instruction.data));
+                }
+            }
+        }
+
+        match self.policy.max_lamports {
+            Some(max) if lamports_out > max => Err(violation(format!(
+                "transaction moves {} lamports from the payer, more than the limit of {}",
+                lamports_out, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionSigner for PolicySigner {
+    fn pubkey(&self) -> Pubkey {
+        self.inner.pubkey()
+    }
+
+    async fn sign_message(&self, message: &VersionedMessage) -> Result<Signature> {
+        self.check(message)?;
+        self.inner.sign_message(message).await
+    }
+}
+
+fn violation(reason: String) -> BlockchainError {
+    BlockchainError::PolicyViolation(reason)
+}
+
+/// The price set by a `SetComputeUnitPrice` instruction: tag 3, then a little-endian u64.
+fn compute_unit_price(data: &[u8]) -> Option<u64> {
+    match data {
+        [3, price @ ..] => Some(u64::from_le_bytes(price.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+/// Lamports a System Program instruction moves out of its first account.
+fn system_lamports(data: &[u8]) -> u64 {
+    match limited_deserialize(data) {
+        Ok(SystemInstruction::Transfer { lamports })
+        | Ok(SystemInstruction::TransferWithSeed { lamports, .. })
+        | Ok(SystemInstruction::CreateAccount { lamports, .. })
+        | Ok(SystemInstruction::CreateAccountWithSeed { lamports, .. }) => lamports,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::LocalSigner;
+    use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction, hash::Hash, instruction::Instruction,
+        message::Message, signature::Keypair, system_instruction,
+    };
+
+    fn message(payer: &Pubkey, instructions: &[Instruction]) -> VersionedMessage {
+        VersionedMessage::Legacy(Message::new_with_blockhash(instructions, Some(payer), &Hash::new_unique()))
+    }
+
+    #[tokio::test]
+    async fn test_enforces_limits() {
+        let program = Pubkey::new_unique();
+        let signer = PolicySigner::new(
+            Arc::new(LocalSigner::new(Keypair::new())),
+            SigningPolicy {
+                allowed_programs: vec![program.to_string(), system_program::id().to_string()],
+                max_lamports: Some(1_000),
+                max_compute_unit_price: Some(50),
+            },
+        )
+        .unwrap();
+        let payer = signer.pubkey();
+        let allowed = Instruction::new_with_bytes(program, &[], vec![]);
+
+        let ok = message(&payer, &[ComputeBudgetInstruction::set_compute_unit_price(50), allowed.clone()]);
+        assert!(signer.sign_message(&ok).await.is_ok());
+
+        let refused = [
+            vec![Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![])],
+            vec![ComputeBudgetInstruction::set_compute_unit_price(51), allowed],
+            vec![
+                system_instruction::transfer(&payer, &Pubkey::new_unique(), 600),
+                system_instruction::transfer(&payer, &Pubkey::new_unique(), 600),
+            ],
+        ];
+        for instructions in refused {
+            let result = signer.sign_message(&message(&payer, &instructions)).await;
+            assert!(matches!(result, Err(BlockchainError::PolicyViolation(_))), "{:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_default_policy_allows_anything() {
+        let signer = PolicySigner::new(Arc::new(LocalSigner::new(Keypair::new())), SigningPolicy::default()).unwrap();
+        let payer = signer.pubkey();
+        let instructions = [
+            ComputeBudgetInstruction::set_compute_unit_price(u64::MAX),
+            system_instruction::transfer(&payer, &Pubkey::new_unique(), u64::MAX),
+        ];
+        assert!(signer.check(&message(&payer, &instructions)).is_ok());
+
+        let policy = SigningPolicy { allowed_programs: vec!["not a pubkey".to_string()], ..SigningPolicy::default() };
+        assert!(PolicySigner::new(Arc::new(LocalSigner::new(Keypair::new())), policy).is_err());
+    }
+}
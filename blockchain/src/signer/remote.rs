@@ -0,0 +1,356 @@
+//! Signing through a separate daemon that holds the key.
+//!
+//! The protocol is one JSON request and one JSON response, either POSTed to
+//! an HTTP endpoint or written as a single line over a Unix socket:
+//!
+//! ```text
+//! {"method":"get_pubkey"}                      -> {"pubkey":"<base58>"}
+//! {"method":"sign_message","message":"<b64>"}  -> {"signature":"<base58>"}
+//!                                              or {"error":"<reason>"}
+//! ```
+//!
+//! `message` is the bincode-serialized `VersionedMessage`, legacy or v0, so
+//! the daemon can inspect what it signs. `SigningDaemon` serves the protocol
+//! from any `TransactionSigner`, for tests and local development.
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    message::VersionedMessage, packet::PACKET_DATA_SIZE, program_utils::limited_deserialize,
+    pubkey::Pubkey, signature::Signature,
+};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+use super::TransactionSigner;
+use crate::errors::{BlockchainError, Result};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SignRequest {
+    GetPubkey,
+    SignMessage { message: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignResponse {
+    Pubkey(String),
+    Signature(String),
+    Error(String),
+}
+
+enum Transport {
+    #[cfg(unix)]
+    Unix(PathBuf),
+    Http {
+        client: reqwest::Client,
+        url: String,
+    },
+}
+
+pub struct RemoteSigner {
+    transport: Transport,
+    pubkey: Pubkey,
+}
+
+impl RemoteSigner {
+    /// A signer for `pubkey` behind `url`, either `unix:<socket path>` or an
+    /// `http(s)://` endpoint. Nothing is contacted until the first signature.
+    pub fn new(url: &str, pubkey: Pubkey) -> Result<Self> {
+        let transport = if let Some(path) = url.strip_prefix("unix:") {
+            #[cfg(unix)]
+            {
+                Transport::Unix(PathBuf::from(path.trim_start_matches("//")))
+            }
+            #[cfg(not(unix))]
+            {
+                let _ = path;
+                return Err(BlockchainError::ValidationError(
+                    "Unix socket signers are only supported on Unix".to_string(),
+                ));
+            }
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            let client = reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .map_err(|e| BlockchainError::NetworkError(e.to_string()))?;
+            Transport::Http {
+                client,
+                url: url.to_string(),
+            }
+        } else {
+            return Err(BlockchainError::ValidationError(format!(
+                "Remote signer URL must start with unix:, http:// or https://, got {}",
+                url
+            )));
+        };
+        Ok(Self { transport, pubkey })
+    }
+
+    /// Connect to `url` and ask the daemon which key it holds.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let mut signer = Self::new(url, Pubkey::default())?;
+        signer.pubkey = match signer.call(&SignRequest::GetPubkey).await? {
+            SignResponse::Pubkey(pubkey) => Pubkey::from_str(&pubkey)
+                .map_err(|e| BlockchainError::InvalidPubkey(format!("remote signer: {}", e)))?,
+            other => return Err(unexpected(other)),
+        };
+        Ok(signer)
+    }
+
+    async fn call(&self, request: &SignRequest) -> Result<SignResponse> {
+        let response = match &self.transport {
+            #[cfg(unix)]
+            Transport::Unix(path) => tokio::time::timeout(REQUEST_TIMEOUT, call_unix(path, request))
+                .await
+                .map_err(|_| BlockchainError::NetworkError("Remote signer timed out".to_string()))??,
+            Transport::Http { client, url } => client
+                .post(url)
+                .json(request)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| BlockchainError::NetworkError(format!("Remote signer: {}", e)))?
+                .json()
+                .await
+                .map_err(|e| BlockchainError::SerializationError(format!("Remote signer: {}", e)))?,
+        };
+        match response {
+            SignResponse::Error(reason) => Err(BlockchainError::TransactionError(format!(
+                "Remote signer refused: {}",
+                reason
+            ))),
+            response => Ok(response),
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn call_unix(path: &std::path::Path, request: &SignRequest) -> Result<SignResponse> {
+    let io_error = |e: std::io::Error| BlockchainError::NetworkError(format!("Remote signer {}: {}", path.display(), e));
+    let mut stream = tokio::net::UnixStream::connect(path).await.map_err(io_error)?;
+
+    let mut line = serde_json::to_vec(request).map_err(|e| BlockchainError::SerializationError(e.to_string()))?;
+    line.push(b'\n');
+    stream.write_all(&line).await.map_err(io_error)?;
+
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).await.map_err(io_error)?;
+    serde_json::from_str(&response)
+        .map_err(|e| BlockchainError::SerializationError(format!("Remote signer: {}", e)))
+}
+
+#[async_trait]
+impl TransactionSigner for RemoteSigner {
+    fn pubkey(&self) -> Pubkey {
+        self.pubkey
+    }
+
+    async fn sign_message(&self, message: &VersionedMessage) -> Result<Signature> {
+        let request = SignRequest::SignMessage {
+            message: BASE64.encode(message.serialize()),
+        };
+        match self.call(&request).await? {
+            SignResponse::Signature(signature) => Signature::from_str(&signature)
+                .map_err(|e| BlockchainError::SerializationError(format!("Remote signer signature: {}", e))),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+fn unexpected(response: SignResponse) -> BlockchainError {
+    BlockchainError::SerializationError(format!("Unexpected remote signer response: {:?}", response))
+}
+
+/// Serves the remote signing protocol on behalf of `signer`.
+pub struct SigningDaemon {
+    signer: Arc<dyn TransactionSigner>,
+}
+
+impl SigningDaemon {
+    pub fn new(signer: Arc<dyn TransactionSigner>) -> Arc<Self> {
+        Arc::new(Self { signer })
+    }
+
+    pub async fn handle(&self, request: SignRequest) -> SignResponse {
+        match request {
+            SignRequest::GetPubkey => SignResponse::Pubkey(self.signer.pubkey().to_string()),
+            SignRequest::SignMessage { message } => {
+                let message = BASE64
+                    .decode(message)
+                    .map_err(|e| e.to_string())
+                    .and_then(|bytes| {
+                        limited_deserialize::<VersionedMessage>(&bytes).map_err(|e| e.to_string())
+                    });
+                let signature = match message {
+                    Ok(message) => self.signer.sign_message(&message).await.map_err(|e| e.to_string()),
+                    Err(e) => Err(format!("Malformed message: {}", e)),
+                };
+                match signature {
+                    Ok(signature) => SignResponse::Signature(signature.to_string()),
+                    Err(e) => SignResponse::Error(e),
+                }
+            }
+        }
+    }
+
+    /// Answer one line-delimited request per connection until the listener fails.
+    #[cfg(unix)]
+    pub async fn serve_unix(self: Arc<Self>, listener: tokio::net::UnixListener) -> std::io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let daemon = self.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut line = String::new();
+                if BufReader::new(read).read_line(&mut line).await.is_err() {
+                    return;
+                }
+                let mut response = serde_json::to_vec(&daemon.respond(line.as_bytes()).await).unwrap_or_default();
+                response.push(b'\n');
+                let _ = write.write_all(&response).await;
+            });
+        }
+    }
+
+    /// Answer one POST per connection until the listener fails. Just enough
+    /// HTTP/1.1 for `RemoteSigner`; put a real server in front in production.
+    pub async fn serve_http(self: Arc<Self>, listener: TcpListener) -> std::io::Result<()> {
+        loop {
+            let (mut stream, _) = listener.accept().await?;
+            let daemon = self.clone();
+            tokio::spawn(async move {
+                let Ok(body) = read_http_body(&mut stream).await else {
+                    return;
+                };
+                let json = serde_json::to_string(&daemon.respond(&body).await).unwrap_or_default();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    json.len(),
+                    json
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    }
+
+    async fn respond(&self, request: &[u8]) -> SignResponse {
+        match serde_json::from_slice(request) {
+            Ok(request) => self.handle(request).await,
+            Err(e) => SignResponse::Error(format!("Malformed request: {}", e)),
+        }
+    }
+}
+
+async fn read_http_body(stream: &mut tokio::net::TcpStream) -> std::io::Result<Vec<u8>> {
+    let mut reader = BufReader::new(stream);
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 {
+            break;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; content_length.min(PACKET_DATA_SIZE * 2)];
+    reader.read_exact(&mut body).await?;
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::{sign_transaction, sign_versioned_transaction, LocalSigner, PolicySigner, SigningPolicy};
+    use solana_sdk::{
+        address_lookup_table::AddressLookupTableAccount,
+        hash::Hash,
+        message::Message,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    };
+
+    fn daemon(keypair: &Keypair) -> Arc<SigningDaemon> {
+        SigningDaemon::new(Arc::new(LocalSigner::new(keypair.insecure_clone())))
+    }
+
+    async fn assert_signs_like_local(remote: &RemoteSigner, keypair: &Keypair) {
+        let instructions = [system_instruction::transfer(&keypair.pubkey(), &Pubkey::new_unique(), 1)];
+        let blockhash = Hash::new_unique();
+        let signed = sign_transaction(remote, &instructions, blockhash).await.unwrap();
+        let expected = Transaction::new_signed_with_payer(&instructions, Some(&keypair.pubkey()), &[keypair], blockhash);
+        assert_eq!(signed, expected);
+
+        // v0 messages travel the same way
+        let recipient = instructions[0].accounts[1].pubkey;
+        let table = AddressLookupTableAccount { key: Pubkey::new_unique(), addresses: vec![recipient] };
+        let signed = sign_versioned_transaction(remote, &instructions, blockhash, &[table]).await.unwrap();
+        assert_eq!(signed.message.address_table_lookups().map(<[_]>::len), Some(1));
+        assert!(signed.verify_with_results().into_iter().all(|ok| ok));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_signer() {
+        let keypair = Keypair::new();
+        let path = std::env::temp_dir().join(format!("mkt4u-signer-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let server = tokio::spawn(daemon(&keypair).serve_unix(listener));
+
+        let remote = RemoteSigner::connect(&format!("unix:{}", path.display())).await.unwrap();
+        assert_eq!(remote.pubkey(), keypair.pubkey());
+        assert_signs_like_local(&remote, &keypair).await;
+
+        server.abort();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_http_signer() {
+        let keypair = Keypair::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = tokio::spawn(daemon(&keypair).serve_http(listener));
+
+        let remote = RemoteSigner::new(&url, keypair.pubkey()).unwrap();
+        assert_signs_like_local(&remote, &keypair).await;
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_daemon_reports_refusals() {
+        let keypair = Keypair::new();
+        let policy = SigningPolicy { max_lamports: Some(0), ..SigningPolicy::default() };
+        let guarded = PolicySigner::new(Arc::new(LocalSigner::new(keypair.insecure_clone())), policy).unwrap();
+        let daemon = SigningDaemon::new(Arc::new(guarded));
+
+        let message = Message::new(
+            &[system_instruction::transfer(&keypair.pubkey(), &Pubkey::new_unique(), 1)],
+            Some(&keypair.pubkey()),
+        );
+        let request = SignRequest::SignMessage { message: BASE64.encode(message.serialize()) };
+        assert!(matches!(daemon.handle(request).await, SignResponse::Error(_)));
+
+        let garbage = SignRequest::SignMessage { message: BASE64.encode([1, 2, 3]) };
+        assert!(matches!(daemon.handle(garbage).await, SignResponse::Error(_)));
+
+        assert!(RemoteSigner::new("ftp://signer", keypair.pubkey()).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! Transaction simulation, used for previews and for dry-run mode.
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    account::Account,
+    address_lookup_table::AddressLookupTableAccount,
+    message::{
+        v0::{LoadedAddresses, LoadedMessage},
+        VersionedMessage,
+    },
+    pubkey::Pubkey,
+    transaction::VersionedTransaction,
+};
+
+use crate::backend::ChainBackend;
+use crate::errors::Result;
+use crate::fees::ResolvedBudget;
+
+/// What a transaction would do if it were sent now.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationReport {
+    /// Why the transaction would fail, or `None` if it would succeed.
+    pub err: Option<String>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// Writable accounts whose state would change. Empty when the backend
+    /// cannot report post-execution state.
+    pub account_changes: Vec<AccountChange>,
+    /// Lamports charged for signatures and priority.
+    pub fee: u64,
+    pub compute_unit_limit: Option<u32>,
+    pub compute_unit_price: u64,
+}
+
+impl SimulationReport {
+    pub fn succeeded(&self) -> bool {
+        self.err.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountChange {
+    pub pubkey: String,
+    pub lamports_before: u64,
+    pub lamports_after: u64,
+    pub data_len_before: usize,
+    pub data_len_after: usize,
+    pub data_changed: bool,
+    /// Owner after the transaction, if the account would exist.
+    pub owner_after: Option<String>,
+}
+
+impl AccountChange {
+    fn between(pubkey: &Pubkey, before: Option<&Account>, after: Option<&Account>) -> Option<Self> {
+        if before == after {
+            return None;
+        }
+        let empty = Vec::new();
+        let data_before = before.map_or(&empty, |a| &a.data);
+        let data_after = after.map_or(&empty, |a| &a.data);
+        Some(Self {
+            pubkey: pubkey.to_string(),
+            lamports_before: before.map_or(0, |a| a.lamports),
+            lamports_after: after.map_or(0, |a| a.lamports),
+            data_len_before: data_before.len(),
+            data_len_after: data_after.len(),
+            data_changed: data_before != data_after,
+            owner_after: after.map(|a| a.owner.to_string()),
+        })
+    }
+}
+
+/// Simulate a signed transaction and diff the writable accounts it touches,
+/// including those a v0 message loads from `lookup_tables`.
+pub(crate) async fn simulate(
+    backend: &dyn ChainBackend,
+    transaction: &VersionedTransaction,
+    lookup_tables: &[AddressLookupTableAccount],
+    budget: &ResolvedBudget,
+) -> Result<SimulationReport> {
+    let message = &transaction.message;
+    let writable = writable_accounts(message, lookup_tables);
+
+    let mut before = Vec::with_capacity(writable.len());
+    for pubkey in &writable {
+        before.push(backend.get_account(pubkey).await?);
+    }
+    let simulated = backend.simulate_transaction(transaction, &writable).await?;
+    let fee = backend.get_fee_for_message(message).await?;
+
+    let account_changes = simulated
+        .accounts
+        .map(|after| {
+            writable
+                .iter()
+                .zip(before.iter().zip(after.iter()))
+                .filter_map(|(pubkey, (before, after))| {
+                    AccountChange::between(pubkey, before.as_ref(), after.as_ref())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(SimulationReport {
+        err: simulated.err.map(|e| e.to_string()),
+        logs: simulated.logs,
+        units_consumed: simulated.units_consumed,
+        account_changes,
+        fee,
+        compute_unit_limit: budget.unit_limit,
+        compute_unit_price: budget.unit_price,
+    })
+}
+
+/// Every account `message` can write, with lookup table addresses resolved
+fn writable_accounts(message: &VersionedMessage, lookup_tables: &[AddressLookupTableAccount]) -> Vec<Pubkey> {
+    match message {
+        VersionedMessage::Legacy(message) => message
+            .account_keys
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| message.is_writable(*i))
+            .map(|(_, key)| *key)
+            .collect(),
+        VersionedMessage::V0(message) => {
+            let mut loaded_addresses = LoadedAddresses::default();
+            for lookup in &message.address_table_lookups {
+                let Some(table) = lookup_tables.iter().find(|table| table.key == lookup.account_key) else {
+                    continue;
+                };
+                let resolve = |indexes: &[u8]| {
+                    indexes
+                        .iter()
+                        .filter_map(|&index| table.addresses.get(index as usize).copied())
+                        .collect::<Vec<_>>()
+                };
+                loaded_addresses.writable.extend(resolve(&lookup.writable_indexes));
+                loaded_addresses.readonly.extend(resolve(&lookup.readonly_indexes));
+            }
+            let loaded = LoadedMessage::new(message.clone(), loaded_addresses);
+            loaded
+                .account_keys()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| loaded.is_writable(*i))
+                .map(|(_, key)| *key)
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryBackend, SimulatedTransaction};
+    use solana_sdk::{
+        message::v0,
+        signature::{Keypair, Signer},
+        system_instruction, system_program,
+        transaction::{Transaction, TransactionError},
+    };
+
+    const NO_BUDGET: ResolvedBudget = ResolvedBudget {
+        unit_limit: None,
+        unit_price: 0,
+    };
+
+    #[tokio::test]
+    async fn test_reports_account_changes() {
+        let backend = MemoryBackend::new();
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        backend.set_balance(payer.pubkey(), 1_000_000);
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &recipient, 400_000)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            backend.get_latest_blockhash().await.unwrap(),
+        );
+        let fee = MemoryBackend::FEE_PER_SIGNATURE;
+        backend.queue_simulation(SimulatedTransaction {
+            err: None,
+            logs: vec!["Program 11111111111111111111111111111111 success".to_string()],
+            units_consumed: Some(150),
+            accounts: Some(vec![
+                Some(Account::new(1_000_000 - 400_000 - fee, 0, &system_program::id())),
+                Some(Account::new(400_000, 0, &system_program::id())),
+            ]),
+        });
+
+        let report = simulate(&backend, &transaction.into(), &[], &NO_BUDGET).await.unwrap();
+        assert!(report.succeeded());
+        assert_eq!(report.units_consumed, Some(150));
+        assert_eq!(report.fee, fee);
+        assert_eq!(report.account_changes.len(), 2);
+        assert_eq!(report.account_changes[0].lamports_after, 1_000_000 - 400_000 - fee);
+        let created = &report.account_changes[1];
+        assert_eq!(created.pubkey, recipient.to_string());
+        assert_eq!((created.lamports_before, created.lamports_after), (0, 400_000));
+        assert_eq!(created.owner_after, Some(system_program::id().to_string()));
+
+        // Nothing was sent
+        assert!(backend.sent_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reports_accounts_loaded_from_lookup_table() {
+        let backend = MemoryBackend::new();
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        backend.set_balance(payer.pubkey(), 1_000_000);
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![recipient],
+        };
+
+        let instruction = system_instruction::transfer(&payer.pubkey(), &recipient, 400_000);
+        let blockhash = backend.get_latest_blockhash().await.unwrap();
+        let message = v0::Message::try_compile(&payer.pubkey(), &[instruction], std::slice::from_ref(&table), blockhash).unwrap();
+        assert!(!message.account_keys.contains(&recipient));
+        let transaction = VersionedTransaction::try_new(VersionedMessage::V0(message), &[&payer]).unwrap();
+        backend.queue_simulation(SimulatedTransaction {
+            err: None,
+            logs: Vec::new(),
+            units_consumed: Some(150),
+            accounts: Some(vec![
+                Some(Account::new(1_000_000 - 400_000, 0, &system_program::id())),
+                Some(Account::new(400_000, 0, &system_program::id())),
+            ]),
+        });
+
+        let report = simulate(&backend, &transaction, &[table], &NO_BUDGET).await.unwrap();
+        assert_eq!(report.account_changes.len(), 2);
+        assert_eq!(report.account_changes[1].pubkey, recipient.to_string());
+        assert_eq!(report.fee, MemoryBackend::FEE_PER_SIGNATURE);
+    }
+
+    #[tokio::test]
+    async fn test_reports_failure() {
+        let backend = MemoryBackend::new();
+        let payer = Keypair::new();
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            backend.get_latest_blockhash().await.unwrap(),
+        );
+
+        let report = simulate(&backend, &transaction.into(), &[], &NO_BUDGET).await.unwrap();
+        assert_eq!(
+            report.err,
+            Some(TransactionError::InsufficientFundsForFee.to_string())
+        );
+        assert!(report.account_changes.is_empty());
+    }
+}
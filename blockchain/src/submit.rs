@@ -0,0 +1,393 @@
+//! Transaction submission with retries.
+//!
+//! A failed send is ambiguous: the transaction may have landed even though
+//! the caller saw an error. Before every retry the pipeline asks the cluster
+//! for the signature's status, and it only re-signs with a fresh blockhash
+//! once the old blockhash has expired, so at most one signature can land.
+
+use async_trait::async_trait;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    hash::Hash,
+    signature::Signature,
+    transaction::{Transaction, VersionedTransaction},
+};
+use std::future::Future;
+use std::time::Duration;
+
+use crate::backend::ChainBackend;
+use crate::errors::{BlockchainError, Result};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// Total submissions, including the first.
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 8_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff before retry number `attempt`, with the upper half jittered
+    /// so clients that failed together do not retry together.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .initial_backoff_ms
+            .saturating_mul(1u64 << attempt.saturating_sub(1).min(32))
+            .min(self.max_backoff_ms);
+        let floor = ceiling / 2;
+        Duration::from_millis(rand::thread_rng().gen_range(floor..=ceiling))
+    }
+}
+
+/// Why a submission failed, as far as retrying is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    BlockhashExpired,
+    NodeBehind,
+    RateLimited,
+    Network,
+    /// The program rejected the transaction; sending it again cannot help.
+    Program,
+    Fatal,
+}
+
+impl FailureKind {
+    pub fn classify(error: &BlockchainError) -> Self {
+        match error {
+            BlockchainError::BlockhashExpired => Self::BlockhashExpired,
+            BlockchainError::NodeBehind(_) => Self::NodeBehind,
+            BlockchainError::RateLimited(_) => Self::RateLimited,
+            BlockchainError::NetworkError(_) => Self::Network,
+            BlockchainError::ProgramError(_) => Self::Program,
+            _ => Self::Fatal,
+        }
+    }
+
+    pub fn is_retryable(self) -> bool {
+        !matches!(self, Self::Program | Self::Fatal)
+    }
+}
+
+/// A signed transaction the pipeline can send, legacy or v0.
+#[async_trait]
+pub(crate) trait Submittable: Send + Sync {
+    fn signature(&self) -> Signature;
+
+    fn blockhash(&self) -> Hash;
+
+    async fn send(&self, backend: &dyn ChainBackend, confirm: bool) -> Result<Signature>;
+}
+
+#[async_trait]
+impl Submittable for Transaction {
+    fn signature(&self) -> Signature {
+        self.signatures[0]
+    }
+
+    fn blockhash(&self) -> Hash {
+        self.message.recent_blockhash
+    }
+
+    async fn send(&self, backend: &dyn ChainBackend, confirm: bool) -> Result<Signature> {
+        if confirm {
+            backend.send_and_confirm_transaction(self).await
+        } else {
+            backend.send_transaction(self).await
+        }
+    }
+}
+
+#[async_trait]
+impl Submittable for VersionedTransaction {
+    fn signature(&self) -> Signature {
+        self.signatures[0]
+    }
+
+    fn blockhash(&self) -> Hash {
+        *self.message.recent_blockhash()
+    }
+
+    async fn send(&self, backend: &dyn ChainBackend, confirm: bool) -> Result<Signature> {
+        if confirm {
+            backend.send_and_confirm_versioned_transaction(self).await
+        } else {
+            backend.send_versioned_transaction(self).await
+        }
+    }
+}
+
+/// Send `transaction` and wait for it to be confirmed, retrying transient
+/// failures under `policy`.
+///
+/// `resign` builds a replacement on a fresh blockhash; it returns `None` for
+/// pre-signed transactions the client cannot re-sign.
+pub(crate) async fn submit<T, F, Fut>(
+    backend: &dyn ChainBackend,
+    policy: &RetryPolicy,
+    transaction: T,
+    resign: F,
+) -> Result<Signature>
+where
+    T: Submittable,
+    F: Fn(Hash) -> Fut,
+    Fut: Future<Output = Result<Option<T>>>,
+{
+    let sent = send(backend, policy, transaction, resign, true).await?;
+    Ok(sent.signature())
+}
+
+/// Like `submit`, but return once the cluster has accepted the transaction,
+/// with the transaction as finally sent so its blockhash can be tracked.
+pub(crate) async fn submit_unconfirmed<T, F, Fut>(
+    backend: &dyn ChainBackend,
+    policy: &RetryPolicy,
+    transaction: T,
+    resign: F,
+) -> Result<T>
+where
+    T: Submittable,
+    F: Fn(Hash) -> Fut,
+    Fut: Future<Output = Result<Option<T>>>,
+{
+    send(backend, policy, transaction, resign, false).await
+}
+
+async fn send<T, F, Fut>(
+    backend: &dyn ChainBackend,
+    policy: &RetryPolicy,
+    mut transaction: T,
+    resign: F,
+    confirm: bool,
+) -> Result<T>
+where
+    T: Submittable,
+    F: Fn(Hash) -> Fut,
+    Fut: Future<Output = Result<Option<T>>>,
+{
+    let mut attempt = 1;
+    loop {
+        let signature = transaction.signature();
+        let error = match transaction.send(backend, confirm).await {
+            Ok(_) => return Ok(transaction),
+            Err(error) => error,
+        };
+
+        let kind = FailureKind::classify(&error);
+        if !kind.is_retryable() {
+            return Err(error);
+        }
+
+        // The blockhash is checked before the status: a signature unseen after
+        // its blockhash had already expired cannot land later
+        let blockhash = transaction.blockhash();
+        let expired = matches!(backend.is_blockhash_valid(&blockhash).await, Ok(false));
+        let landed = backend.get_signature_status(&signature).await;
+        match landed {
+            Ok(Some(Ok(()))) => return Ok(transaction),
+            Ok(Some(Err(e))) => return Err(BlockchainError::ProgramError(e.to_string())),
+            _ => {}
+        }
+        if attempt >= policy.max_attempts {
+            return Err(error);
+        }
+
+        // Re-signing is only safe once the old signature can never land
+        if expired && matches!(landed, Ok(None)) {
+            let latest = backend.get_latest_blockhash().await?;
+            transaction = resign(latest).await?.ok_or(BlockchainError::BlockhashExpired)?;
+            log::warn!("Blockhash expired for {}, re-signed as {}", signature, transaction.signature());
+        }
+
+        let delay = policy.backoff(attempt);
+        log::warn!(
+            "Submission attempt {} of {} failed ({:?}: {}), retrying in {:?}",
+            attempt,
+            policy.max_attempts,
+            kind,
+            error,
+            delay
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_instruction,
+    };
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 2,
+        }
+    }
+
+    fn funded() -> (MemoryBackend, Keypair) {
+        let backend = MemoryBackend::new();
+        let payer = Keypair::new();
+        backend.set_balance(payer.pubkey(), 1_000_000);
+        (backend, payer)
+    }
+
+    async fn not_resignable(_: Hash) -> Result<Option<Transaction>> {
+        Ok(None)
+    }
+
+    fn transfer(payer: &Keypair, blockhash: Hash) -> Transaction {
+        Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1)],
+            Some(&payer.pubkey()),
+            &[payer],
+            blockhash,
+        )
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+        };
+        for _ in 0..20 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let third = policy.backoff(3);
+            assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+            assert!(policy.backoff(40) <= Duration::from_millis(1_000));
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            FailureKind::classify(&BlockchainError::RateLimited("429".to_string())),
+            FailureKind::RateLimited
+        );
+        assert!(FailureKind::classify(&BlockchainError::NodeBehind("slot".to_string())).is_retryable());
+        assert!(!FailureKind::classify(&BlockchainError::ProgramError("0x3".to_string())).is_retryable());
+        assert!(!FailureKind::classify(&BlockchainError::InsufficientFunds).is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failure_with_same_signature() {
+        let (backend, payer) = funded();
+        backend.fail_next_send(BlockchainError::RateLimited("429".to_string()));
+        backend.fail_next_send(BlockchainError::NodeBehind("behind by 40 slots".to_string()));
+
+        let transaction = transfer(&payer, backend.get_latest_blockhash().await.unwrap());
+        let expected = transaction.signatures[0];
+        let signature = submit(&backend, &fast_policy(), transaction, not_resignable).await.unwrap();
+
+        assert_eq!(signature, expected);
+        assert_eq!(backend.sent_transactions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_resubmit_landed_transaction() {
+        let (backend, payer) = funded();
+        backend.drop_next_response(BlockchainError::NetworkError("connection reset".to_string()));
+
+        let transaction = transfer(&payer, backend.get_latest_blockhash().await.unwrap());
+        let signature = submit(&backend, &fast_policy(), transaction, |blockhash| {
+            let resigned = transfer(&payer, blockhash);
+            async move { Ok(Some(resigned)) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(backend.sent_transactions().len(), 1);
+        assert_eq!(backend.sent_transactions()[0].signatures[0], signature);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_resubmit_transaction_landing_as_blockhash_expires() {
+        let (backend, payer) = funded();
+        backend.land_next_send_late(BlockchainError::NetworkError("timeout".to_string()));
+
+        let transaction = transfer(&payer, backend.get_latest_blockhash().await.unwrap());
+        let expected = transaction.signatures[0];
+        let signature = submit(&backend, &fast_policy(), transaction, |blockhash| {
+            let resigned = transfer(&payer, blockhash);
+            async move { Ok(Some(resigned)) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(signature, expected);
+        assert_eq!(backend.sent_transactions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_resigns_after_blockhash_expiry() {
+        let (backend, payer) = funded();
+        let stale = transfer(&payer, backend.get_latest_blockhash().await.unwrap());
+        let latest = backend.advance_blockhash();
+
+        let signature = submit(&backend, &fast_policy(), stale.clone(), |blockhash| {
+            let resigned = transfer(&payer, blockhash);
+            async move { Ok(Some(resigned)) }
+        })
+        .await
+        .unwrap();
+
+        assert_ne!(signature, stale.signatures[0]);
+        let sent = backend.sent_transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].message.recent_blockhash, latest);
+    }
+
+    #[tokio::test]
+    async fn test_presigned_transaction_is_not_resigned() {
+        let (backend, payer) = funded();
+        let stale = transfer(&payer, backend.get_latest_blockhash().await.unwrap());
+        backend.advance_blockhash();
+
+        let result = submit(&backend, &fast_policy(), stale, not_resignable).await;
+        assert!(matches!(result, Err(BlockchainError::BlockhashExpired)));
+        assert!(backend.sent_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_program_errors_are_not_retried() {
+        let (backend, payer) = funded();
+        backend.fail_next_send(BlockchainError::ProgramError("custom program error: 0x4".to_string()));
+
+        let transaction = transfer(&payer, backend.get_latest_blockhash().await.unwrap());
+        let result = submit(&backend, &fast_policy(), transaction, not_resignable).await;
+        assert!(matches!(result, Err(BlockchainError::ProgramError(_))));
+        assert!(backend.sent_transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let (backend, payer) = funded();
+        for _ in 0..3 {
+            backend.fail_next_send(BlockchainError::NetworkError("timeout".to_string()));
+        }
+
+        let transaction = transfer(&payer, backend.get_latest_blockhash().await.unwrap());
+        let result = submit(&backend, &fast_policy(), transaction, not_resignable).await;
+        assert!(matches!(result, Err(BlockchainError::NetworkError(_))));
+        assert!(backend.sent_transactions().is_empty());
+    }
+}
@@ -0,0 +1,527 @@
+//! Streams of on-chain changes over the PubSub websocket.
+//!
+//! Each subscription runs on its own connection to `ws_url` in a background
+//! task. When the connection drops, the task reconnects with the configured
+//! retry backoff and subscribes again. After every (re)subscribe it reads the
+//! current state over RPC, so nothing that changed in the gap is missed.
+//! Account updates are skipped when their data is unchanged since the last
+//! update delivered.
+
+use futures::{future::ready, stream::BoxStream, Stream, StreamExt};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSignatureSubscribeConfig},
+    rpc_filter::RpcFilterType,
+    rpc_response::RpcSignatureResult,
+};
+use solana_sdk::{
+    account::Account,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::TransactionError,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::errors::{BlockchainError, Result};
+use crate::SolanaClient;
+use mkt4u_program::state::{
+    unpack, CampaignAccount, CampaignData, CampaignMetrics, RewardProgram, RewardProgramAccount,
+};
+
+/// Updates buffered per subscription before the connection is back-pressured.
+const BUFFER: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CampaignUpdate {
+    pub address: Pubkey,
+    /// Slot of the notification, or `None` when read over RPC on (re)subscribing.
+    pub slot: Option<u64>,
+    pub campaign: CampaignData,
+    pub metrics: CampaignMetrics,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardProgramUpdate {
+    pub address: Pubkey,
+    /// Slot of the notification, or `None` when read over RPC on (re)subscribing.
+    pub slot: Option<u64>,
+    pub program: RewardProgram,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureUpdate {
+    pub signature: Signature,
+    /// Slot of the notification, or `None` when read over RPC on (re)subscribing.
+    pub slot: Option<u64>,
+    pub result: std::result::Result<(), TransactionError>,
+}
+
+/// Updates from one subscription. Dropping it closes the connection.
+pub struct Subscription<T> {
+    receiver: mpsc::Receiver<T>,
+    task: JoinHandle<()>,
+}
+
+impl<T> Subscription<T> {
+    /// The next update, or `None` once the subscription has ended.
+    pub async fn recv(&mut self) -> Option<T> {
+        self.receiver.recv().await
+    }
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+type Decoder<T> = fn(Pubkey, Option<u64>, &[u8]) -> Option<T>;
+
+/// The accounts a subscription follows.
+#[derive(Clone, Copy)]
+enum Target {
+    Account(Pubkey),
+    /// Every account owned by `program_id` whose data is `data_size` bytes.
+    Program { program_id: Pubkey, data_size: usize },
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Account(address) => write!(f, "account {}", address),
+            Self::Program { program_id, .. } => write!(f, "accounts of {}", program_id),
+        }
+    }
+}
+
+/// Opens subscriptions with the client's `ws_url`, commitment and retry policy.
+///
+/// Subscriptions spawn their connection task, so they must be opened within
+/// a Tokio runtime.
+pub struct SubscriptionManager {
+    client: SolanaClient,
+}
+
+impl SubscriptionManager {
+    pub fn new(client: SolanaClient) -> Self {
+        Self { client }
+    }
+
+    /// Follow one campaign account
+    pub fn campaign(&self, address: &Pubkey) -> Subscription<CampaignUpdate> {
+        self.accounts(Target::Account(*address), decode_campaign)
+    }
+
+    /// Follow every campaign in the current account layout, starting with
+    /// each one as it is now
+    pub fn campaigns(&self) -> Result<Subscription<CampaignUpdate>> {
+        let program_id = Pubkey::from_str(&self.client.get_config().program_id)
+            .map_err(|e| BlockchainError::InvalidPubkey(e.to_string()))?;
+        let target = Target::Program {
+            program_id,
+            data_size: CampaignAccount::LEN,
+        };
+        Ok(self.accounts(target, decode_campaign))
+    }
+
+    /// Follow one reward program account
+    pub fn reward_program(&self, address: &Pubkey) -> Subscription<RewardProgramUpdate> {
+        self.accounts(Target::Account(*address), decode_reward_program)
+    }
+
+    /// Wait for `signature` to reach the configured commitment. The
+    /// subscription ends after its single update.
+    pub fn signature(&self, signature: &Signature) -> Subscription<SignatureUpdate> {
+        let (sender, receiver) = mpsc::channel(1);
+        let task = tokio::spawn(follow_signature(self.client.clone(), *signature, sender));
+        Subscription { receiver, task }
+    }
+
+    fn accounts<T: Send + 'static>(&self, target: Target, decode: Decoder<T>) -> Subscription<T> {
+        let (sender, receiver) = mpsc::channel(BUFFER);
+        let task = tokio::spawn(follow_accounts(self.client.clone(), target, decode, sender));
+        Subscription { receiver, task }
+    }
+}
+
+fn decode_campaign(address: Pubkey, slot: Option<u64>, data: &[u8]) -> Option<CampaignUpdate> {
+    let account = CampaignAccount::unpack(data).ok()?;
+    Some(CampaignUpdate {
+        address,
+        slot,
+        campaign: account.campaign,
+        metrics: account.metrics,
+    })
+}
+
+fn decode_reward_program(address: Pubkey, slot: Option<u64>, data: &[u8]) -> Option<RewardProgramUpdate> {
+    let account: RewardProgramAccount = unpack(data).ok()?;
+    Some(RewardProgramUpdate {
+        address,
+        slot,
+        program: account.program,
+    })
+}
+
+async fn follow_accounts<T>(client: SolanaClient, target: Target, decode: Decoder<T>, sender: mpsc::Sender<T>) {
+    let mut delivered = HashMap::new();
+    let mut attempt = 0;
+    loop {
+        match account_session(&client, target, decode, &sender, &mut delivered, &mut attempt).await {
+            Ok(()) => return,
+            Err(e) => {
+                attempt += 1;
+                log::warn!("Subscription to {} lost, reconnecting (attempt {}): {}", target, attempt, e);
+            }
+        }
+        if !wait_to_reconnect(&client, &sender, attempt).await {
+            return;
+        }
+    }
+}
+
+/// One connection's worth of account updates. `Ok` once the subscriber has
+/// gone away, `Err` when the connection fails.
+async fn account_session<T>(
+    client: &SolanaClient,
+    target: Target,
+    decode: Decoder<T>,
+    sender: &mpsc::Sender<T>,
+    delivered: &mut HashMap<Pubkey, Vec<u8>>,
+    attempt: &mut u32,
+) -> Result<()> {
+    let config = client.get_config();
+    let pubsub = PubsubClient::new(&config.ws_url).await.map_err(network_error)?;
+    let account_config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(config.commitment.config()),
+        ..RpcAccountInfoConfig::default()
+    };
+
+    let mut notifications: BoxStream<'_, (Pubkey, u64, Account)> = match target {
+        Target::Account(address) => pubsub
+            .account_subscribe(&address, Some(account_config))
+            .await
+            .map_err(network_error)?
+            .0
+            .filter_map(move |response| {
+                let account = response.value.decode::<Account>();
+                ready(account.map(|account| (address, response.context.slot, account)))
+            })
+            .boxed(),
+        Target::Program { program_id, data_size } => {
+            let program_config = RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::DataSize(data_size as u64)]),
+                account_config,
+                ..RpcProgramAccountsConfig::default()
+            };
+            pubsub
+                .program_subscribe(&program_id, Some(program_config))
+                .await
+                .map_err(network_error)?
+                .0
+                .filter_map(|response| {
+                    let address = Pubkey::from_str(&response.value.pubkey).ok();
+                    let account = response.value.account.decode::<Account>();
+                    ready(address.zip(account).map(|(address, account)| (address, response.context.slot, account)))
+                })
+                .boxed()
+        }
+    };
+    *attempt = 0;
+
+    // Catch up on anything that changed before the subscription was in place
+    let backend = client.get_backend();
+    let current = match target {
+        Target::Account(address) => backend.get_account(&address).await?.map(|account| (address, account)).into_iter().collect(),
+        Target::Program { program_id, data_size } => backend
+            .get_program_accounts(&program_id)
+            .await?
+            .into_iter()
+            .filter(|(_, account)| account.data.len() == data_size)
+            .collect::<Vec<_>>(),
+    };
+    for (address, account) in current {
+        if !deliver(decode, sender, delivered, address, None, account.data).await {
+            return Ok(());
+        }
+    }
+
+    loop {
+        tokio::select! {
+            _ = sender.closed() => return Ok(()),
+            notification = notifications.next() => match notification {
+                Some((address, slot, account)) => {
+                    if !deliver(decode, sender, delivered, address, Some(slot), account.data).await {
+                        return Ok(());
+                    }
+                }
+                None => return Err(connection_closed(&config.ws_url)),
+            },
+        }
+    }
+}
+
+/// Send the decoded account unless its data is what was last delivered for
+/// `address`. Returns false once the subscriber has gone away.
+async fn deliver<T>(
+    decode: Decoder<T>,
+    sender: &mpsc::Sender<T>,
+    delivered: &mut HashMap<Pubkey, Vec<u8>>,
+    address: Pubkey,
+    slot: Option<u64>,
+    data: Vec<u8>,
+) -> bool {
+    if delivered.get(&address) == Some(&data) {
+        return true;
+    }
+    let Some(update) = decode(address, slot, &data) else {
+        log::debug!("Skipping update to {} that does not decode", address);
+        return true;
+    };
+    delivered.insert(address, data);
+    sender.send(update).await.is_ok()
+}
+
+async fn follow_signature(client: SolanaClient, signature: Signature, sender: mpsc::Sender<SignatureUpdate>) {
+    let mut attempt = 0;
+    loop {
+        match signature_session(&client, signature, &sender, &mut attempt).await {
+            Ok(()) => return,
+            Err(e) => {
+                attempt += 1;
+                log::warn!("Subscription to {} lost, reconnecting (attempt {}): {}", signature, attempt, e);
+            }
+        }
+        if !wait_to_reconnect(&client, &sender, attempt).await {
+            return;
+        }
+    }
+}
+
+async fn signature_session(
+    client: &SolanaClient,
+    signature: Signature,
+    sender: &mpsc::Sender<SignatureUpdate>,
+    attempt: &mut u32,
+) -> Result<()> {
+    let config = client.get_config();
+    let pubsub = PubsubClient::new(&config.ws_url).await.map_err(network_error)?;
+    let subscribe_config = RpcSignatureSubscribeConfig {
+        commitment: Some(config.commitment.config()),
+        enable_received_notification: Some(false),
+    };
+    let (mut notifications, _unsubscribe) = pubsub
+        .signature_subscribe(&signature, Some(subscribe_config))
+        .await
+        .map_err(network_error)?;
+    *attempt = 0;
+
+    // The notification is sent once, so it may have gone out while disconnected
+    if let Some(result) = client.get_backend().get_signature_status(&signature).await? {
+        let _ = sender.send(SignatureUpdate { signature, slot: None, result }).await;
+        return Ok(());
+    }
+
+    loop {
+        tokio::select! {
+            _ = sender.closed() => return Ok(()),
+            notification = notifications.next() => match notification {
+                Some(response) => {
+                    if let RpcSignatureResult::ProcessedSignature(processed) = response.value {
+                        let result = processed.err.map_or(Ok(()), Err);
+                        let slot = Some(response.context.slot);
+                        let _ = sender.send(SignatureUpdate { signature, slot, result }).await;
+                        return Ok(());
+                    }
+                }
+                None => return Err(connection_closed(&config.ws_url)),
+            },
+        }
+    }
+}
+
+/// Back off before reconnecting. Returns false if the subscriber went away meanwhile.
+async fn wait_to_reconnect<T>(client: &SolanaClient, sender: &mpsc::Sender<T>, attempt: u32) -> bool {
+    tokio::select! {
+        _ = sender.closed() => false,
+        _ = tokio::time::sleep(client.get_config().retry.backoff(attempt)) => true,
+    }
+}
+
+fn network_error(error: impl fmt::Display) -> BlockchainError {
+    BlockchainError::NetworkError(format!("PubSub: {}", error))
+}
+
+fn connection_closed(ws_url: &str) -> BlockchainError {
+    BlockchainError::NetworkError(format!("PubSub connection to {} closed", ws_url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::MemoryBackend,
+        fixtures::{program_account, sample_campaign},
+        submit::RetryPolicy,
+        BlockchainConfig,
+    };
+    use futures::SinkExt;
+    use serde_json::{json, Value};
+    use solana_account_decoder::UiAccount;
+    use solana_sdk::{
+        signature::{Keypair, Signer},
+        system_instruction,
+    };
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message;
+
+    /// A PubSub endpoint that takes one connection per entry in `script`,
+    /// answers its subscription, sends it that entry's notification results
+    /// and hangs up. Resolves to the subscribe requests it received.
+    async fn fake_pubsub(script: Vec<Vec<Value>>) -> (String, JoinHandle<Vec<Value>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for (subscription, results) in script.into_iter().enumerate() {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                let request: Value = loop {
+                    if let Message::Text(text) = ws.next().await.unwrap().unwrap() {
+                        break serde_json::from_str(&text).unwrap();
+                    }
+                };
+                let reply = json!({"jsonrpc": "2.0", "result": subscription, "id": request["id"]});
+                ws.send(Message::Text(reply.to_string())).await.unwrap();
+
+                let method = request["method"].as_str().unwrap().replace("Subscribe", "Notification");
+                for result in results {
+                    let params = json!({"result": result, "subscription": subscription});
+                    let notification = json!({"jsonrpc": "2.0", "method": method, "params": params});
+                    ws.send(Message::Text(notification.to_string())).await.unwrap();
+                }
+                requests.push(request);
+                let _ = ws.close(None).await;
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    fn client(ws_url: String) -> (SolanaClient, Arc<MemoryBackend>, Keypair) {
+        let backend = Arc::new(MemoryBackend::new());
+        let payer = Keypair::new();
+        backend.set_balance(payer.pubkey(), 1_000_000_000);
+        let config = BlockchainConfig {
+            ws_url,
+            program_id: Pubkey::new_unique().to_string(),
+            payer_keypair: Some(payer.to_base58_string()),
+            retry: RetryPolicy {
+                max_attempts: 3,
+                initial_backoff_ms: 100,
+                max_backoff_ms: 100,
+            },
+            ..BlockchainConfig::default()
+        };
+        (SolanaClient::with_backend(config, backend.clone()).unwrap(), backend, payer)
+    }
+
+    fn campaign_account(program_id: &Pubkey, views: u64) -> Account {
+        let campaign = CampaignData {
+            id: "campaign-1".to_string(),
+            ..sample_campaign()
+        };
+        let mut state = CampaignAccount::new(Pubkey::new_unique(), campaign);
+        state.metrics.views = views;
+        program_account(program_id, &state, CampaignAccount::LEN)
+    }
+
+    fn account_notification(slot: u64, address: &Pubkey, account: &Account) -> Value {
+        let account = UiAccount::encode(address, account, UiAccountEncoding::Base64, None, None);
+        json!({"context": {"slot": slot}, "value": account})
+    }
+
+    #[tokio::test]
+    async fn test_campaign_updates_survive_reconnects() {
+        let address = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let [a, b, c, d] = [1, 2, 3, 4].map(|views| campaign_account(&owner, views));
+        let (url, server) = fake_pubsub(vec![
+            // The first notification repeats the state read on subscribing
+            vec![account_notification(10, &address, &a), account_notification(11, &address, &b)],
+            vec![account_notification(20, &address, &d)],
+        ])
+        .await;
+        let (client, backend, _) = client(url);
+        backend.set_account(address, a);
+        let mut subscription = SubscriptionManager::new(client).campaign(&address);
+
+        let update = subscription.recv().await.unwrap();
+        assert_eq!((update.slot, update.metrics.views), (None, 1));
+        assert_eq!(update.campaign.id, "campaign-1");
+        let update = subscription.recv().await.unwrap();
+        assert_eq!((update.slot, update.metrics.views), (Some(11), 2));
+
+        // Changed while reconnecting, so read back on resubscribing
+        backend.set_account(address, c);
+        let update = subscription.recv().await.unwrap();
+        assert_eq!((update.slot, update.metrics.views), (None, 3));
+        let update = subscription.recv().await.unwrap();
+        assert_eq!((update.slot, update.metrics.views), (Some(20), 4));
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 2);
+        for request in requests {
+            assert_eq!(request["method"], "accountSubscribe");
+            assert_eq!(request["params"][0], address.to_string());
+            assert_eq!(request["params"][1]["encoding"], "base64");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_signature_confirmation_after_reconnect() {
+        let (url, server) = fake_pubsub(vec![
+            vec![],
+            vec![json!({"context": {"slot": 7}, "value": {"err": null}})],
+            vec![],
+        ])
+        .await;
+        let (client, _, payer) = client(url);
+        let subscriptions = SubscriptionManager::new(client.clone());
+
+        let pending = Signature::new_unique();
+        let mut subscription = subscriptions.signature(&pending);
+        let update = subscription.recv().await.unwrap();
+        assert_eq!(update, SignatureUpdate { signature: pending, slot: Some(7), result: Ok(()) });
+        assert!(subscription.recv().await.is_none());
+
+        // A transaction that already landed is reported without a notification
+        let transfer = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+        let receipt = client.send_instructions(&[transfer]).await.unwrap();
+        let landed = Signature::from_str(&receipt.signature).unwrap();
+        let mut subscription = subscriptions.signature(&landed);
+        let update = subscription.recv().await.unwrap();
+        assert_eq!(update, SignatureUpdate { signature: landed, slot: None, result: Ok(()) });
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests[0]["method"], "signatureSubscribe");
+        assert_eq!(requests[2]["params"][0], landed.to_string());
+    }
+}
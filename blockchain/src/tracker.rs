@@ -0,0 +1,435 @@
+//! Following sent transactions until they are final enough.
+//!
+//! `send_instructions` holds the caller until the cluster confirms. The
+//! tracker instead takes a signature that has only been sent and polls
+//! `getSignatureStatuses` for it in the background, reporting each
+//! commitment level it passes up to the one asked for. A signature still
+//! unseen once its blockhash has expired can never land, and is reported as
+//! dropped so the caller can build a new transaction.
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{hash::Hash, signature::Signature, transaction::TransactionError};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+
+use crate::backend::{ChainBackend, SignatureStatus, MAX_SIGNATURE_STATUSES};
+use crate::config::Commitment;
+use crate::errors::{BlockchainError, Result};
+use crate::fees::TransactionReceipt;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfirmationPolicy {
+    /// Time between status checks of pending transactions.
+    pub poll_interval_ms: u64,
+    /// Commitment reward payouts wait for before they count as paid.
+    pub payouts: Commitment,
+    /// Commitment campaign metrics updates wait for.
+    pub metrics: Commitment,
+}
+
+impl Default for ConfirmationPolicy {
+    fn default() -> Self {
+        // Roughly one slot between checks
+        Self {
+            poll_interval_ms: 400,
+            payouts: Commitment::Finalized,
+            metrics: Commitment::Processed,
+        }
+    }
+}
+
+impl ConfirmationPolicy {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionState {
+    Processed,
+    Confirmed,
+    Finalized,
+    /// Landed, but the program rejected it. Nothing further is reported.
+    Failed(TransactionError),
+    /// Its blockhash expired before it landed, so it never will. Nothing
+    /// further is reported.
+    Dropped,
+}
+
+impl From<Commitment> for TransactionState {
+    fn from(commitment: Commitment) -> Self {
+        match commitment {
+            Commitment::Processed => Self::Processed,
+            Commitment::Confirmed => Self::Confirmed,
+            Commitment::Finalized => Self::Finalized,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmationUpdate {
+    pub signature: Signature,
+    pub state: TransactionState,
+    /// Slot the transaction landed in; `None` once dropped.
+    pub slot: Option<u64>,
+}
+
+type Callback = Box<dyn Fn(&ConfirmationUpdate) + Send + Sync>;
+
+enum Listener {
+    Channel(mpsc::UnboundedSender<ConfirmationUpdate>),
+    Callback(Callback),
+}
+
+impl Listener {
+    fn notify(&self, update: &ConfirmationUpdate) {
+        match self {
+            // A caller that stopped listening is no reason to stop tracking
+            Self::Channel(sender) => {
+                let _ = sender.send(update.clone());
+            }
+            Self::Callback(callback) => callback(update),
+        }
+    }
+}
+
+struct Pending {
+    blockhash: Hash,
+    target: Commitment,
+    reached: Option<Commitment>,
+    listener: Arc<Listener>,
+}
+
+impl Pending {
+    /// Updates for every level passed since the last check, and whether tracking is over.
+    fn advance(&mut self, signature: Signature, status: &SignatureStatus) -> (Vec<ConfirmationUpdate>, bool) {
+        let update = |state| ConfirmationUpdate {
+            signature,
+            state,
+            slot: Some(status.slot),
+        };
+        if let Some(err) = &status.err {
+            return (vec![update(TransactionState::Failed(err.clone()))], true);
+        }
+
+        let reached = status.commitment.min(self.target);
+        let updates = [Commitment::Processed, Commitment::Confirmed, Commitment::Finalized]
+            .into_iter()
+            .filter(|&level| Some(level) > self.reached && level <= reached)
+            .map(|level| update(level.into()))
+            .collect();
+        self.reached = self.reached.max(Some(reached));
+        (updates, reached == self.target)
+    }
+}
+
+/// Polls the status of sent transactions and reports their progress.
+///
+/// Cloning is cheap and clones share the pending set. Tracking starts a
+/// background poller on first use, so it must happen within a Tokio
+/// runtime; the poller stops once every clone is dropped.
+#[derive(Clone)]
+pub struct ConfirmationTracker {
+    inner: Arc<TrackerInner>,
+}
+
+struct TrackerInner {
+    backend: Arc<dyn ChainBackend>,
+    poll_interval: Duration,
+    pending: Mutex<HashMap<Signature, Pending>>,
+    /// Wakes the poller early when a signature is added.
+    added: Arc<Notify>,
+    poller_started: Mutex<bool>,
+}
+
+impl ConfirmationTracker {
+    pub fn new(backend: Arc<dyn ChainBackend>, poll_interval: Duration) -> Self {
+        Self {
+            inner: Arc::new(TrackerInner {
+                backend,
+                poll_interval,
+                pending: Mutex::new(HashMap::new()),
+                added: Arc::new(Notify::new()),
+                poller_started: Mutex::new(false),
+            }),
+        }
+    }
+
+    /// Track `signature`, sent on `blockhash`, until it reaches `target`.
+    /// The receiver closes after the last update.
+    pub fn track(
+        &self,
+        signature: Signature,
+        blockhash: Hash,
+        target: Commitment,
+    ) -> mpsc::UnboundedReceiver<ConfirmationUpdate> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.watch(signature, blockhash, target, Listener::Channel(sender));
+        receiver
+    }
+
+    /// Track `signature` like `track`, calling `callback` with each update
+    /// from the poller task instead
+    pub fn track_with(
+        &self,
+        signature: Signature,
+        blockhash: Hash,
+        target: Commitment,
+        callback: impl Fn(&ConfirmationUpdate) + Send + Sync + 'static,
+    ) {
+        self.watch(signature, blockhash, target, Listener::Callback(Box::new(callback)));
+    }
+
+    /// Signatures still being followed.
+    pub fn pending_count(&self) -> usize {
+        self.inner.pending.lock().unwrap().len()
+    }
+
+    /// Check every pending signature once. The poller does this on its own;
+    /// calling it directly only makes the next check happen sooner.
+    pub async fn poll(&self) -> Result<()> {
+        self.inner.poll().await
+    }
+
+    fn watch(&self, signature: Signature, blockhash: Hash, target: Commitment, listener: Listener) {
+        let pending = Pending {
+            blockhash,
+            target,
+            reached: None,
+            listener: Arc::new(listener),
+        };
+        self.inner.pending.lock().unwrap().insert(signature, pending);
+
+        let mut started = self.inner.poller_started.lock().unwrap();
+        if !*started {
+            tokio::spawn(run_poller(Arc::downgrade(&self.inner)));
+            *started = true;
+        }
+        self.inner.added.notify_one();
+    }
+}
+
+impl TrackerInner {
+    async fn poll(&self) -> Result<()> {
+        let waiting: Vec<(Signature, Hash)> = self
+            .pending
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(signature, pending)| (*signature, pending.blockhash))
+            .collect();
+        if waiting.is_empty() {
+            return Ok(());
+        }
+
+        // Blockhashes are checked before statuses: a signature unseen after its
+        // blockhash had already expired cannot land later
+        let mut expired = HashSet::new();
+        for blockhash in waiting.iter().map(|(_, blockhash)| *blockhash).collect::<HashSet<_>>() {
+            if !self.backend.is_blockhash_valid(&blockhash).await? {
+                expired.insert(blockhash);
+            }
+        }
+        let signatures: Vec<Signature> = waiting.iter().map(|(signature, _)| *signature).collect();
+        let mut statuses = Vec::with_capacity(signatures.len());
+        for chunk in signatures.chunks(MAX_SIGNATURE_STATUSES) {
+            statuses.extend(self.backend.get_signature_statuses(chunk).await?);
+        }
+
+        let mut notifications = Vec::new();
+        {
+            let mut pending = self.pending.lock().unwrap();
+            for ((signature, blockhash), status) in waiting.into_iter().zip(statuses) {
+                let Some(entry) = pending.get_mut(&signature) else {
+                    continue;
+                };
+                let (updates, done) = match status {
+                    Some(status) => entry.advance(signature, &status),
+                    None if expired.contains(&blockhash) => {
+                        let dropped = ConfirmationUpdate {
+                            signature,
+                            state: TransactionState::Dropped,
+                            slot: None,
+                        };
+                        (vec![dropped], true)
+                    }
+                    None => continue,
+                };
+                notifications.extend(updates.into_iter().map(|update| (entry.listener.clone(), update)));
+                if done {
+                    pending.remove(&signature);
+                }
+            }
+        }
+
+        // Listeners run unlocked so a callback may track further signatures
+        for (listener, update) in notifications {
+            listener.notify(&update);
+        }
+        Ok(())
+    }
+}
+
+async fn run_poller(inner: Weak<TrackerInner>) {
+    loop {
+        let Some(tracker) = inner.upgrade() else {
+            return;
+        };
+        if let Err(e) = tracker.poll().await {
+            log::warn!("Checking pending transactions failed: {}", e);
+        }
+
+        // Sleep without holding the tracker, so dropping it stops the poller
+        let (interval, added) = (tracker.poll_interval, tracker.added.clone());
+        drop(tracker);
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = added.notified() => {}
+        }
+    }
+}
+
+/// A transaction sent without waiting, with its progress as it is tracked.
+pub struct TrackedTransaction {
+    pub receipt: TransactionReceipt,
+    /// Commitment the transaction is tracked up to.
+    pub target: Commitment,
+    pub updates: mpsc::UnboundedReceiver<ConfirmationUpdate>,
+}
+
+impl TrackedTransaction {
+    /// Wait until the transaction reaches the commitment it is tracked to.
+    ///
+    /// A dry run returns at once, since nothing was sent.
+    pub async fn wait(mut self) -> Result<TransactionReceipt> {
+        if self.receipt.dry_run {
+            return Ok(self.receipt);
+        }
+
+        while let Some(update) = self.updates.recv().await {
+            let reached = match update.state {
+                TransactionState::Processed => Commitment::Processed,
+                TransactionState::Confirmed => Commitment::Confirmed,
+                TransactionState::Finalized => Commitment::Finalized,
+                TransactionState::Failed(err) => return Err(BlockchainError::ProgramError(err.to_string())),
+                TransactionState::Dropped => return Err(BlockchainError::BlockhashExpired),
+            };
+            if reached == self.target {
+                return Ok(self.receipt);
+            }
+        }
+        Err(BlockchainError::TransactionError(format!(
+            "Stopped tracking {} before it was {:?}",
+            self.receipt.signature, self.target
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    };
+
+    /// A tracker whose poller waits long enough for tests to poll by hand.
+    fn tracker() -> (ConfirmationTracker, Arc<MemoryBackend>, Keypair) {
+        let backend = Arc::new(MemoryBackend::new());
+        let payer = Keypair::new();
+        backend.set_balance(payer.pubkey(), 1_000_000);
+        let tracker = ConfirmationTracker::new(backend.clone(), Duration::from_secs(3600));
+        (tracker, backend, payer)
+    }
+
+    async fn send(backend: &MemoryBackend, payer: &Keypair) -> (Signature, Hash) {
+        let blockhash = backend.get_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1)],
+            Some(&payer.pubkey()),
+            &[payer],
+            blockhash,
+        );
+        (backend.send_transaction(&transaction).await.unwrap(), blockhash)
+    }
+
+    fn states(updates: &mut mpsc::UnboundedReceiver<ConfirmationUpdate>) -> Vec<TransactionState> {
+        std::iter::from_fn(|| updates.try_recv().ok()).map(|update| update.state).collect()
+    }
+
+    #[tokio::test]
+    async fn test_reports_each_level_up_to_target() {
+        let (tracker, backend, payer) = tracker();
+        backend.set_landing_commitment(Commitment::Processed);
+        backend.set_slot(42);
+        let (signature, blockhash) = send(&backend, &payer).await;
+
+        let mut updates = tracker.track(signature, blockhash, Commitment::Finalized);
+        tracker.poll().await.unwrap();
+        let update = updates.try_recv().unwrap();
+        assert_eq!(update, ConfirmationUpdate { signature, state: TransactionState::Processed, slot: Some(42) });
+        tracker.poll().await.unwrap();
+        assert!(states(&mut updates).is_empty());
+
+        // Levels passed between polls are still reported, in order
+        backend.set_commitment(&signature, Commitment::Finalized);
+        tracker.poll().await.unwrap();
+        assert_eq!(states(&mut updates), vec![TransactionState::Confirmed, TransactionState::Finalized]);
+        assert!(updates.recv().await.is_none());
+        assert_eq!(tracker.pending_count(), 0);
+
+        // Nothing past the target is waited for
+        let (signature, blockhash) = send(&backend, &payer).await;
+        let mut updates = tracker.track(signature, blockhash, Commitment::Processed);
+        tracker.poll().await.unwrap();
+        assert_eq!(states(&mut updates), vec![TransactionState::Processed]);
+        assert!(updates.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reports_dropped_once_blockhash_expires() {
+        let (tracker, backend, payer) = tracker();
+        backend.lose_next_send();
+        let (signature, blockhash) = send(&backend, &payer).await;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = seen.clone();
+        tracker.track_with(signature, blockhash, Commitment::Confirmed, move |update| {
+            recorded.lock().unwrap().push(update.clone());
+        });
+        tracker.poll().await.unwrap();
+        assert!(seen.lock().unwrap().is_empty());
+        assert_eq!(tracker.pending_count(), 1);
+
+        backend.advance_blockhash();
+        tracker.poll().await.unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![ConfirmationUpdate { signature, state: TransactionState::Dropped, slot: None }]
+        );
+        assert_eq!(tracker.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_poller_runs_in_background() {
+        let backend = Arc::new(MemoryBackend::new());
+        let payer = Keypair::new();
+        backend.set_balance(payer.pubkey(), 1_000_000);
+        backend.set_landing_commitment(Commitment::Confirmed);
+        let tracker = ConfirmationTracker::new(backend.clone(), Duration::from_millis(10));
+        let (signature, blockhash) = send(&backend, &payer).await;
+
+        let mut updates = tracker.track(signature, blockhash, Commitment::Finalized);
+        assert_eq!(updates.recv().await.unwrap().state, TransactionState::Processed);
+        assert_eq!(updates.recv().await.unwrap().state, TransactionState::Confirmed);
+        backend.set_commitment(&signature, Commitment::Finalized);
+        assert_eq!(updates.recv().await.unwrap().state, TransactionState::Finalized);
+        assert!(updates.recv().await.is_none());
+    }
+}
@@ -0,0 +1,1527 @@
+//! In-process tests for the on-chain program, driven through the manager APIs.
+
+use async_trait::async_trait;
+use mkt4u_blockchain::{
+    backend::{ChainBackend, SignatureStatus, SimulatedTransaction},
+    campaigns::{
+        CampaignData, CampaignManager, CampaignMetrics, CampaignStatus, Milestone, MilestoneApproval, MilestoneStatus,
+    },
+    config::Commitment,
+    errors::{self, BlockchainError},
+    nonce::{self, NonceManager},
+    rewards::{RewardCriteria, RewardProgram, RewardType, RewardsManager},
+    BlockchainConfig, SolanaClient,
+};
+use mkt4u_program::{
+    error::MktError,
+    instruction,
+    pda::find_campaign_address,
+    state::{CampaignAccount, LegacyCampaignAccount, LegacyCampaignData, CAMPAIGN_ACCOUNT_VERSION},
+};
+use solana_program_test::{processor, BanksClient, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
+    message::{Message, VersionedMessage},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    rent::Rent,
+    program_pack::Pack,
+    signature::{Keypair, Signature, Signer},
+    system_instruction, system_program,
+    transaction::{Transaction, TransactionError, VersionedTransaction},
+};
+use spl_associated_token_account::{get_associated_token_address, instruction::create_associated_token_account};
+use spl_token::state::{Account as TokenAccount, Mint};
+use std::sync::Arc;
+
+fn program_test(program_id: Pubkey) -> ProgramTest {
+    ProgramTest::new(
+        "mkt4u_program",
+        program_id,
+        processor!(mkt4u_program::processor::process_instruction),
+    )
+}
+
+/// `ChainBackend` over the in-process bank, so manager calls execute the program.
+struct BanksBackend {
+    banks_client: BanksClient,
+}
+
+impl BanksBackend {
+    /// Execute on the bank directly, which unlike `process_transaction` also
+    /// takes transactions on a durable nonce.
+    ///
+    /// A batch from `process` can hold its account locks for a moment after
+    /// its status is visible, so a lock conflict is retried as a cluster would.
+    async fn process_on_bank(&self, transaction: VersionedTransaction) -> errors::Result<Signature> {
+        let mut attempts = 0;
+        loop {
+            let result = self
+                .banks_client
+                .clone()
+                .process_transaction_with_metadata(transaction.clone())
+                .await
+                .map_err(|e| BlockchainError::RpcError(e.to_string()))?
+                .result;
+            match result {
+                Err(TransactionError::AccountInUse) if attempts < 10 => {
+                    attempts += 1;
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                }
+                result => {
+                    result.map_err(|e| BlockchainError::TransactionError(e.to_string()))?;
+                    return Ok(transaction.signatures[0]);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ChainBackend for BanksBackend {
+    async fn get_balance(&self, pubkey: &Pubkey) -> errors::Result<u64> {
+        self.banks_client
+            .clone()
+            .get_balance(*pubkey)
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))
+    }
+
+    async fn get_account_data(&self, pubkey: &Pubkey) -> errors::Result<Vec<u8>> {
+        self.banks_client
+            .clone()
+            .get_account(*pubkey)
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))?
+            .map(|account| account.data)
+            .ok_or_else(|| BlockchainError::AccountNotFound(pubkey.to_string()))
+    }
+
+    async fn get_account(&self, pubkey: &Pubkey) -> errors::Result<Option<Account>> {
+        self.banks_client
+            .clone()
+            .get_account(*pubkey)
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> errors::Result<Vec<Option<Account>>> {
+        let mut accounts = Vec::with_capacity(pubkeys.len());
+        for pubkey in pubkeys {
+            accounts.push(self.get_account(pubkey).await?);
+        }
+        Ok(accounts)
+    }
+
+    async fn get_latest_blockhash(&self) -> errors::Result<Hash> {
+        self.banks_client
+            .clone()
+            .get_latest_blockhash()
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))
+    }
+
+    async fn get_slot(&self) -> errors::Result<u64> {
+        self.banks_client
+            .clone()
+            .get_root_slot()
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(&self, data_len: usize) -> errors::Result<u64> {
+        let rent = self
+            .banks_client
+            .clone()
+            .get_rent()
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))?;
+        Ok(rent.minimum_balance(data_len))
+    }
+
+    async fn send_and_confirm_transaction(
+        &self,
+        transaction: &Transaction,
+    ) -> errors::Result<Signature> {
+        self.process_on_bank(transaction.clone().into()).await
+    }
+
+    async fn send_transaction(&self, transaction: &Transaction) -> errors::Result<Signature> {
+        self.banks_client
+            .clone()
+            .send_transaction(transaction.clone())
+            .await
+            .map_err(|e| BlockchainError::TransactionError(e.to_string()))?;
+        Ok(transaction.signatures[0])
+    }
+
+    async fn send_and_confirm_versioned_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+    ) -> errors::Result<Signature> {
+        self.process_on_bank(transaction.clone()).await
+    }
+
+    async fn send_versioned_transaction(&self, transaction: &VersionedTransaction) -> errors::Result<Signature> {
+        self.banks_client
+            .clone()
+            .send_transaction(transaction.clone())
+            .await
+            .map_err(|e| BlockchainError::TransactionError(e.to_string()))?;
+        Ok(transaction.signatures[0])
+    }
+
+    async fn simulate_transaction(
+        &self,
+        transaction: &VersionedTransaction,
+        _addresses: &[Pubkey],
+    ) -> errors::Result<SimulatedTransaction> {
+        let simulation = self
+            .banks_client
+            .clone()
+            .simulate_transaction(transaction.clone())
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))?;
+        let details = simulation.simulation_details;
+        Ok(SimulatedTransaction {
+            err: simulation.result.and_then(|result| result.err()),
+            logs: details.as_ref().map(|d| d.logs.clone()).unwrap_or_default(),
+            units_consumed: details.map(|d| d.units_consumed),
+            // The bank does not report post-simulation account state
+            accounts: None,
+        })
+    }
+
+    async fn get_fee_for_message(&self, message: &VersionedMessage) -> errors::Result<u64> {
+        let VersionedMessage::Legacy(message) = message else {
+            return Err(BlockchainError::RpcError(
+                "BanksClient cannot price v0 messages".to_string(),
+            ));
+        };
+        self.banks_client
+            .clone()
+            .get_fee_for_message(message.clone())
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))?
+            .ok_or_else(|| BlockchainError::RpcError("Blockhash not found".to_string()))
+    }
+
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> errors::Result<bool> {
+        // The bank moves to a new blockhash every tick, but prices fees on any it still remembers
+        let message = Message::new_with_blockhash(&[], Some(&Pubkey::new_unique()), blockhash);
+        let fee = self
+            .banks_client
+            .clone()
+            .get_fee_for_message(message)
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))?;
+        Ok(fee.is_some())
+    }
+
+    async fn get_signature_status(
+        &self,
+        signature: &Signature,
+    ) -> errors::Result<Option<Result<(), TransactionError>>> {
+        let status = self
+            .banks_client
+            .clone()
+            .get_transaction_status(*signature)
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))?;
+        Ok(status.map(|status| status.err.map_or(Ok(()), Err)))
+    }
+
+    async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> errors::Result<Vec<Option<SignatureStatus>>> {
+        let statuses = self
+            .banks_client
+            .clone()
+            .get_transaction_statuses(signatures.to_vec())
+            .await
+            .map_err(|e| BlockchainError::RpcError(e.to_string()))?;
+        // The bank has no voters, so whatever it has processed is as final as it gets
+        Ok(statuses
+            .into_iter()
+            .map(|status| {
+                status.map(|status| SignatureStatus {
+                    slot: status.slot,
+                    commitment: Commitment::Finalized,
+                    err: status.err,
+                })
+            })
+            .collect())
+    }
+
+    async fn get_program_accounts(
+        &self,
+        _program_id: &Pubkey,
+    ) -> errors::Result<Vec<(Pubkey, Account)>> {
+        Err(BlockchainError::RpcError(
+            "BanksClient cannot scan program accounts".to_string(),
+        ))
+    }
+
+    async fn get_recent_prioritization_fees(&self, _accounts: &[Pubkey]) -> errors::Result<Vec<u64>> {
+        Ok(Vec::new())
+    }
+}
+
+fn client_for(program_id: &Pubkey, banks_client: &BanksClient, payer: &Keypair) -> SolanaClient {
+    let config = BlockchainConfig {
+        program_id: program_id.to_string(),
+        payer_keypair: Some(payer.to_base58_string()),
+        ..BlockchainConfig::default()
+    };
+    let backend = BanksBackend {
+        banks_client: banks_client.clone(),
+    };
+    SolanaClient::with_backend(config, Arc::new(backend)).unwrap()
+}
+
+fn sample_campaign() -> CampaignData {
+    CampaignData {
+        id: "test-campaign-1".to_string(),
+        title: "Test Campaign".to_string(),
+        creator: "test-creator".to_string(),
+        target_amount: 1_000_000,
+        current_amount: 0,
+        start_time: 0,
+        end_time: i64::MAX,
+        status: CampaignStatus::Active,
+        metadata_uri: "https://example.com/metadata".to_string(),
+        milestones: Vec::new(),
+        milestone_approval: MilestoneApproval::default(),
+        mint: None,
+        decimals: 9,
+    }
+}
+
+fn sample_reward_program(reward_type: RewardType, pool: u64) -> RewardProgram {
+    RewardProgram {
+        id: "engagement-rewards".to_string(),
+        name: "Engagement Rewards".to_string(),
+        description: "Rewards for engaged users".to_string(),
+        reward_type,
+        total_pool: pool,
+        remaining_pool: pool,
+        start_time: 0,
+        end_time: i64::MAX,
+        criteria: RewardCriteria {
+            min_engagement: 100,
+            min_conversions: 5,
+            min_spend: 50_000,
+            requires_verification: false,
+        },
+        is_active: true,
+    }
+}
+
+async fn process(
+    banks_client: &mut BanksClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+) -> Result<(), TransactionError> {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &all_signers,
+        recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+/// Move the bank's clock to `unix_timestamp`, e.g. past a campaign's end.
+async fn set_clock(context: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    context.set_sysvar(&clock);
+}
+
+fn assert_custom_error<T: std::fmt::Debug>(result: Result<T, TransactionError>, expected: MktError) {
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(expected as u32)),
+    );
+}
+
+async fn create_campaign(
+    banks_client: &mut BanksClient,
+    manager: &CampaignManager,
+    payer: &Keypair,
+    campaign_data: &CampaignData,
+) -> Result<Pubkey, TransactionError> {
+    let (ix, campaign_account) = manager
+        .create_campaign_instruction(&payer.pubkey(), campaign_data)
+        .unwrap();
+    process(banks_client, &[ix], payer, &[]).await?;
+    Ok(campaign_account)
+}
+
+#[tokio::test]
+async fn test_create_campaign() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+    let manager = CampaignManager::new(client_for(&program_id, &banks_client, &payer));
+
+    let campaign_data = sample_campaign();
+    let campaign = manager.create_campaign(&campaign_data).await.unwrap();
+
+    assert_eq!(
+        campaign,
+        find_campaign_address(&program_id, &payer.pubkey(), &campaign_data.id)
+            .0
+            .to_string()
+    );
+    assert_eq!(manager.get_campaign(&campaign).await.unwrap(), campaign_data);
+
+    let account = banks_client
+        .get_account(campaign.parse().unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.owner, program_id);
+    let state = CampaignManager::decode_campaign(&account.data).unwrap();
+    assert_eq!(state.authority, payer.pubkey());
+    assert_eq!(state.metrics, CampaignMetrics::default());
+}
+
+#[tokio::test]
+async fn test_create_campaign_rejects_invalid_schedule() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+    let manager = CampaignManager::new(client_for(&program_id, &banks_client, &payer));
+
+    let mut campaign_data = sample_campaign();
+    campaign_data.end_time = campaign_data.start_time;
+    assert!(manager
+        .create_campaign_instruction(&payer.pubkey(), &campaign_data)
+        .is_err());
+
+    // Bypass the client-side validation to reach the program
+    let (campaign, _) = find_campaign_address(&program_id, &payer.pubkey(), &campaign_data.id);
+    let ix = instruction::create_campaign(&program_id, &campaign, &payer.pubkey(), campaign_data);
+    let result = process(&mut banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::InvalidSchedule);
+}
+
+#[tokio::test]
+async fn test_create_campaign_rejects_duplicate_id() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+    let manager = CampaignManager::new(client_for(&program_id, &banks_client, &payer));
+
+    let mut campaign_data = sample_campaign();
+    create_campaign(&mut banks_client, &manager, &payer, &campaign_data)
+        .await
+        .unwrap();
+
+    // Same database id, so the same address, even with different contents
+    campaign_data.title = "Another title".to_string();
+    let result = create_campaign(&mut banks_client, &manager, &payer, &campaign_data).await;
+    assert_custom_error(result, MktError::AlreadyInitialized);
+}
+
+#[tokio::test]
+async fn test_create_campaign_rejects_foreign_address() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+
+    let campaign_data = sample_campaign();
+    let ix = instruction::create_campaign(
+        &program_id,
+        &Pubkey::new_unique(),
+        &payer.pubkey(),
+        campaign_data,
+    );
+    let result = process(&mut banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::InvalidAccountAddress);
+}
+
+#[tokio::test]
+async fn test_update_metrics_and_close_campaign() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+    let manager = CampaignManager::new(client_for(&program_id, &banks_client, &payer));
+    let campaign_key = manager.create_campaign(&sample_campaign()).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+
+    let metrics = CampaignMetrics {
+        views: 1500,
+        clicks: 150,
+        conversions: 15,
+        total_spent: 500_000,
+        roi: 2.5,
+    };
+    manager.update_metrics(&campaign_key, &metrics).await.unwrap();
+
+    let account = banks_client.get_account(campaign).await.unwrap().unwrap();
+    let state = CampaignManager::decode_campaign(&account.data).unwrap();
+    assert_eq!(state.metrics, metrics);
+
+    manager.close_campaign(&campaign_key).await.unwrap();
+    assert_eq!(
+        manager.get_campaign(&campaign_key).await.unwrap().status,
+        CampaignStatus::Completed
+    );
+
+    // Closed campaigns no longer accept metrics
+    let ix = manager
+        .update_metrics_instruction(&payer.pubkey(), &campaign, &metrics)
+        .unwrap();
+    let result = process(&mut banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::CampaignInactive);
+}
+
+#[tokio::test]
+async fn test_update_metrics_requires_authority() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let intruder = Keypair::new();
+    program_test.add_account(
+        intruder.pubkey(),
+        Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+    );
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let manager = CampaignManager::new(client_for(&program_id, &banks_client, &payer));
+    let campaign = create_campaign(&mut banks_client, &manager, &payer, &sample_campaign())
+        .await
+        .unwrap();
+
+    let ix = manager
+        .update_metrics_instruction(&intruder.pubkey(), &campaign, &CampaignMetrics::default())
+        .unwrap();
+    let result = process(&mut banks_client, &[ix], &intruder, &[]).await;
+    assert_custom_error(result, MktError::InvalidAuthority);
+}
+
+#[tokio::test]
+async fn test_campaign_account_uses_fixed_layout() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+    let manager = CampaignManager::new(client_for(&program_id, &banks_client, &payer));
+
+    let mut campaign_data = sample_campaign();
+    campaign_data.title = "t".repeat(CampaignData::MAX_TITLE_LEN);
+    campaign_data.metadata_uri = "u".repeat(CampaignData::MAX_METADATA_URI_LEN);
+    let campaign = create_campaign(&mut banks_client, &manager, &payer, &campaign_data)
+        .await
+        .unwrap();
+
+    let account = banks_client.get_account(campaign).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), CampaignAccount::LEN);
+    assert_eq!(account.lamports, Rent::default().minimum_balance(CampaignAccount::LEN));
+    let state = CampaignManager::decode_campaign(&account.data).unwrap();
+    assert_eq!(state.version, CAMPAIGN_ACCOUNT_VERSION);
+    assert_eq!(state.campaign, campaign_data);
+}
+
+#[tokio::test]
+async fn test_migrate_legacy_campaign() {
+    let program_id = Pubkey::new_unique();
+    let payer = Keypair::new();
+    let campaign_data = sample_campaign();
+    let (campaign, _) = find_campaign_address(&program_id, &payer.pubkey(), &campaign_data.id);
+
+    let legacy = LegacyCampaignAccount {
+        authority: payer.pubkey(),
+        campaign: LegacyCampaignData {
+            id: campaign_data.id.clone(),
+            title: campaign_data.title.clone(),
+            creator: campaign_data.creator.clone(),
+            target_amount: campaign_data.target_amount,
+            current_amount: campaign_data.current_amount,
+            start_time: campaign_data.start_time,
+            end_time: campaign_data.end_time,
+            is_active: campaign_data.is_active(),
+            metadata_uri: campaign_data.metadata_uri.clone(),
+        },
+        metrics: CampaignMetrics {
+            views: 42,
+            ..CampaignMetrics::default()
+        },
+    };
+    let data = borsh::to_vec(&legacy).unwrap();
+    let mut program_test = program_test(program_id);
+    program_test.add_account(
+        payer.pubkey(),
+        Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+    );
+    program_test.add_account(
+        campaign,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    let (mut banks_client, _, _) = program_test.start().await;
+    let manager = CampaignManager::new(client_for(&program_id, &banks_client, &payer));
+
+    // Legacy accounts are readable but must be migrated before they change
+    let account = banks_client.get_account(campaign).await.unwrap().unwrap();
+    let state = CampaignManager::decode_campaign(&account.data).unwrap();
+    assert_eq!(state.campaign, campaign_data);
+    assert_eq!(state.metrics.views, 42);
+
+    let metrics = CampaignMetrics {
+        views: 100,
+        ..CampaignMetrics::default()
+    };
+    let ix = manager
+        .update_metrics_instruction(&payer.pubkey(), &campaign, &metrics)
+        .unwrap();
+    let result = process(&mut banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::AccountNeedsMigration);
+
+    let ix = manager
+        .migrate_campaign_instruction(&payer.pubkey(), &campaign)
+        .unwrap();
+    process(&mut banks_client, &[ix], &payer, &[]).await.unwrap();
+
+    let account = banks_client.get_account(campaign).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), CampaignAccount::LEN);
+    assert_eq!(account.lamports, Rent::default().minimum_balance(CampaignAccount::LEN));
+    assert!(CampaignAccount::is_current_layout(&account.data));
+    let state = CampaignManager::decode_campaign(&account.data).unwrap();
+    assert_eq!(state.authority, payer.pubkey());
+    assert_eq!(state.campaign, campaign_data);
+    assert_eq!(state.metrics.views, 42);
+
+    let ix = manager
+        .update_metrics_instruction(&payer.pubkey(), &campaign, &metrics)
+        .unwrap();
+    process(&mut banks_client, &[ix], &payer, &[]).await.unwrap();
+
+    // A second migration is rejected
+    let ix = manager
+        .migrate_campaign_instruction(&payer.pubkey(), &campaign)
+        .unwrap();
+    let result = process(&mut banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::AccountAlreadyMigrated);
+}
+
+#[tokio::test]
+async fn test_migrate_resets_claimed_current_amount() {
+    let program_id = Pubkey::new_unique();
+    let payer = Keypair::new();
+    let campaign_data = sample_campaign();
+    let (campaign, _) = find_campaign_address(&program_id, &payer.pubkey(), &campaign_data.id);
+
+    // Before the vault existed clients reported the amount raised themselves
+    let legacy = LegacyCampaignAccount {
+        authority: payer.pubkey(),
+        campaign: LegacyCampaignData {
+            id: campaign_data.id.clone(),
+            title: campaign_data.title.clone(),
+            creator: campaign_data.creator.clone(),
+            target_amount: campaign_data.target_amount,
+            current_amount: campaign_data.target_amount,
+            start_time: campaign_data.start_time,
+            end_time: campaign_data.end_time,
+            is_active: campaign_data.is_active(),
+            metadata_uri: campaign_data.metadata_uri.clone(),
+        },
+        metrics: CampaignMetrics::default(),
+    };
+    let data = borsh::to_vec(&legacy).unwrap();
+    let mut program_test = program_test(program_id);
+    program_test.add_account(
+        payer.pubkey(),
+        Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+    );
+    program_test.add_account(
+        campaign,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    let (mut banks_client, _, _) = program_test.start().await;
+    let manager = CampaignManager::new(client_for(&program_id, &banks_client, &payer));
+
+    let ix = manager
+        .migrate_campaign_instruction(&payer.pubkey(), &campaign)
+        .unwrap();
+    process(&mut banks_client, &[ix], &payer, &[]).await.unwrap();
+    let account = banks_client.get_account(campaign).await.unwrap().unwrap();
+    let state = CampaignManager::decode_campaign(&account.data).unwrap();
+    assert_eq!(state.campaign.current_amount, 0);
+
+    // From here on it counts only what reaches the vault
+    let ix = manager
+        .contribute_instruction(&payer.pubkey(), &campaign, None, 100_000)
+        .unwrap();
+    process(&mut banks_client, &[ix], &payer, &[]).await.unwrap();
+    let account = banks_client.get_account(campaign).await.unwrap().unwrap();
+    let state = CampaignManager::decode_campaign(&account.data).unwrap();
+    assert_eq!(state.campaign.current_amount, 100_000);
+}
+
+#[tokio::test]
+async fn test_create_and_claim_sol_reward() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+    let manager = RewardsManager::new(client_for(&program_id, &banks_client, &payer));
+
+    let program = sample_reward_program(RewardType::SOL { amount: LAMPORTS_PER_SOL }, 5 * LAMPORTS_PER_SOL / 2);
+    let reward_key = manager.create_reward_program(&program).await.unwrap();
+    let reward_account = manager
+        .find_reward_program_address(&payer.pubkey(), &program.id)
+        .unwrap();
+    assert_eq!(reward_key, reward_account.to_string());
+    assert_eq!(manager.get_reward_program(&reward_key).await.unwrap(), program);
+
+    let account = banks_client
+        .get_account(reward_account)
+        .await
+        .unwrap()
+        .unwrap();
+    let rent = Rent::default().minimum_balance(account.data.len());
+    assert_eq!(account.lamports, rent + 5 * LAMPORTS_PER_SOL / 2);
+    let state = RewardsManager::decode_reward_program(&account.data).unwrap();
+    assert_eq!(state.authority, payer.pubkey());
+    assert_eq!(state.program, program);
+
+    let user = Pubkey::new_unique();
+    for user_id in ["user-1", "user-2"] {
+        manager
+            .claim_reward(user_id, &reward_key, &user.to_string())
+            .await
+            .unwrap();
+    }
+    assert_eq!(banks_client.get_balance(user).await.unwrap(), 2 * LAMPORTS_PER_SOL);
+
+    let account = banks_client
+        .get_account(reward_account)
+        .await
+        .unwrap()
+        .unwrap();
+    let state = RewardsManager::decode_reward_program(&account.data).unwrap();
+    assert_eq!(state.program.remaining_pool, LAMPORTS_PER_SOL / 2);
+    assert_eq!(account.lamports, rent + LAMPORTS_PER_SOL / 2);
+
+    // Only half a SOL remains in the pool
+    let ix = manager
+        .claim_reward_instruction(&payer.pubkey(), &reward_account, &user, "user-3")
+        .unwrap();
+    let result = process(&mut banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::RewardPoolExhausted);
+}
+
+#[tokio::test]
+async fn test_simulate_operations() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+    let client = client_for(&program_id, &banks_client, &payer);
+    let campaigns = CampaignManager::new(client.clone());
+    let rewards = RewardsManager::new(client);
+
+    let campaign_data = sample_campaign();
+    let report = campaigns.simulate_create_campaign(&campaign_data).await.unwrap();
+    assert!(report.succeeded(), "{:?}", report.err);
+    assert!(report.units_consumed.unwrap() > 0);
+    assert!(report.logs.iter().any(|log| log.contains(&program_id.to_string())));
+    assert!(report.fee > 0);
+
+    // Nothing was created
+    let campaign = find_campaign_address(&program_id, &payer.pubkey(), &campaign_data.id).0;
+    assert!(banks_client.get_account(campaign).await.unwrap().is_none());
+
+    let program = sample_reward_program(RewardType::SOL { amount: LAMPORTS_PER_SOL }, LAMPORTS_PER_SOL / 2);
+    let reward_key = rewards.create_reward_program(&program).await.unwrap();
+    let report = rewards
+        .simulate_claim_reward("user-1", &reward_key, &Pubkey::new_unique().to_string())
+        .await
+        .unwrap();
+    assert!(!report.succeeded());
+    assert!(report.logs.iter().any(|log| log.contains("custom program error")));
+}
+
+#[tokio::test]
+async fn test_claim_points_reward() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+    let manager = RewardsManager::new(client_for(&program_id, &banks_client, &payer));
+
+    let program = sample_reward_program(RewardType::Points { amount: 100 }, 1_000);
+    let reward_key = manager.create_reward_program(&program).await.unwrap();
+
+    let user = Pubkey::new_unique();
+    manager
+        .claim_reward("user-1", &reward_key, &user.to_string())
+        .await
+        .unwrap();
+
+    let state = manager.get_reward_program(&reward_key).await.unwrap();
+    assert_eq!(state.remaining_pool, 900);
+    assert_eq!(banks_client.get_balance(user).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_claim_reward_against_durable_nonce() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+    let client = client_for(&program_id, &banks_client, &payer);
+    let manager = RewardsManager::new(client.clone());
+    let nonces = NonceManager::new(client.clone());
+
+    let program = sample_reward_program(RewardType::SOL { amount: LAMPORTS_PER_SOL }, 2 * LAMPORTS_PER_SOL);
+    let reward_key = manager.create_reward_program(&program).await.unwrap();
+    let nonce_account = nonces.create_nonce_account("payouts").await.unwrap();
+    let stored = nonces.get_nonce(&nonce_account).await.unwrap();
+    assert_eq!(stored.authority, payer.pubkey());
+
+    let user = Pubkey::new_unique();
+    let unsigned = manager
+        .prepare_claim_reward("user-1", &reward_key, &user.to_string(), &nonce_account)
+        .await
+        .unwrap();
+    let encoded = nonce::serialize_transaction(&unsigned).unwrap();
+
+    // Approval comes later, once the blockhash the nonce was stored on is long gone
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_ne!(banks_client.get_latest_blockhash().await.unwrap(), stored.blockhash());
+
+    let signed = client
+        .sign_durable_transaction(&nonce::deserialize_transaction(&encoded).unwrap())
+        .await
+        .unwrap();
+    client.send_durable_transaction(&signed).await.unwrap();
+    assert_eq!(banks_client.get_balance(user).await.unwrap(), LAMPORTS_PER_SOL);
+
+    // Landing advanced the nonce, so the same payout cannot be replayed
+    assert_ne!(nonces.get_nonce(&nonce_account).await.unwrap().blockhash(), stored.blockhash());
+    assert!(client.send_durable_transaction(&signed).await.is_err());
+    assert_eq!(banks_client.get_balance(user).await.unwrap(), LAMPORTS_PER_SOL);
+}
+
+#[tokio::test]
+async fn test_contribute_to_campaign_vault() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = program_test(program_id);
+    let backer = Keypair::new();
+    program_test.add_account(
+        backer.pubkey(),
+        Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+    );
+    let (mut banks_client, payer, _) = program_test.start().await;
+    let manager = CampaignManager::new(client_for(&program_id, &banks_client, &payer));
+    let campaign_key = manager.create_campaign(&sample_campaign()).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+
+    manager.contribute(&campaign_key, 300_000).await.unwrap();
+    manager.contribute(&campaign_key, 200_000).await.unwrap();
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 100_000)
+        .unwrap();
+    process(&mut banks_client, &[ix], &backer, &[]).await.unwrap();
+
+    assert_eq!(manager.get_campaign(&campaign_key).await.unwrap().current_amount, 600_000);
+    let vault = manager.find_vault_address(&campaign).unwrap();
+    assert_eq!(
+        banks_client.get_balance(vault).await.unwrap(),
+        Rent::default().minimum_balance(0) + 600_000
+    );
+    let receipt = manager
+        .get_contribution(&campaign_key, &payer.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((receipt.campaign, receipt.contributor, receipt.amount), (campaign, payer.pubkey(), 500_000));
+    let receipt = manager
+        .get_contribution(&campaign_key, &backer.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(receipt.amount, 100_000);
+
+    // Zero amounts, receipts at someone else's address and closed campaigns are refused
+    let mut ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 1)
+        .unwrap();
+    ix.data = instruction::MktInstruction::Contribute { amount: 0 }.pack();
+    let result = process(&mut banks_client, &[ix], &backer, &[]).await;
+    assert_custom_error(result, MktError::InvalidAmount);
+
+    let mut ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 1)
+        .unwrap();
+    ix.accounts[2].pubkey = manager
+        .find_contribution_address(&campaign, &payer.pubkey())
+        .unwrap();
+    let result = process(&mut banks_client, &[ix], &backer, &[]).await;
+    assert_custom_error(result, MktError::InvalidAccountAddress);
+
+    manager.close_campaign(&campaign_key).await.unwrap();
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 1)
+        .unwrap();
+    let result = process(&mut banks_client, &[ix], &backer, &[]).await;
+    assert_custom_error(result, MktError::CampaignInactive);
+}
+
+#[tokio::test]
+async fn test_contribute_outside_campaign_window() {
+    let program_id = Pubkey::new_unique();
+    let (mut banks_client, payer, _) = program_test(program_id).start().await;
+    let manager = CampaignManager::new(client_for(&program_id, &banks_client, &payer));
+
+    let upcoming = CampaignData {
+        id: "upcoming".to_string(),
+        start_time: i64::MAX - 1,
+        ..sample_campaign()
+    };
+    let ended = CampaignData {
+        id: "ended".to_string(),
+        start_time: 0,
+        end_time: 1,
+        ..sample_campaign()
+    };
+    for campaign_data in [upcoming, ended] {
+        let campaign = create_campaign(&mut banks_client, &manager, &payer, &campaign_data)
+            .await
+            .unwrap();
+        let ix = manager
+            .contribute_instruction(&payer.pubkey(), &campaign, None, 1_000)
+            .unwrap();
+        let result = process(&mut banks_client, &[ix], &payer, &[]).await;
+        assert_custom_error(result, MktError::OutsideCampaignWindow);
+    }
+}
+
+/// A campaign ending shortly after the bank's current time, and a funded
+/// backer besides the payer.
+async fn funding_test(program_id: Pubkey, target_amount: u64) -> (ProgramTestContext, Keypair, CampaignData) {
+    let mut program_test = program_test(program_id);
+    let backer = Keypair::new();
+    program_test.add_account(
+        backer.pubkey(),
+        Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+    );
+    let mut context = program_test.start_with_context().await;
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    let campaign_data = CampaignData {
+        target_amount,
+        start_time: 0,
+        end_time: clock.unix_timestamp + 1_000,
+        ..sample_campaign()
+    };
+    (context, backer, campaign_data)
+}
+
+#[tokio::test]
+async fn test_refund_when_target_missed() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, LAMPORTS_PER_SOL).await;
+    let payer = context.payer.insecure_clone();
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+
+    manager.contribute(&campaign_key, 300_000).await.unwrap();
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 200_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+
+    // Nothing is refundable while the campaign can still reach its target.
+    // Paid for by the payer, so the retry below is not a duplicate of it.
+    let refund_ix = manager.refund_instruction(&backer.pubkey(), &campaign, None).unwrap();
+    let result = process(&mut context.banks_client, std::slice::from_ref(&refund_ix), &payer, &[&backer]).await;
+    assert_custom_error(result, MktError::CampaignNotEnded);
+
+    set_clock(&mut context, campaign_data.end_time + 1).await;
+    context.get_new_latest_blockhash().await.unwrap();
+    process(&mut context.banks_client, &[refund_ix], &backer, &[]).await.unwrap();
+    let result = manager.withdraw_funds(&campaign_key).await;
+    assert!(result.is_err());
+
+    // The backer gets back their contribution and the receipt's rent, paying only fees
+    let backer_balance = context.banks_client.get_balance(backer.pubkey()).await.unwrap();
+    assert_eq!(backer_balance, LAMPORTS_PER_SOL - 2 * 5_000);
+    let contribution = manager
+        .find_contribution_address(&campaign, &backer.pubkey())
+        .unwrap();
+    assert!(context.banks_client.get_account(contribution).await.unwrap().is_none());
+    assert_eq!(manager.get_campaign(&campaign_key).await.unwrap().current_amount, 300_000);
+
+    manager.refund(&campaign_key).await.unwrap();
+    assert_eq!(manager.get_campaign(&campaign_key).await.unwrap().current_amount, 0);
+    let vault = manager.find_vault_address(&campaign).unwrap();
+    assert_eq!(context.banks_client.get_balance(vault).await.unwrap(), Rent::default().minimum_balance(0));
+}
+
+#[tokio::test]
+async fn test_create_campaign_ignores_claimed_current_amount() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, LAMPORTS_PER_SOL).await;
+    let payer = context.payer.insecure_clone();
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_data = CampaignData {
+        current_amount: LAMPORTS_PER_SOL - 1,
+        ..campaign_data
+    };
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+    assert_eq!(manager.get_campaign(&campaign_key).await.unwrap().current_amount, 0);
+
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 200_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    assert_eq!(manager.get_campaign(&campaign_key).await.unwrap().current_amount, 200_000);
+
+    // The refund is the backer's whole contribution, not a share diluted by the claim
+    set_clock(&mut context, campaign_data.end_time + 1).await;
+    context.get_new_latest_blockhash().await.unwrap();
+    let ix = manager.refund_instruction(&backer.pubkey(), &campaign, None).unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    // Out only two fees and the rent of the vault the backer opened
+    let backer_balance = context.banks_client.get_balance(backer.pubkey()).await.unwrap();
+    assert_eq!(backer_balance, LAMPORTS_PER_SOL - 2 * 5_000 - Rent::default().minimum_balance(0));
+}
+
+#[tokio::test]
+async fn test_withdraw_funds_when_target_met() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, 500_000).await;
+    let payer = context.payer.insecure_clone();
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 400_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    manager.contribute(&campaign_key, 200_000).await.unwrap();
+
+    // Reaching the target does not open the vault before the campaign is over
+    let ix = manager
+        .withdraw_funds_instruction(&payer.pubkey(), &campaign, None)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::CampaignNotEnded);
+
+    set_clock(&mut context, campaign_data.end_time + 1).await;
+    context.get_new_latest_blockhash().await.unwrap();
+
+    // Only the campaign authority can withdraw
+    let ix = manager
+        .withdraw_funds_instruction(&backer.pubkey(), &campaign, None)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &backer, &[]).await;
+    assert_custom_error(result, MktError::InvalidAuthority);
+
+    let before = context.banks_client.get_balance(payer.pubkey()).await.unwrap();
+    manager.withdraw_funds(&campaign_key).await.unwrap();
+    let after = context.banks_client.get_balance(payer.pubkey()).await.unwrap();
+    assert_eq!(after, before + 600_000 - 5_000);
+    let vault = manager.find_vault_address(&campaign).unwrap();
+    assert_eq!(context.banks_client.get_balance(vault).await.unwrap(), Rent::default().minimum_balance(0));
+
+    // Backers of a funded campaign get nothing back
+    let ix = manager.refund_instruction(&backer.pubkey(), &campaign, None).unwrap();
+    let result = process(&mut context.banks_client, &[ix], &backer, &[]).await;
+    assert_custom_error(result, MktError::TargetMet);
+
+    // Withdrawing completed the campaign, so it cannot be cancelled to feign open refunds
+    assert_eq!(
+        manager.get_campaign(&campaign_key).await.unwrap().status,
+        CampaignStatus::Completed
+    );
+    let ix = manager
+        .set_status_instruction(&payer.pubkey(), &campaign, CampaignStatus::Cancelled)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::InvalidStatusTransition);
+}
+
+#[tokio::test]
+async fn test_claimed_current_amount_does_not_unlock_withdrawal() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, 500_000).await;
+    let payer = context.payer.insecure_clone();
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_data = CampaignData {
+        current_amount: 500_000,
+        ..campaign_data
+    };
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 100_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+
+    set_clock(&mut context, campaign_data.end_time + 1).await;
+    let ix = manager
+        .withdraw_funds_instruction(&payer.pubkey(), &campaign, None)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::TargetNotMet);
+
+    let ix = manager.refund_instruction(&backer.pubkey(), &campaign, None).unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    let vault = manager.find_vault_address(&campaign).unwrap();
+    assert_eq!(context.banks_client.get_balance(vault).await.unwrap(), Rent::default().minimum_balance(0));
+}
+
+/// Two milestones splitting a 500_000 lamport target, due after the campaign ends.
+fn milestone_campaign(campaign_data: CampaignData, milestone_approval: MilestoneApproval) -> CampaignData {
+    let end_time = campaign_data.end_time;
+    CampaignData {
+        milestones: vec![
+            Milestone::new(200_000, "Prototype".to_string(), end_time + 100),
+            Milestone::new(300_000, "Launch".to_string(), end_time + 200),
+        ],
+        milestone_approval,
+        ..campaign_data
+    }
+}
+
+#[tokio::test]
+async fn test_release_milestones_approved_by_approver() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, 500_000).await;
+    let payer = context.payer.insecure_clone();
+    let approval = MilestoneApproval::Approver {
+        approver: backer.pubkey().to_string(),
+    };
+    let campaign_data = milestone_campaign(campaign_data, approval);
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+    let vault = manager.find_vault_address(&campaign).unwrap();
+
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 500_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+
+    // Milestones can only be submitted once the campaign is over
+    let ix = manager
+        .submit_milestone_instruction(&payer.pubkey(), &campaign, 0)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::CampaignNotEnded);
+
+    // Once the campaign is over, funds leave the vault milestone by milestone, not all at once
+    set_clock(&mut context, campaign_data.end_time + 1).await;
+    let ix = manager
+        .withdraw_funds_instruction(&payer.pubkey(), &campaign, None)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::MilestonesPending);
+
+    let ix = manager
+        .release_milestone_instruction(&payer.pubkey(), &campaign, None, 0)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::InvalidMilestoneStatus);
+
+    manager.submit_milestone(&campaign_key, 0).await.unwrap();
+
+    // Only the configured approver approves, not the campaign authority
+    let ix = manager
+        .approve_milestone_instruction(&payer.pubkey(), &campaign, 0)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::InvalidAuthority);
+    let ix = manager
+        .approve_milestone_instruction(&backer.pubkey(), &campaign, 0)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+
+    manager.release_milestone(&campaign_key, 0).await.unwrap();
+    let rent = Rent::default().minimum_balance(0);
+    assert_eq!(context.banks_client.get_balance(vault).await.unwrap(), rent + 300_000);
+
+    manager.submit_milestone(&campaign_key, 1).await.unwrap();
+    let ix = manager
+        .approve_milestone_instruction(&backer.pubkey(), &campaign, 1)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    manager.release_milestone(&campaign_key, 1).await.unwrap();
+    assert_eq!(context.banks_client.get_balance(vault).await.unwrap(), rent);
+
+    let state = manager.get_campaign(&campaign_key).await.unwrap();
+    assert!(state
+        .milestones
+        .iter()
+        .all(|milestone| milestone.status == MilestoneStatus::Released));
+    manager.withdraw_funds(&campaign_key).await.unwrap();
+
+    // A released milestone cannot be paid out again
+    let ix = manager
+        .release_milestone_instruction(&payer.pubkey(), &campaign, None, 1)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::InvalidMilestoneStatus);
+}
+
+#[tokio::test]
+async fn test_contributor_vote_and_missed_milestone_refund() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, 500_000).await;
+    let payer = context.payer.insecure_clone();
+    let approval = MilestoneApproval::ContributorVote { quorum_bps: 5_000 };
+    let campaign_data = milestone_campaign(campaign_data, approval);
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+
+    manager.contribute(&campaign_key, 200_000).await.unwrap();
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 400_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    set_clock(&mut context, campaign_data.end_time + 1).await;
+    manager.submit_milestone(&campaign_key, 0).await.unwrap();
+
+    // A third of the votes is short of the quorum, and each backer votes once
+    manager.approve_milestone(&campaign_key, 0).await.unwrap();
+    let state = manager.get_campaign(&campaign_key).await.unwrap();
+    assert_eq!(state.milestones[0].status, MilestoneStatus::Submitted);
+    assert_eq!(state.milestones[0].approvals, 200_000);
+    // Paid for by the backer, so it cannot be mistaken for the first vote
+    let ix = manager
+        .approve_milestone_instruction(&payer.pubkey(), &campaign, 0)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &backer, &[&payer]).await;
+    assert_custom_error(result, MktError::AlreadyVoted);
+
+    let ix = manager
+        .approve_milestone_instruction(&backer.pubkey(), &campaign, 0)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    let state = manager.get_campaign(&campaign_key).await.unwrap();
+    assert_eq!(state.milestones[0].status, MilestoneStatus::Approved);
+    manager.release_milestone(&campaign_key, 0).await.unwrap();
+
+    // The second milestone misses its deadline, so backers share what is left.
+    // Paid for by the payer, so the retry below is not a duplicate of it.
+    let refund_ix = manager.refund_instruction(&backer.pubkey(), &campaign, None).unwrap();
+    let result = process(&mut context.banks_client, std::slice::from_ref(&refund_ix), &payer, &[&backer]).await;
+    assert_custom_error(result, MktError::TargetMet);
+    set_clock(&mut context, campaign_data.milestones[1].deadline + 1).await;
+    context.get_new_latest_blockhash().await.unwrap();
+    let ix = manager
+        .submit_milestone_instruction(&payer.pubkey(), &campaign, 1)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::MilestoneDeadlinePassed);
+
+    let vault = manager.find_vault_address(&campaign).unwrap();
+    let rent = Rent::default().minimum_balance(0);
+    process(&mut context.banks_client, &[refund_ix], &backer, &[]).await.unwrap();
+    assert_eq!(context.banks_client.get_balance(vault).await.unwrap(), rent + 400_000 - 266_666);
+    manager.refund(&campaign_key).await.unwrap();
+    assert_eq!(context.banks_client.get_balance(vault).await.unwrap(), rent);
+    assert_eq!(manager.get_campaign(&campaign_key).await.unwrap().current_amount, 0);
+}
+
+#[tokio::test]
+async fn test_refund_withdraws_votes_from_quorum() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, 500_000).await;
+    let payer = context.payer.insecure_clone();
+    let approval = MilestoneApproval::ContributorVote { quorum_bps: 7_000 };
+    let campaign_data = milestone_campaign(campaign_data, approval);
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+
+    manager.contribute(&campaign_key, 300_000).await.unwrap();
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 200_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    set_clock(&mut context, campaign_data.end_time + 1).await;
+    manager.submit_milestone(&campaign_key, 0).await.unwrap();
+    manager.submit_milestone(&campaign_key, 1).await.unwrap();
+
+    let ix = manager
+        .approve_milestone_instruction(&backer.pubkey(), &campaign, 1)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    let state = manager.get_campaign(&campaign_key).await.unwrap();
+    assert_eq!(state.milestones[1].approvals, 200_000);
+    assert_eq!(state.milestones[1].quorum_base, 500_000);
+
+    // The first milestone is missed and the voter leaves with their vote
+    set_clock(&mut context, campaign_data.milestones[0].deadline + 1).await;
+    context.get_new_latest_blockhash().await.unwrap();
+    let ix = manager.refund_instruction(&backer.pubkey(), &campaign, None).unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    let state = manager.get_campaign(&campaign_key).await.unwrap();
+    assert_eq!(state.current_amount, 300_000);
+    assert_eq!(state.milestones[1].approvals, 0);
+
+    // The rest still fall short of the quorum of what was raised
+    manager.approve_milestone(&campaign_key, 1).await.unwrap();
+    let state = manager.get_campaign(&campaign_key).await.unwrap();
+    assert_eq!(state.milestones[1].approvals, 300_000);
+    assert_eq!(state.milestones[1].status, MilestoneStatus::Submitted);
+}
+
+#[tokio::test]
+async fn test_cancel_refunds_approved_milestones() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, 500_000).await;
+    let payer = context.payer.insecure_clone();
+    let campaign_data = milestone_campaign(campaign_data, MilestoneApproval::CampaignAuthority);
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+
+    manager.contribute(&campaign_key, 300_000).await.unwrap();
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 200_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    set_clock(&mut context, campaign_data.end_time + 1).await;
+    manager.submit_milestone(&campaign_key, 0).await.unwrap();
+    manager.approve_milestone(&campaign_key, 0).await.unwrap();
+    manager.cancel_campaign(&campaign_key).await.unwrap();
+
+    // A cancelled campaign releases nothing, so the approved amount goes back to backers too
+    let ix = manager
+        .release_milestone_instruction(&payer.pubkey(), &campaign, None, 0)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::CampaignCancelled);
+
+    let ix = manager.refund_instruction(&backer.pubkey(), &campaign, None).unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    manager.refund(&campaign_key).await.unwrap();
+    let vault = manager.find_vault_address(&campaign).unwrap();
+    assert_eq!(context.banks_client.get_balance(vault).await.unwrap(), Rent::default().minimum_balance(0));
+    // The backer is out only two fees
+    let backer_balance = context.banks_client.get_balance(backer.pubkey()).await.unwrap();
+    assert_eq!(backer_balance, LAMPORTS_PER_SOL - 2 * 5_000);
+}
+
+/// Create a mint with 6 decimals and give each of `holders` 10 tokens in
+/// their associated token account.
+async fn create_token_mint(context: &mut ProgramTestContext, holders: &[Pubkey]) -> Pubkey {
+    let payer = context.payer.insecure_clone();
+    let mint = Keypair::new();
+    let mut instructions = vec![
+        system_instruction::create_account(
+            &payer.pubkey(),
+            &mint.pubkey(),
+            Rent::default().minimum_balance(Mint::LEN),
+            Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint2(&spl_token::id(), &mint.pubkey(), &payer.pubkey(), None, 6).unwrap(),
+    ];
+    for holder in holders {
+        instructions.push(create_associated_token_account(&payer.pubkey(), holder, &mint.pubkey(), &spl_token::id()));
+        instructions.push(
+            spl_token::instruction::mint_to(
+                &spl_token::id(),
+                &mint.pubkey(),
+                &get_associated_token_address(holder, &mint.pubkey()),
+                &payer.pubkey(),
+                &[],
+                10_000_000,
+            )
+            .unwrap(),
+        );
+    }
+    process(&mut context.banks_client, &instructions, &payer, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+async fn token_balance(banks_client: &mut BanksClient, owner: &Pubkey, mint: &Pubkey) -> u64 {
+    let account = banks_client
+        .get_account(get_associated_token_address(owner, mint))
+        .await
+        .unwrap()
+        .unwrap();
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+#[tokio::test]
+async fn test_token_campaign_withdraw_funds() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, 1_000_000).await;
+    let payer = context.payer.insecure_clone();
+    let mint = create_token_mint(&mut context, &[payer.pubkey(), backer.pubkey()]).await;
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_data = CampaignData {
+        mint: Some(mint.to_string()),
+        ..campaign_data
+    };
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+    let vault = manager.find_vault_address(&campaign).unwrap();
+
+    // The program takes the decimals from the mint
+    let stored = manager.get_campaign(&campaign_key).await.unwrap();
+    assert_eq!(stored.decimals, 6);
+    assert_eq!(stored.mint(), Ok(Some(mint)));
+
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, Some(&mint), 400_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    manager.contribute(&campaign_key, 700_000).await.unwrap();
+    assert_eq!(token_balance(&mut context.banks_client, &vault, &mint).await, 1_100_000);
+    assert_eq!(token_balance(&mut context.banks_client, &backer.pubkey(), &mint).await, 9_600_000);
+    let stored = manager.get_campaign(&campaign_key).await.unwrap();
+    assert_eq!(stored.ui_amount(stored.current_amount), 1.1);
+
+    // Lamport accounts are not accepted in place of the token accounts
+    let ix = manager
+        .withdraw_funds_instruction(&payer.pubkey(), &campaign, None)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_eq!(
+        result.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys),
+    );
+
+    set_clock(&mut context, campaign_data.end_time + 1).await;
+    manager.withdraw_funds(&campaign_key).await.unwrap();
+    assert_eq!(token_balance(&mut context.banks_client, &vault, &mint).await, 0);
+    assert_eq!(token_balance(&mut context.banks_client, &payer.pubkey(), &mint).await, 10_400_000);
+}
+
+#[tokio::test]
+async fn test_token_campaign_refund() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, 1_000_000).await;
+    let payer = context.payer.insecure_clone();
+    let mint = create_token_mint(&mut context, &[backer.pubkey()]).await;
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_data = CampaignData {
+        mint: Some(mint.to_string()),
+        ..campaign_data
+    };
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, Some(&mint), 250_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+
+    set_clock(&mut context, campaign_data.end_time + 1).await;
+    context.get_new_latest_blockhash().await.unwrap();
+    let ix = manager
+        .refund_instruction(&backer.pubkey(), &campaign, Some(&mint))
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+
+    assert_eq!(token_balance(&mut context.banks_client, &backer.pubkey(), &mint).await, 10_000_000);
+    let vault = manager.find_vault_address(&campaign).unwrap();
+    assert_eq!(token_balance(&mut context.banks_client, &vault, &mint).await, 0);
+    assert_eq!(manager.get_campaign(&campaign_key).await.unwrap().current_amount, 0);
+}
+
+#[tokio::test]
+async fn test_campaign_status_lifecycle() {
+    let program_id = Pubkey::new_unique();
+    let (mut context, backer, campaign_data) = funding_test(program_id, LAMPORTS_PER_SOL).await;
+    let payer = context.payer.insecure_clone();
+    let manager = CampaignManager::new(client_for(&program_id, &context.banks_client, &payer));
+    let campaign_data = CampaignData {
+        status: CampaignStatus::Draft,
+        ..campaign_data
+    };
+    let campaign_key = manager.create_campaign(&campaign_data).await.unwrap();
+    let campaign: Pubkey = campaign_key.parse().unwrap();
+
+    // Drafts and paused campaigns turn contributions away
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 100_000)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &backer, &[]).await;
+    assert_custom_error(result, MktError::CampaignInactive);
+
+    manager.resume_campaign(&campaign_key).await.unwrap();
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 200_000)
+        .unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+
+    manager.pause_campaign(&campaign_key).await.unwrap();
+    assert_eq!(manager.get_campaign(&campaign_key).await.unwrap().status, CampaignStatus::Paused);
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &campaign, None, 300_000)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &backer, &[]).await;
+    assert_custom_error(result, MktError::CampaignInactive);
+    manager.update_metrics(&campaign_key, &CampaignMetrics::default()).await.unwrap();
+
+    // Only the authority moves a campaign, and never back to draft
+    let ix = manager
+        .set_status_instruction(&payer.pubkey(), &campaign, CampaignStatus::Draft)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::InvalidStatusTransition);
+    let ix = manager
+        .set_status_instruction(&backer.pubkey(), &campaign, CampaignStatus::Cancelled)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &backer, &[]).await;
+    assert_custom_error(result, MktError::InvalidAuthority);
+
+    // Cancelling is final, and refunds backers before the campaign would have ended
+    manager.cancel_campaign(&campaign_key).await.unwrap();
+    assert!(manager.resume_campaign(&campaign_key).await.is_err());
+    let ix = manager
+        .withdraw_funds_instruction(&payer.pubkey(), &campaign, None)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::CampaignCancelled);
+    let ix = manager.refund_instruction(&backer.pubkey(), &campaign, None).unwrap();
+    process(&mut context.banks_client, &[ix], &backer, &[]).await.unwrap();
+    assert_eq!(manager.get_campaign(&campaign_key).await.unwrap().current_amount, 0);
+
+    // Completed campaigns take neither contributions nor metrics
+    let completed_data = CampaignData {
+        id: "completed".to_string(),
+        status: CampaignStatus::Active,
+        ..campaign_data
+    };
+    let completed = create_campaign(&mut context.banks_client, &manager, &payer, &completed_data)
+        .await
+        .unwrap();
+    manager.complete_campaign(&completed.to_string()).await.unwrap();
+    let ix = manager
+        .update_metrics_instruction(&payer.pubkey(), &completed, &CampaignMetrics::default())
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::CampaignInactive);
+    let ix = manager
+        .contribute_instruction(&backer.pubkey(), &completed, None, 100_000)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &backer, &[]).await;
+    assert_custom_error(result, MktError::CampaignInactive);
+    let ix = manager
+        .set_status_instruction(&payer.pubkey(), &completed, CampaignStatus::Cancelled)
+        .unwrap();
+    let result = process(&mut context.banks_client, &[ix], &payer, &[]).await;
+    assert_custom_error(result, MktError::InvalidStatusTransition);
+}